@@ -0,0 +1,217 @@
+use serde::{Deserialize, Serialize};
+use crate::inventory::ship::ShipType;
+use crate::replay::ReplayEvent;
+use crate::runtime::{GameSummary, Session, ShotStatus};
+
+
+/// Ships a perfect game has to sink, one shot each
+pub const PERFECT_GAME_SHIPS: i32 = 5;
+/// Games a veteran has finished
+pub const VETERAN_GAMES: u32 = 100;
+
+/////////////////////////////////////////////////////////////////////////
+/// Achievements a player unlocks over their games
+/////////////////////////////////////////////////////////////////////////
+///
+///FirstWin, LuckyStrike, PerfectGame, BeatTheClock, Veteran
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Achievement {
+    /// Win a game
+    FirstWin,
+    /// Sink the Patrol Boat with the first shot of a game
+    LuckyStrike,
+    /// Win a game sinking at least `PERFECT_GAME_SHIPS` ships without a single miss
+    PerfectGame,
+    /// Win a game played against a total time limit
+    BeatTheClock,
+    /// Finish `VETERAN_GAMES` games
+    Veteran,
+}
+
+impl Achievement {
+    /// Every achievement, in the order they are listed
+    pub fn all() -> Vec<Achievement> {
+        vec![Achievement::FirstWin, Achievement::LuckyStrike, Achievement::PerfectGame, Achievement::BeatTheClock, Achievement::Veteran]
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Achievement::FirstWin => "First win",
+            Achievement::LuckyStrike => "Lucky strike",
+            Achievement::PerfectGame => "Perfect game",
+            Achievement::BeatTheClock => "Beat the clock",
+            Achievement::Veteran => "Veteran",
+        }
+    }
+
+    pub fn get_description(&self) -> &'static str {
+        match self {
+            Achievement::FirstWin => "Win a game",
+            Achievement::LuckyStrike => "Sink the Patrol Boat with the first shot of a game",
+            Achievement::PerfectGame => "Sink 5 ships in 5 shots",
+            Achievement::BeatTheClock => "Win a game with a time limit",
+            Achievement::Veteran => "Play 100 games",
+        }
+    }
+
+    /// Games needed to unlock the achievement, 1 for the ones earned within a single game
+    pub fn get_target(&self) -> u32 {
+        match self {
+            Achievement::Veteran => VETERAN_GAMES,
+            _ => 1,
+        }
+    }
+}
+
+/// Achievements earned within a single finished game, from its events and its summary
+pub fn evaluate_game(events: &[ReplayEvent], summary: &GameSummary, timed: bool) -> Vec<Achievement> {
+    // the first event that used up a shot, hints do not count
+    let first_shot = events.iter().find(|event| matches!(event, ReplayEvent::Shot(_) | ReplayEvent::Weapon(_) | ReplayEvent::Timeout { .. }));
+    let lucky = matches!(first_shot, Some(ReplayEvent::Shot(record)) if record.status == ShotStatus::Hit && record.ship_type == Some(ShipType::PatrolBoat));
+
+    let checks = [
+        (Achievement::FirstWin, summary.won),
+        (Achievement::LuckyStrike, lucky),
+        (Achievement::PerfectGame, summary.won && summary.ships_sunk >= PERFECT_GAME_SHIPS && summary.shots_fired <= summary.ships_sunk),
+        (Achievement::BeatTheClock, summary.won && timed),
+    ];
+    checks.iter().filter(|(_, earned)| *earned).map(|(achievement, _)| *achievement).collect()
+}
+
+/// An achievement and when it was unlocked, in milliseconds since the unix epoch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnlockedAchievement {
+    pub achievement: Achievement,
+    pub timestamp: u64,
+}
+
+/// What a player sees of an achievement in the achievement list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AchievementStatus {
+    pub achievement: Achievement,
+    /// When the achievement was unlocked, `None` while it is locked
+    pub unlocked_at: Option<u64>,
+    /// Games counted so far towards `target`
+    pub progress: u32,
+    pub target: u32,
+}
+
+/// Achievements of one player and the games counted towards them
+///
+/// ```
+/// use battleship_game_lib::achievements::{Achievement, AchievementRecord};
+/// use battleship_game_lib::runtime::Session;
+///
+/// let session = Session::start(String::from("Adetayo"));
+/// let mut record = AchievementRecord::default();
+/// record.record_game(&session, 1_000);
+/// assert_eq!(1, record.get_games_played());
+/// assert!(!record.is_unlocked(Achievement::Veteran));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AchievementRecord {
+    games_played: u32,
+    unlocked: Vec<UnlockedAchievement>,
+}
+
+impl AchievementRecord {
+    /// Count a finished game and unlock what it earned at `timestamp`.
+    /// Games where a shot was taken back count as played but earn nothing else.
+    /// Return: achievements unlocked by this game
+    pub fn record_game(&mut self, session: &Session, timestamp: u64) -> Vec<Achievement> {
+        self.games_played += 1;
+        let mut earned = if session.is_undo_used() {
+            Vec::new()
+        } else {
+            evaluate_game(&session.get_replay().events, &session.get_summary(), session.get_rules().total_time_limit.is_some())
+        };
+        if self.games_played >= VETERAN_GAMES {
+            earned.push(Achievement::Veteran);
+        }
+
+        earned.retain(|achievement| !self.is_unlocked(*achievement));
+        self.unlocked.extend(earned.iter().map(|achievement| UnlockedAchievement { achievement: *achievement, timestamp }));
+        earned
+    }
+
+    pub fn get_games_played(&self) -> u32 {
+        self.games_played
+    }
+
+    pub fn is_unlocked(&self, achievement: Achievement) -> bool {
+        self.get_unlocked_at(achievement).is_some()
+    }
+
+    pub fn get_unlocked_at(&self, achievement: Achievement) -> Option<u64> {
+        self.unlocked.iter().find(|unlocked| unlocked.achievement == achievement).map(|unlocked| unlocked.timestamp)
+    }
+
+    /// Unlocked achievements in the order they were unlocked
+    pub fn get_unlocked(&self) -> &[UnlockedAchievement] {
+        &self.unlocked
+    }
+
+    /// Every achievement, locked or not, with the progress towards it
+    pub fn list(&self) -> Vec<AchievementStatus> {
+        Achievement::all().into_iter().map(|achievement| {
+            let unlocked_at = self.get_unlocked_at(achievement);
+            let target = achievement.get_target();
+            let progress = match (achievement, unlocked_at) {
+                (_, Some(_)) => target,
+                (Achievement::Veteran, None) => self.games_played.min(target),
+                (_, None) => 0,
+            };
+            AchievementStatus { achievement, unlocked_at, progress, target }
+        }).collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::inventory::grid::Grid;
+    use crate::inventory::ship::Orientation;
+    use crate::replay::ShipPlacement;
+    use crate::rules::GameRules;
+    use crate::runtime::GridPoint;
+
+    use super::*;
+
+    fn row_fleet() -> Grid {
+        let fleet: Vec<ShipPlacement> = [ShipType::AircraftCarrier, ShipType::Battleship, ShipType::Submarine, ShipType::Destroyer, ShipType::PatrolBoat].iter()
+            .enumerate()
+            .map(|(n, ship_type)| ShipPlacement { ship_type: *ship_type, origin: GridPoint { x: 1, y: n as i32 * 2 + 1 }, orientation: Orientation::Horizontal })
+            .collect();
+        Grid::build_from_fleet(&fleet).unwrap()
+    }
+
+    #[test]
+    fn achievements_from_a_game() {
+        let mut session = Session::build_with_rules(String::from("Adetayo"), row_fleet(), GameRules::classic());
+        for y in [9, 1, 3, 5, 7] {
+            session.shoot_ship(GridPoint { x: 1, y });
+        }
+
+        let mut record = AchievementRecord::default();
+        let earned = record.record_game(&session, 1_000);
+        assert_eq!(vec![Achievement::FirstWin, Achievement::LuckyStrike, Achievement::PerfectGame], earned);
+        assert_eq!(Some(1_000), record.get_unlocked_at(Achievement::PerfectGame));
+        // achievements are only unlocked once
+        assert!(record.record_game(&session, 2_000).is_empty());
+        assert_eq!(Some(1_000), record.get_unlocked_at(Achievement::FirstWin));
+
+        let veteran = record.list().into_iter().find(|status| status.achievement == Achievement::Veteran).unwrap();
+        assert_eq!((None, 2, VETERAN_GAMES), (veteran.unlocked_at, veteran.progress, veteran.target));
+    }
+
+    #[test]
+    fn veteran_after_enough_games() {
+        let session = Session::build_with_rules(String::from("Adetayo"), row_fleet(), GameRules::classic());
+        let mut record = AchievementRecord::default();
+        for game in 1..VETERAN_GAMES {
+            assert!(record.record_game(&session, u64::from(game)).is_empty());
+        }
+        assert_eq!(vec![Achievement::Veteran], record.record_game(&session, 100));
+        assert!(record.list().iter().all(|status| status.progress <= status.target));
+    }
+}
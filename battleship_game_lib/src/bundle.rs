@@ -0,0 +1,229 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use crate::achievements::AchievementRecord;
+use crate::campaign::CampaignProgress;
+use crate::save::{self, SaveError, SaveFile};
+use crate::stats::PlayerStats;
+use crate::storage::Score;
+
+
+/// Version written by `Bundle::to_json`, bumped whenever the format changes
+pub const BUNDLE_FORMAT_VERSION: u32 = 1;
+
+/// Everything a store holds, in one file that can be carried to another machine or build.
+///
+/// Saves are signed with a key that never leaves the device, so they travel unsigned. They are
+/// rebuilt from their replays on import and signed again with the key of the importing store.
+/// A high score is only imported if the replay of a save in the bundle or the store scores it,
+/// unless the bundle holds the records a store signed for itself, see `Store::export_records`.
+///
+/// ```
+/// use battleship_game_lib::bundle::{Bundle, MergeStrategy};
+/// use battleship_game_lib::runtime::{GridPoint, Play};
+/// use battleship_game_lib::storage::Store;
+///
+/// let mut play = Play::init(String::from("Adetayo"));
+/// play.get_session_as_mut().shoot_ship(GridPoint::new(3, 4));
+/// let mut store = Store::build();
+/// store.save_play(play);
+///
+/// let bundle = Bundle::from_json(&store.export_bundle().to_json()).unwrap();
+/// let mut other = Store::build();
+/// let report = other.import_bundle(bundle, MergeStrategy::KeepExisting, false).unwrap();
+/// assert_eq!(1, report.saves_added.len());
+/// assert_eq!(1, other.list_saves("Adetayo").len());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bundle {
+    pub format_version: u32,
+    /// Milliseconds since the unix epoch when the bundle was written
+    pub exported_at: u64,
+    pub saves: Vec<SaveFile>,
+    pub scores: Vec<Score>,
+    pub players: Vec<PlayerRecord>,
+    /// Hex encoded HMAC-SHA256 of the bundle without this field, only set on the records a store keeps on its device
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mac: Option<String>,
+}
+
+/// Statistics, achievements and campaign progress of one player
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlayerRecord {
+    pub name: String,
+    pub stats: PlayerStats,
+    pub achievements: AchievementRecord,
+    pub campaigns: Vec<CampaignProgress>,
+}
+
+impl Bundle {
+    /// Export the bundle as pretty printed JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("bundle is always serialisable")
+    }
+
+    /// Sign the bundle with an HMAC of its JSON under `key`, like `SaveFile::sign`
+    pub fn sign(&mut self, key: &[u8]) {
+        self.mac = None;
+        self.mac = Some(save::sign_json(&self.to_json(), key));
+    }
+
+    /// Read a bundle written by `to_json` of a signed bundle, refusing it unless it was signed with `key` and not changed since
+    pub fn load_signed(json: &str, key: &[u8]) -> Result<Bundle, BundleError> {
+        let bundle = Self::from_json(json)?;
+        let mac = bundle.mac.as_deref().ok_or(BundleError::Unsigned)?;
+        if !save::verify_written_json(json, mac, key) {
+            return Err(BundleError::Tampered);
+        }
+        Ok(bundle)
+    }
+
+    /// Read a bundle written by `to_json`. The saves in it are upgraded to the current save format.
+    pub fn from_json(json: &str) -> Result<Bundle, BundleError> {
+        let value: serde_json::Value = serde_json::from_str(json).map_err(|err| BundleError::Parse(err.to_string()))?;
+        let version = value.get("format_version").and_then(serde_json::Value::as_u64).ok_or(BundleError::MissingVersion)? as u32;
+        if version == 0 {
+            return Err(BundleError::MissingVersion);
+        }
+        if version > BUNDLE_FORMAT_VERSION {
+            return Err(BundleError::UnsupportedVersion(version));
+        }
+
+        let saves = value.get("saves").and_then(serde_json::Value::as_array).cloned().unwrap_or_default();
+        let saves = saves.iter().enumerate()
+            .map(|(n, save)| SaveFile::from_json(&save.to_string()).map_err(|err| BundleError::Save { save: n, error: err }))
+            .collect::<Result<Vec<SaveFile>, BundleError>>()?;
+
+        #[derive(Deserialize)]
+        struct Rest {
+            exported_at: u64,
+            scores: Vec<Score>,
+            players: Vec<PlayerRecord>,
+            #[serde(default)]
+            mac: Option<String>,
+        }
+        let rest: Rest = serde_json::from_value(value).map_err(|err| BundleError::Parse(err.to_string()))?;
+        Ok(Bundle { format_version: BUNDLE_FORMAT_VERSION, exported_at: rest.exported_at, saves, scores: rest.scores, players: rest.players, mac: rest.mac })
+    }
+}
+
+/////////
+/// What to do with a save or a player of a bundle that is already in the store with other contents.
+/// Saves and players that are the same on both sides are left as they are.
+/////////
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MergeStrategy {
+    /// Keep what is in the store and skip what conflicts with it
+    KeepExisting,
+    /// Replace what is in the store with what is in the bundle
+    Overwrite,
+    /// Keep both, giving conflicting saves new ids and conflicting players new names
+    KeepBoth,
+}
+
+/// What an import changed, or would change in a dry run
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ImportReport {
+    /// Nothing was changed, this is what the import would have done
+    pub dry_run: bool,
+    /// Ids of the saves added under the id they had in the bundle
+    pub saves_added: Vec<String>,
+    /// Saves added under a new id, as (id in the bundle, new id)
+    pub saves_renumbered: Vec<(String, String)>,
+    /// Ids of the saves replaced by the one in the bundle
+    pub saves_replaced: Vec<String>,
+    /// Ids of the saves left as they were in the store
+    pub saves_skipped: Vec<String>,
+    /// Ids of the saves already in the store as they are in the bundle
+    pub saves_unchanged: Vec<String>,
+    /// Names of the players added
+    pub players_added: Vec<String>,
+    /// Players imported under a new name, as (name in the bundle, new name)
+    pub players_renamed: Vec<(String, String)>,
+    /// Names of the players whose records were replaced by the ones in the bundle
+    pub players_replaced: Vec<String>,
+    /// Names of the players whose records were left as they were in the store
+    pub players_skipped: Vec<String>,
+    /// High scores added, scores already in the store are not added twice
+    pub scores_added: usize,
+    /// High scores left out because no save in the bundle or the store replays to them
+    pub scores_unverified: usize,
+}
+
+impl ImportReport {
+    /// Nothing is, or would be, changed by the import
+    pub fn is_empty(&self) -> bool {
+        self.saves_added.is_empty() && self.saves_renumbered.is_empty() && self.saves_replaced.is_empty()
+            && self.players_added.is_empty() && self.players_renamed.is_empty() && self.players_replaced.is_empty()
+            && self.scores_added == 0
+    }
+}
+
+impl fmt::Display for ImportReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let verb = |done: &'static str, planned: &'static str| if self.dry_run { planned } else { done };
+        if self.scores_unverified > 0 {
+            writeln!(f, "{} {} high scores no save replays to", verb("Left out", "Would leave out"), self.scores_unverified)?;
+        }
+        if self.is_empty() {
+            return writeln!(f, "{}", verb("Nothing was imported", "Nothing would be imported"));
+        }
+
+        for id in self.saves_added.iter() {
+            writeln!(f, "{} save {}", verb("Added", "Would add"), id)?;
+        }
+        for (id, new_id) in self.saves_renumbered.iter() {
+            writeln!(f, "{} save {} as {}", verb("Added", "Would add"), id, new_id)?;
+        }
+        for id in self.saves_replaced.iter() {
+            writeln!(f, "{} save {}", verb("Replaced", "Would replace"), id)?;
+        }
+        for id in self.saves_skipped.iter() {
+            writeln!(f, "{} save {}, it differs from the one here", verb("Skipped", "Would skip"), id)?;
+        }
+        for name in self.players_added.iter() {
+            writeln!(f, "{} player {}", verb("Added", "Would add"), name)?;
+        }
+        for (name, new_name) in self.players_renamed.iter() {
+            writeln!(f, "{} player {} as {}", verb("Added", "Would add"), name, new_name)?;
+        }
+        for name in self.players_replaced.iter() {
+            writeln!(f, "{} the statistics of {}", verb("Replaced", "Would replace"), name)?;
+        }
+        for name in self.players_skipped.iter() {
+            writeln!(f, "{} the statistics of {}, they differ from the ones here", verb("Kept", "Would keep"), name)?;
+        }
+        writeln!(f, "{} {} high scores", verb("Added", "Would add"), self.scores_added)
+    }
+}
+
+/// Reasons a bundle can not be imported, nothing is imported from a bundle with an error
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BundleError {
+    /// The JSON could not be read
+    Parse(String),
+    /// The bundle does not say which version wrote it
+    MissingVersion,
+    /// The bundle was written by a newer version of the game
+    UnsupportedVersion(u32),
+    /// Save number `save` of the bundle can not be loaded
+    Save { save: usize, error: SaveError },
+    /// The bundle was expected to be signed and has no signature
+    Unsigned,
+    /// The signature does not match, the bundle was changed or signed with another key
+    Tampered,
+}
+
+impl fmt::Display for BundleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BundleError::Parse(err) => write!(f, "could not read bundle: {}", err),
+            BundleError::MissingVersion => write!(f, "bundle has no format version"),
+            BundleError::UnsupportedVersion(version) => write!(f, "bundle format version {} is newer than {}, update the game to import it", version, BUNDLE_FORMAT_VERSION),
+            BundleError::Save { save, error } => write!(f, "save {} of the bundle: {}", save + 1, error),
+            BundleError::Unsigned => write!(f, "bundle is not signed"),
+            BundleError::Tampered => write!(f, "bundle was changed after it was written"),
+        }
+    }
+}
+
+impl std::error::Error for BundleError {}
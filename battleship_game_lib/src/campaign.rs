@@ -0,0 +1,241 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use crate::inventory::grid::PlacementError;
+use crate::inventory::ship::ShipType;
+use crate::rules::GameRules;
+use crate::runtime::Session;
+use crate::terrain::{BoardTemplate, TemplateError};
+
+
+/// One level of a campaign: the board, the fleet to sink and the scores to aim for
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Level {
+    pub name: String,
+    /// Size and terrain of the board
+    pub board: BoardTemplate,
+    /// Ships to sink, each type at most once
+    pub fleet: Vec<ShipType>,
+    pub shot_budget: i32,
+    /// Score needed to beat the level, worth one star
+    pub target_score: i32,
+    /// Scores needed for two and for three stars
+    pub star_scores: [i32; 2],
+}
+
+impl Level {
+    /// Classic rules with the shot budget of the level
+    pub fn get_rules(&self) -> GameRules {
+        GameRules { shot_budget: Some(self.shot_budget), target_score: self.target_score, ..GameRules::classic() }
+    }
+
+    /// Start a session of the level with the fleet shuffled from `seed`
+    /// Return: `PlacementError` if the fleet does not fit on the board, levels read with `Campaign::from_json` always fit
+    pub fn start(&self, player_name: String, seed: u64) -> Result<Session, PlacementError> {
+        Session::start_on_board(player_name, &self.board, &self.fleet, seed, self.get_rules())
+    }
+
+    /// Stars (0 - 3) earned by finishing the level with `score`, 0 means the level was not beaten
+    pub fn rate(&self, score: i32) -> u8 {
+        if score < self.target_score {
+            0
+        } else if score < self.star_scores[0] {
+            1
+        } else if score < self.star_scores[1] {
+            2
+        } else {
+            3
+        }
+    }
+
+    fn validate(&self, level: usize) -> Result<(), CampaignError> {
+        self.board.validate().map_err(|err| CampaignError::InvalidBoard(level, err))?;
+        let repeated = self.fleet.iter().enumerate().any(|(n, ship_type)| self.fleet[..n].contains(ship_type));
+        let scores_rise = self.target_score <= self.star_scores[0] && self.star_scores[0] <= self.star_scores[1];
+        if self.fleet.is_empty() || repeated || self.shot_budget <= 0 || !scores_rise {
+            return Err(CampaignError::InvalidLevel(level));
+        }
+        self.board.check_fleet(&self.fleet).map_err(|err| CampaignError::FleetDoesNotFit(level, err))?;
+        Ok(())
+    }
+}
+
+/// Levels played in order, beating a level unlocks the next one
+///
+/// ```
+/// use battleship_game_lib::campaign::{Campaign, CampaignProgress};
+///
+/// let campaign = Campaign::builtin();
+/// let mut progress = CampaignProgress::build(&campaign);
+/// assert!(progress.is_unlocked(0) && !progress.is_unlocked(1));
+///
+/// progress.record(0, campaign.levels[0].rate(campaign.levels[0].target_score));
+/// assert!(progress.is_unlocked(1));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Campaign {
+    pub name: String,
+    pub levels: Vec<Level>,
+}
+
+impl Campaign {
+    /// Campaign shipped with the library
+    pub fn builtin() -> Campaign {
+        Campaign::from_json(include_str!("../campaigns/classic.json")).expect("built-in campaign is valid")
+    }
+
+    /// Read a campaign from JSON, checking every level can be played
+    pub fn from_json(json: &str) -> Result<Campaign, CampaignError> {
+        let campaign: Campaign = serde_json::from_str(json).map_err(|err| CampaignError::Parse(err.to_string()))?;
+        if campaign.levels.is_empty() {
+            return Err(CampaignError::NoLevels);
+        }
+        for (n, level) in campaign.levels.iter().enumerate() {
+            level.validate(n)?;
+        }
+        Ok(campaign)
+    }
+
+    /// Export the campaign as pretty printed JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("campaign is always serialisable")
+    }
+}
+
+/// Best star rating of each level a player has finished
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CampaignProgress {
+    /// Name of the campaign
+    pub campaign: String,
+    stars: Vec<u8>,
+}
+
+impl CampaignProgress {
+    /// Progress of a player who has not played `campaign` yet
+    pub fn build(campaign: &Campaign) -> CampaignProgress {
+        CampaignProgress { campaign: campaign.name.clone(), stars: vec![0; campaign.levels.len()] }
+    }
+
+    /// The first level is always open, every other one once the level before it is beaten
+    pub fn is_unlocked(&self, level: usize) -> bool {
+        level < self.stars.len() && (level == 0 || self.stars[level - 1] > 0)
+    }
+
+    /// Best stars earned on `level`
+    pub fn get_stars(&self, level: usize) -> u8 {
+        self.stars.get(level).copied().unwrap_or(0)
+    }
+
+    /// Stars earned over the whole campaign
+    pub fn get_total_stars(&self) -> u32 {
+        self.stars.iter().map(|stars| u32::from(*stars)).sum()
+    }
+
+    /// Keep `stars` for `level` if they beat the best so far, `true` if they did
+    pub fn record(&mut self, level: usize, stars: u8) -> bool {
+        match self.stars.get_mut(level) {
+            Some(best) if stars > *best => {
+                *best = stars;
+                true
+            },
+            _ => false,
+        }
+    }
+}
+
+/// What a player sees of a level in the level list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LevelStatus {
+    pub level: usize,
+    pub name: String,
+    pub unlocked: bool,
+    pub stars: u8,
+}
+
+/// Reasons a campaign can not be read or a level can not be played
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CampaignError {
+    /// The JSON could not be read
+    Parse(String),
+    /// The campaign has no levels
+    NoLevels,
+    /// The board of a level is not valid
+    InvalidBoard(usize, TemplateError),
+    /// A level has no fleet, repeats a ship, has no shots or its scores do not rise
+    InvalidLevel(usize),
+    /// The fleet of a level does not fit on its board
+    FleetDoesNotFit(usize, PlacementError),
+    /// There is no level with this number
+    NoSuchLevel(usize),
+    /// The level before this one has not been beaten yet
+    Locked(usize),
+    /// No campaign level is being played
+    NoLevelInPlay,
+    /// The level being played is not over yet
+    NotFinished,
+}
+
+impl fmt::Display for CampaignError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CampaignError::Parse(err) => write!(f, "could not read campaign: {}", err),
+            CampaignError::NoLevels => write!(f, "the campaign has no levels"),
+            CampaignError::InvalidBoard(level, err) => write!(f, "level {}: {}", level + 1, err),
+            CampaignError::InvalidLevel(level) => write!(f, "level {} can not be played", level + 1),
+            CampaignError::FleetDoesNotFit(level, err) => write!(f, "level {}: {}", level + 1, err),
+            CampaignError::NoSuchLevel(level) => write!(f, "there is no level {}", level + 1),
+            CampaignError::Locked(level) => write!(f, "level {} is locked", level + 1),
+            CampaignError::NoLevelInPlay => write!(f, "no campaign level is being played"),
+            CampaignError::NotFinished => write!(f, "the level is not over yet"),
+        }
+    }
+}
+
+impl std::error::Error for CampaignError {}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_campaign_levels_start() {
+        let campaign = Campaign::builtin();
+        assert_eq!(Ok(campaign.clone()), Campaign::from_json(&campaign.to_json()));
+        for level in campaign.levels.iter() {
+            let session = level.start(String::from("Adetayo"), 1).unwrap();
+            assert_eq!(level.board.width, session.get_board_view().get_width());
+            assert_eq!(level.fleet.len(), session.get_replay().fleet.len());
+            assert_eq!(level.shot_budget, session.get_remaining_shots());
+        }
+    }
+
+    #[test]
+    fn star_ratings_and_unlocks() {
+        let campaign = Campaign::builtin();
+        let level = &campaign.levels[0];
+        assert_eq!(0, level.rate(level.target_score - 1));
+        assert_eq!(1, level.rate(level.target_score));
+        assert_eq!(3, level.rate(level.star_scores[1]));
+
+        let mut progress = CampaignProgress::build(&campaign);
+        assert!(!progress.record(0, 0));
+        assert!(!progress.is_unlocked(1));
+        assert!(progress.record(0, 2));
+        assert!(!progress.record(0, 1));
+        assert_eq!(2, progress.get_stars(0));
+        assert!(progress.is_unlocked(1));
+        assert!(!progress.is_unlocked(campaign.levels.len()));
+    }
+
+    #[test]
+    fn reject_invalid_campaigns() {
+        assert_eq!(Err(CampaignError::NoLevels), Campaign::from_json(r#"{ "name": "empty", "levels": [] }"#));
+        let json = r#"{ "name": "twice", "levels": [{ "name": "one", "board": { "name": "sea" }, "fleet": ["Destroyer", "Destroyer"],
+            "shot_budget": 10, "target_score": 1, "star_scores": [2, 3] }] }"#;
+        assert_eq!(Err(CampaignError::InvalidLevel(0)), Campaign::from_json(json));
+        let json = r#"{ "name": "cramped", "levels": [{ "name": "one", "board": { "name": "pond", "width": 4, "height": 4 }, "fleet": ["AircraftCarrier"],
+            "shot_budget": 10, "target_score": 1, "star_scores": [2, 3] }] }"#;
+        assert_eq!(Err(CampaignError::FleetDoesNotFit(0, PlacementError::ShipTooLarge(ShipType::AircraftCarrier))), Campaign::from_json(json));
+        assert!(matches!(Campaign::from_json("{"), Err(CampaignError::Parse(_))));
+    }
+}
@@ -0,0 +1,231 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use crate::inventory::{grid::Grid, ship::ShipType};
+use crate::replay::ShipPlacement;
+use crate::rules::GameRules;
+use crate::runtime::{GridPoint, Session, ShotStatus};
+
+
+/// Length in bytes of the random salt of a commitment
+pub const SALT_LEN: usize = 32;
+
+/// Hex encoded SHA-256 of a salted fleet, published before the first shot.
+/// It binds the player to the fleet without showing where any ship is.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FleetCommitment {
+    pub hash: String,
+}
+
+/// Fleet and salt behind a commitment, kept secret until the game is over
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FleetReveal {
+    pub fleet: Vec<ShipPlacement>,
+    /// Hex encoded random salt, so the fleet can not be found by hashing every possible layout
+    pub salt: String,
+}
+
+impl FleetReveal {
+    /// Commitment to this fleet and salt, the same whatever order the ships are listed in
+    pub fn commit(&self) -> FleetCommitment {
+        let mut fleet = self.fleet.clone();
+        fleet.sort_by_key(|placement| placement.ship_type);
+
+        let mut hasher = Sha256::new();
+        hasher.update(self.salt.as_bytes());
+        hasher.update(serde_json::to_string(&fleet).expect("fleet is always serialisable").as_bytes());
+        FleetCommitment { hash: hex::encode(hasher.finalize()) }
+    }
+}
+
+/// Answer to a shot as reported to the opponent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShotReport {
+    pub point: GridPoint,
+    pub status: ShotStatus,
+    /// Ship sunk by the shot
+    pub ship_type: Option<ShipType>,
+}
+
+/// A player's own fleet in a game played on commitments, answering the opponent's shots
+///
+/// ```
+/// use battleship_game_lib::commitment::{self, CommittedFleet};
+/// use battleship_game_lib::inventory::ship::CLASSIC_FLEET;
+/// use battleship_game_lib::runtime::GridPoint;
+///
+/// let mut fleet = CommittedFleet::build(7);
+/// let commitment = fleet.get_commitment();
+/// let reports = vec![fleet.answer(GridPoint::new(3, 4)), fleet.answer(GridPoint::new(5, 5))];
+///
+/// assert!(commitment::verify(&commitment, &fleet.reveal(), &CLASSIC_FLEET, &reports).is_ok());
+/// ```
+#[derive(Debug, Clone)]
+pub struct CommittedFleet {
+    reveal: FleetReveal,
+    /// The opponent's shots at the fleet
+    session: Session,
+}
+
+impl CommittedFleet {
+    /// Classic fleet shuffled from `seed` with a random salt
+    pub fn build(seed: u64) -> CommittedFleet {
+        let fleet = Session::start_with_seed(String::new(), seed, GameRules::unlimited()).get_replay().fleet.clone();
+        let salt: Vec<u8> = (0..SALT_LEN).map(|_| rand::random()).collect();
+        Self::from_fleet(fleet, hex::encode(salt)).expect("shuffled fleets are valid")
+    }
+
+    /// Commit to a fleet placed by the player, `None` if the fleet can not be placed
+    pub fn from_fleet(fleet: Vec<ShipPlacement>, salt: String) -> Option<CommittedFleet> {
+        let grid = Grid::build_from_fleet(&fleet)?;
+        let session = Session::build_with_rules(String::new(), grid, GameRules::unlimited());
+        Some(CommittedFleet { reveal: FleetReveal { fleet, salt }, session })
+    }
+
+    /// Commitment to publish before the first shot
+    pub fn get_commitment(&self) -> FleetCommitment {
+        self.reveal.commit()
+    }
+
+    /// Answer a shot of the opponent
+    pub fn answer(&mut self, point: GridPoint) -> ShotReport {
+        let shot = self.session.shoot_ship(point);
+        ShotReport { point, status: shot.status, ship_type: shot.ship_type }
+    }
+
+    /// Every ship has been sunk
+    pub fn is_sunk(&self) -> bool {
+        !self.session.is_any_ship_left()
+    }
+
+    /// Fleet and salt to hand over once the game is over
+    pub fn reveal(&self) -> FleetReveal {
+        self.reveal.clone()
+    }
+}
+
+/// Check a revealed fleet matches its commitment, is made of the ships of `fleet` the players agreed on,
+/// and that every shot was answered honestly
+pub fn verify(commitment: &FleetCommitment, reveal: &FleetReveal, fleet: &[ShipType], reports: &[ShotReport]) -> Result<(), CommitmentError> {
+    if reveal.commit() != *commitment {
+        return Err(CommitmentError::HashMismatch);
+    }
+    let mut revealed: Vec<ShipType> = reveal.fleet.iter().map(|placement| placement.ship_type).collect();
+    let mut agreed = fleet.to_vec();
+    revealed.sort();
+    agreed.sort();
+    if revealed != agreed {
+        return Err(CommitmentError::InvalidFleet);
+    }
+
+    let mut fleet = CommittedFleet::from_fleet(reveal.fleet.clone(), reveal.salt.clone()).ok_or(CommitmentError::InvalidFleet)?;
+    for (n, report) in reports.iter().enumerate() {
+        let honest = fleet.answer(report.point);
+        if honest != *report {
+            return Err(CommitmentError::WrongAnswer { shot: n, honest });
+        }
+    }
+    Ok(())
+}
+
+/// Reasons a revealed fleet is refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommitmentError {
+    /// The fleet or the salt is not the one committed to
+    HashMismatch,
+    /// The fleet is not the one agreed on, or it overlaps, repeats a ship or leaves the grid
+    InvalidFleet,
+    /// Shot number `shot` was answered with something other than `honest`
+    WrongAnswer { shot: usize, honest: ShotReport },
+}
+
+impl fmt::Display for CommitmentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CommitmentError::HashMismatch => write!(f, "the revealed fleet is not the one committed to"),
+            CommitmentError::InvalidFleet => write!(f, "the revealed fleet is not the agreed fleet or can not be placed"),
+            CommitmentError::WrongAnswer { shot, honest } => write!(f, "shot {} at {} should have been answered {:?}", shot + 1, honest.point, honest.status),
+        }
+    }
+}
+
+impl std::error::Error for CommitmentError {}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::inventory::ship::{Orientation, CLASSIC_FLEET};
+
+    use super::*;
+
+    /// Every square of the board, row by row
+    fn every_square() -> Vec<GridPoint> {
+        (1..=10).flat_map(|y| (1..=10).map(move |x| GridPoint { x, y })).collect()
+    }
+
+    #[test]
+    fn honest_game_verifies() {
+        let mut fleets = [CommittedFleet::build(1), CommittedFleet::build(2)];
+        let commitments = [fleets[0].get_commitment(), fleets[1].get_commitment()];
+        assert_ne!(commitments[0], commitments[1]);
+
+        // the players take turns firing row by row at the other fleet until one is sunk
+        let mut reports: [Vec<ShotReport>; 2] = [Vec::new(), Vec::new()];
+        for point in every_square() {
+            for player in 0..2 {
+                reports[player].push(fleets[player].answer(point));
+            }
+            if fleets.iter().any(|fleet| fleet.is_sunk()) {
+                break;
+            }
+        }
+
+        for player in 0..2 {
+            assert_eq!(Ok(()), verify(&commitments[player], &fleets[player].reveal(), &CLASSIC_FLEET, &reports[player]));
+        }
+    }
+
+    #[test]
+    fn cheating_is_caught() {
+        let mut fleet = CommittedFleet::build(3);
+        let commitment = fleet.get_commitment();
+        let mut reports: Vec<ShotReport> = every_square().into_iter().take(30).map(|point| fleet.answer(point)).collect();
+        let reveal = fleet.reveal();
+
+        // a hit reported as a miss
+        let hit = reports.iter().position(|report| report.status == ShotStatus::Hit).unwrap();
+        reports[hit] = ShotReport { status: ShotStatus::Miss, ship_type: None, ..reports[hit] };
+        assert!(matches!(verify(&commitment, &reveal, &CLASSIC_FLEET, &reports), Err(CommitmentError::WrongAnswer { shot, .. }) if shot == hit));
+
+        // a ship moved after committing, or a different salt
+        let mut moved = reveal.clone();
+        moved.fleet.reverse();
+        assert_eq!(commitment, moved.commit());
+        moved.fleet[0].origin.x += 1;
+        assert_eq!(Err(CommitmentError::HashMismatch), verify(&commitment, &moved, &CLASSIC_FLEET, &[]));
+        let resalted = FleetReveal { salt: String::from("00"), ..reveal };
+        assert_eq!(Err(CommitmentError::HashMismatch), verify(&commitment, &resalted, &CLASSIC_FLEET, &[]));
+    }
+
+    #[test]
+    fn oversized_fleet_is_refused() {
+        // a flagship added to the classic fleet wherever it fits
+        let classic = CommittedFleet::build(4).reveal().fleet;
+        let mut oversized = classic.clone();
+        let flagship = every_square().into_iter()
+            .map(|origin| ShipPlacement { ship_type: ShipType::Flagship, origin, orientation: Orientation::Horizontal })
+            .find(|placement| Grid::build_from_fleet(&[classic.clone(), vec![*placement]].concat()).is_some())
+            .unwrap();
+        oversized.push(flagship);
+
+        let mut fleet = CommittedFleet::from_fleet(oversized, String::from("00")).unwrap();
+        let commitment = fleet.get_commitment();
+        let reports: Vec<ShotReport> = every_square().into_iter().take(30).map(|point| fleet.answer(point)).collect();
+        assert_eq!(Err(CommitmentError::InvalidFleet), verify(&commitment, &fleet.reveal(), &CLASSIC_FLEET, &reports));
+        assert_eq!(Ok(()), verify(&commitment, &fleet.reveal(), &[CLASSIC_FLEET.as_slice(), &[ShipType::Flagship]].concat(), &reports));
+
+        // or one ship short
+        let short = CommittedFleet::from_fleet(classic[1..].to_vec(), String::from("00")).unwrap();
+        assert_eq!(Err(CommitmentError::InvalidFleet), verify(&short.get_commitment(), &short.reveal(), &CLASSIC_FLEET, &[]));
+    }
+}
@@ -2,37 +2,34 @@
 use wasm_bindgen::prelude::*;
 use std::collections::HashMap;
 use std::fmt;
-use rand::{thread_rng, Rng};
+use rand::{rngs::StdRng, seq::SliceRandom, thread_rng, Rng, SeedableRng};
+use crate::replay::ShipPlacement;
 use crate::runtime::{GridPoint, Shot, ShotStatus};
+use crate::rules::DistanceMetric;
+use crate::terrain::{BoardTemplate, CellKind};
+use crate::view::{BoardView, CellState};
 
-use super::ship::{Orientation, Ship, ShipType};
+use super::ship::{Orientation, Ship, ShipDesign, ShipType};
 
+/// Random squares tried for each ship before a shuffle gives up
+pub const MAX_PLACEMENT_ATTEMPTS: usize = 10_000;
 
 /// Square
 ///
 ///
 /// ```
-/// 
-///
-/// pub struct Square {
-///   origin: GridPoint,
-///   ship: Option<ShipType>
-/// }  
-///
-/// impl fmt::Display for Square {
-///     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-///         write!(f, "Square (origin = {}, ship = {})", self.origin, self.get_ship_string())
-///     }
-/// }
+/// use battleship_game_lib::inventory::grid::Square;
+/// use battleship_game_lib::runtime::GridPoint;
 ///
 /// let square = Square::build(GridPoint{x: 1, y: 2});
-///
+/// assert_eq!("Square (origin = GridPoint(x = 1, y = 2), ship = None)", square.to_string());
 /// ```
 #[cfg_attr(feature = "wasm", wasm_bindgen)]
 #[derive(Debug,Clone,Copy)]
 pub struct Square {
     origin: GridPoint,
-    ship: Option<ShipType>
+    ship: Option<ShipType>,
+    kind: CellKind
 }
 
 impl fmt::Display for Square {
@@ -58,12 +55,14 @@ impl Square {
     /// # Examples
     ///
     /// ```
-    /// 
-    /// use inventory::Square;
+    /// use battleship_game_lib::inventory::grid::Square;
+    /// use battleship_game_lib::runtime::GridPoint;
+    ///
     /// let square = Square::build(GridPoint{x: 1, y: 2});
+    /// assert!(!square.has_ship());
     /// ```
     pub fn build(origin: GridPoint) -> Square {
-        Square { origin, ship: None }
+        Square { origin, ship: None, kind: CellKind::Water }
     }
 
     /// Set origin of square
@@ -86,69 +85,139 @@ impl Square {
         self.ship.is_some()
     }
 
+    /// Get what the square is made of
+    pub fn get_kind(&self) -> CellKind {
+        self.kind
+    }
+
     /// Get ShipType string display on square
     pub fn get_ship_string(&self) -> String {
         match &self.ship {
             Some(ship) => format!("{:?}",ship),
-            None => format!("None"),
+            None => "None".to_string(),
         }
     }
 }
 
-/// Grid representation with a 10 by 10, 2 dimensional array as layout
+/// Grid representation, 10 by 10 unless a board template sets another size.
+/// The layout holds the squares in row-major order.
 ///
 ///
 /// ```
-/// 
-///
-/// pub struct Grid {
-///   layout: [Square; 100],
-///   ships: HashMap<ShipType,Ship>
-/// }  
-///
-///
+/// use battleship_game_lib::inventory::grid::Grid;
 ///
 /// let grid = Grid::build();
-///
+/// assert_eq!((10, 10), (grid.get_width(), grid.get_height()));
 /// ```
 #[cfg_attr(feature = "wasm", wasm_bindgen)]
 #[derive(Debug, Clone)]
 pub struct Grid {
-    layout: [Square; 100],
-    ships: HashMap<ShipType,Ship>
+    layout: Vec<Square>,
+    width: i32,
+    height: i32,
+    ships: HashMap<ShipType,Ship>,
+    template: Option<BoardTemplate>
 }
 
 impl Grid {
     /// Generate a blank grid
     pub fn build() -> Grid {
-        Self::initialize_layout()
+        Self::initialize_layout(10, 10)
     }
 
     /// Generate a grid from layout (a previously saved session)
     /// Argument: `layout: [[Square; 10]; 10]``
     pub fn build_from_layout(layout: [Square; 100]) -> Grid {
-        Grid { layout , ships: Ship::create_ships()}
+        Grid { layout: layout.to_vec(), width: 10, height: 10, ships: Ship::create_ships(), template: None }
+    }
+
+    /// Generate a blank grid with the size and terrain of a board template
+    /// Argument: `template: &BoardTemplate`
+    pub fn build_from_template(template: &BoardTemplate) -> Grid {
+        let mut grid = Self::initialize_layout(template.width, template.height);
+        for point in template.islands.iter().chain(template.reefs.iter()) {
+            grid.get_square_mut(*point).kind = template.get_cell_kind(*point);
+        }
+        grid.template = Some(template.clone());
+        grid
     }
 
     /// Hit ship
     /// Argument: `grid_point: GridPoint`
-    /// Return: `Shot`
+    /// Return: `Shot`, with `ShotStatus::OffBoard` for a point outside the grid
     pub fn hit_ship(&mut self, grid_point: GridPoint) -> Shot {
-        let square = self.layout[Self::get_index(Self::get_arr_pos(grid_point.x), Self::get_arr_pos(grid_point.y))];
+        if !self.is_on_grid(grid_point) {
+            return Shot{ status: ShotStatus::OffBoard, ship_type: None, point: None, distance: None };
+        }
+        let square = *self.get_square(grid_point);
         if square.has_ship(){
             let ship_type = square.ship.unwrap();
             let ship = self.ships.get(&ship_type).unwrap();
-            return self.remove_ship(ship_type, grid_point, ship.orientation.unwrap(), ship.get_size());
+            return self.remove_ship(ship_type, ship.origin.unwrap(), ship.orientation.unwrap());
+        }
+        Shot{ status: ShotStatus::Miss, ship_type: None, point: None, distance: None }
+    }
+
+    /// Put a sunk ship back where it was, undoing `hit_ship`
+    /// Argument: `ship_type: ShipType`
+    /// Return: `false` if the ship is not sunk or was never placed
+    pub fn restore_ship(&mut self, ship_type: ShipType) -> bool {
+        let ship = self.ships.get(&ship_type).unwrap();
+        if !ship.is_destroyed() {
+            return false;
+        }
+        match (ship.origin, ship.orientation) {
+            (Some(origin), Some(orientation)) => {
+                self.add_ship(ship_type, origin, orientation);
+                self.ships.get_mut(&ship_type).unwrap().repair();
+                true
+            },
+            _ => false,
+        }
+    }
+
+    /// Distance from the point to the nearest square holding a ship still afloat
+    /// Argument: `grid_point: GridPoint`, `metric: DistanceMetric`
+    /// Return: `None` when no ship is left
+    pub fn distance_to_nearest_ship(&self, grid_point: GridPoint, metric: DistanceMetric) -> Option<i32> {
+        (1..=self.get_height())
+            .flat_map(|y| (1..=self.get_width()).map(move |x| GridPoint { x, y }))
+            .filter(|square| self.get_square(*square).has_ship())
+            .map(|square| metric.distance(grid_point, square))
+            .min()
+    }
+
+    /// Get what the square at the point is made of
+    /// Argument: `grid_point: GridPoint`
+    /// Return: `None` for a point outside the grid
+    pub fn get_cell_kind(&self, grid_point: GridPoint) -> Option<CellKind> {
+        if !self.is_on_grid(grid_point) {
+            return None;
+        }
+        Some(self.get_square(grid_point).get_kind())
+    }
+
+    /// Get every island and reef square
+    pub fn get_terrain_squares(&self) -> Vec<GridPoint> {
+        match &self.template {
+            Some(template) => template.islands.iter().chain(template.reefs.iter()).copied().collect(),
+            None => Vec::new(),
         }
-        Shot{ status: ShotStatus::Miss, ship_type: None, point: None }
+    }
+
+    /// Get the board template the grid was built from, `None` for open sea
+    pub fn get_template(&self) -> Option<&BoardTemplate> {
+        self.template.as_ref()
     }
 
     /// Get ship
     /// Argument: `grid_point: GridPoint`
-    /// Return: `Option<ShipType>`
+    /// Return: `Option<ShipType>`, `None` for a point outside the grid
     pub fn get_ship(&self, grid_point: GridPoint) -> Option<ShipType> {
-        let square = self.get_square(grid_point);
-        square.get_ship()
+        if !self.is_on_grid(grid_point) {
+            return None;
+        }
+        self.get_square(grid_point).get_ship()
     }
 
     /// Get ship locations
@@ -163,6 +232,45 @@ impl Grid {
     }
 
 
+    /// Build a renderable view of the grid
+    /// Argument: `shots: &[GridPoint]` squares fired at so far, `reveal_ships: bool` show ships still afloat
+    /// Return: `BoardView`
+    pub fn build_view(&self, shots: &[GridPoint], reveal_ships: bool) -> BoardView {
+        let mut view = BoardView::build(self.get_width(), self.get_height());
+        // terrain is charted, so it is always shown
+        for square in self.get_terrain_squares() {
+            match self.get_square(square).get_kind() {
+                CellKind::Island => view.set_cell(square, CellState::Island),
+                CellKind::Reef => view.set_cell(square, CellState::Reef),
+                CellKind::Water => (),
+            }
+        }
+        // sunk ships first, ships afloat may since have moved over their wrecks
+        let mut ships: Vec<&Ship> = self.ships.values().collect();
+        ships.sort_by_key(|ship| !ship.is_destroyed());
+
+        for ship in ships {
+            let state = match (ship.is_destroyed(), reveal_ships) {
+                (true, _) => CellState::Sunk,
+                (false, true) => CellState::Ship,
+                (false, false) => continue,
+            };
+            for square in ship.get_squares() {
+                view.set_cell(square, state);
+            }
+        }
+
+        for shot in shots {
+            let state = match view.get_cell(*shot) {
+                Some(CellState::Sunk) => CellState::Hit,
+                _ => CellState::Miss,
+            };
+            view.set_cell(*shot, state);
+        }
+
+        view
+    }
+
     /// Is any ship left
     pub fn is_any_ship_left(&self) -> bool{
         for (_key, ship) in self.ships.iter() {
@@ -173,163 +281,294 @@ impl Grid {
         false
     }
 
+    /// Get ships that have not been destroyed yet
+    pub fn get_remaining_ships(&self) -> Vec<Ship>{
+        self.ships.values().filter(|&ship| !ship.is_destroyed()).cloned().collect()
+    }
+
     /// Get destroyed ships
     pub fn get_destroyed_ships(&self) -> Vec<Ship>{
-        let ships: Vec<Ship> = self.ships.values().cloned().filter(|ship| ship.is_destroyed()).collect();
+        let ships: Vec<Ship> = self.ships.values().filter(|&ship| ship.is_destroyed()).cloned().collect();
         ships
     }
 
     /// Shuffle ship locations randomly on the grid
-    pub fn shuffle_ship_location<'a>(&'a mut self){
-        for (_key, ship) in self.ships.clone().iter_mut() {
-            let mut rng = thread_rng();
-            let mut x_axis = rng.gen_range(1..=10);
-            let mut y_axis = rng.gen_range(1..=10);
-            let mut orientation: Orientation = rand::random();
-            
-            loop {
-                if self.verify_allocation(GridPoint{ x: x_axis, y: y_axis }, orientation, ship.get_size()) {
-                    self.add_ship(ship.get_type(), GridPoint{ x: x_axis, y: y_axis }, orientation, ship.get_size());
-                    break;
-                }
-
-                x_axis = rng.gen_range(1..=10);
-                y_axis = rng.gen_range(1..=10);
-                orientation = rand::random();
+    /// Return: `PlacementError` if the fleet does not fit, some ships may be placed already
+    pub fn shuffle_ship_location(&mut self) -> Result<(), PlacementError> {
+        self.shuffle_with(&mut thread_rng())
+    }
+
+    /// Shuffle ship locations on the grid, the same seed always gives the same layout
+    /// Return: `PlacementError` if the fleet does not fit, some ships may be placed already
+    pub fn shuffle_ship_location_seeded(&mut self, seed: u64) -> Result<(), PlacementError> {
+        self.shuffle_with(&mut StdRng::seed_from_u64(seed))
+    }
+
+    /// Check every ship fits on the grid in some orientation and the fleet is no larger than the open water
+    pub fn check_fleet_fits(&self) -> Result<(), PlacementError> {
+        let mut ships: Vec<&Ship> = self.ships.values().collect();
+        ships.sort_by_key(|ship| ship.get_type());
+        for ship in ships.iter() {
+            let fits = ship.get_rotations().iter().any(|cells| {
+                cells.iter().all(|(x, y)| *x < self.get_width() && *y < self.get_height())
+            });
+            if !fits {
+                return Err(PlacementError::ShipTooLarge(ship.get_type()));
             }
         }
-        
+
+        let needed: usize = ships.iter().map(|ship| ship.get_size() as usize).sum();
+        let open = self.layout.iter().filter(|square| square.kind == CellKind::Water && !square.has_ship()).count();
+        if needed > open {
+            return Err(PlacementError::NotEnoughWater { needed, open });
+        }
+        Ok(())
     }
 
+    /// Generate a blank grid holding a custom fleet, each ship type at most once
+    /// Argument: `ship_types: &[ShipType]`
+    /// The ships still have to be placed, with `shuffle_ship_location` for example
+    pub fn build_with_fleet(ship_types: &[ShipType]) -> Grid {
+        let mut grid = Grid::build();
+        grid.set_fleet(ship_types);
+        grid
+    }
+
+    /// Replace the ships of a blank grid with a custom fleet, each ship type at most once
+    /// Argument: `ship_types: &[ShipType]`
+    pub fn set_fleet(&mut self, ship_types: &[ShipType]) {
+        self.ships = Ship::create_fleet(ship_types);
+    }
+
+    /// Replace the ships of a blank grid with ships of any shape, see `Ship::with_shape`
+    /// Argument: `ships: Vec<Ship>` each ship type at most once, a later ship of a type replaces an earlier one
+    pub fn set_ships(&mut self, ships: Vec<Ship>) {
+        self.ships = ships.into_iter().map(|ship| (ship.get_type(), ship)).collect();
+    }
+
+    /// Get the design of every ship that is not the standard ship of its type, ordered by ship type
+    pub fn get_designs(&self) -> Vec<ShipDesign> {
+        let mut designs: Vec<ShipDesign> = self.ships.values()
+            .map(Ship::get_design)
+            .filter(|design| *design != Ship::build(design.ship_type).get_design())
+            .collect();
+        designs.sort_by_key(|design| design.ship_type);
+        designs
+    }
+
+    /// Generate a grid from a fleet layout (a recorded replay)
+    /// Argument: `fleet: &[ShipPlacement]`
+    /// Only the ships in the layout are on the grid
+    /// Return: `None` if a ship is repeated, overlaps another or leaves the grid
+    pub fn build_from_fleet(fleet: &[ShipPlacement]) -> Option<Grid> {
+        Self::build_from_fleet_on(None, fleet)
+    }
+
+    /// Generate a grid from a fleet layout on the terrain of `template`, open sea if `None`
+    /// Return: `None` if a ship is repeated, overlaps another, leaves the grid or sits on terrain
+    pub fn build_from_fleet_on(template: Option<&BoardTemplate>, fleet: &[ShipPlacement]) -> Option<Grid> {
+        Self::build_from_designs_on(template, &[], fleet)
+    }
+
+    /// Generate a grid from a fleet layout whose ships of a type in `designs` take that shape
+    /// Return: `None` if a design is not a valid shape, or a ship is repeated, overlaps another, leaves the grid or sits on terrain
+    pub fn build_from_designs_on(template: Option<&BoardTemplate>, designs: &[ShipDesign], fleet: &[ShipPlacement]) -> Option<Grid> {
+        let mut grid = match template {
+            Some(template) => Grid::build_from_template(template),
+            None => Grid::build(),
+        };
+        grid.ships.clear();
+
+        for placement in fleet {
+            let ship = match designs.iter().find(|design| design.ship_type == placement.ship_type) {
+                Some(design) => Ship::from_design(design).ok()?,
+                None => Ship::build(placement.ship_type),
+            };
+            if grid.ships.contains_key(&placement.ship_type) || !grid.verify_allocation(&ship.get_squares_at(placement.origin, placement.orientation)) {
+                return None;
+            }
+            grid.ships.insert(placement.ship_type, ship);
+            grid.add_ship(placement.ship_type, placement.origin, placement.orientation);
+        }
 
-    fn get_square<'a>(&self, grid_point: GridPoint) -> &Square {
-        &self.layout[Self::get_index(Self::get_arr_pos(grid_point.x), Self::get_arr_pos(grid_point.y))]
-    }
+        Some(grid)
+    }
+
+    /// Get the placement of every ship on the grid, ordered by ship type
+    pub fn get_fleet_layout(&self) -> Vec<ShipPlacement> {
+        let mut fleet: Vec<ShipPlacement> = self.ships.values()
+            .filter_map(|ship| Some(ShipPlacement { ship_type: ship.get_type(), origin: ship.origin?, orientation: ship.orientation? }))
+            .collect();
+        fleet.sort_by_key(|placement| placement.ship_type);
+        fleet
+    }
+
+    /// Movement phase: every ship still afloat moves one square or rotates about its origin,
+    /// picked at random among the moves that pass the placement checks and avoid `fired`.
+    /// A ship with no such move stays where it is.
+    /// Return: the new placement of every ship that moved
+    pub fn move_ships<R: Rng>(&mut self, rng: &mut R, fired: &[GridPoint]) -> Vec<ShipPlacement> {
+        let mut ships = self.get_remaining_ships();
+        ships.sort_by_key(|ship| ship.get_type());
+        let mut moved = Vec::new();
+
+        for ship in ships {
+            let (origin, orientation) = match (ship.origin, ship.orientation) {
+                (Some(origin), Some(orientation)) => (origin, orientation),
+                _ => continue,
+            };
+            let rotated = orientation.rotate();
+            let mut moves = vec![
+                (GridPoint { x: origin.x + 1, y: origin.y }, orientation),
+                (GridPoint { x: origin.x - 1, y: origin.y }, orientation),
+                (GridPoint { x: origin.x, y: origin.y + 1 }, orientation),
+                (GridPoint { x: origin.x, y: origin.y - 1 }, orientation),
+            ];
+            if ship.get_cells(rotated) != ship.get_cells(orientation) {
+                moves.push((origin, rotated));
+            }
+            moves.shuffle(rng);
+
+            // lift the ship off the grid so it does not block its own move
+            for square in ship.get_squares() {
+                self.get_square_mut(square).ship = None;
+            }
+
+            let target = moves.into_iter().find(|(point, orientation)| {
+                let squares = ship.get_squares_at(*point, *orientation);
+                self.verify_allocation(&squares) && !squares.iter().any(|square| fired.contains(square))
+            });
+
+            match target {
+                Some((point, orientation)) => {
+                    self.add_ship(ship.get_type(), point, orientation);
+                    moved.push(ShipPlacement { ship_type: ship.get_type(), origin: point, orientation });
+                },
+                None => { self.add_ship(ship.get_type(), origin, orientation); },
+            }
+        }
 
-    fn set_square(&mut self, square: Square) {
-        self.layout[Self::get_index(Self::get_arr_pos(square.origin.x), Self::get_arr_pos(square.origin.y))] = square;
+        moved
     }
 
+    fn shuffle_with<R: Rng>(&mut self, rng: &mut R) -> Result<(), PlacementError> {
+        self.check_fleet_fits()?;
+        // place ships in a fixed order so a seeded generator always gives the same layout
+        let mut ships: Vec<Ship> = self.ships.values().cloned().collect();
+        ships.sort_by_key(|ship| ship.get_type());
 
-    fn initialize_layout() -> Grid{
-        let mut layout: [Square;100] = [Square::default(); 100];
-        for (n, cell) in layout.iter_mut().enumerate() {
-            cell.set_gridpoint(GridPoint { x: Self::get_grid_pos(Self::cal_pos_x(n)), y: Self::get_grid_pos(Self::cal_pos_y(n)) })
-            
+        for ship in ships {
+            let placed = (0..MAX_PLACEMENT_ATTEMPTS).any(|_| {
+                let origin = GridPoint{ x: rng.gen_range(1..=self.get_width()), y: rng.gen_range(1..=self.get_height()) };
+                let orientation: Orientation = rng.gen();
+                self.verify_allocation(&ship.get_squares_at(origin, orientation)) && self.add_ship(ship.get_type(), origin, orientation)
+            });
+            if !placed {
+                return Err(PlacementError::NoRoom(ship.get_type()));
+            }
         }
-        
-        Self::build_from_layout(layout)
+        Ok(())
     }
 
-    fn get_index(row: usize, column: usize) -> usize {
-        row * 10 + column
+
+    /// Is the point inside the grid
+    pub fn is_on_grid(&self, grid_point: GridPoint) -> bool {
+        (1..=self.get_width()).contains(&grid_point.x) && (1..=self.get_height()).contains(&grid_point.y)
     }
 
-    fn cal_pos_y(i: usize) -> usize {
-        i/10
+    /// Number of columns on the grid
+    pub fn get_width(&self) -> i32 {
+        self.width
     }
 
-    fn cal_pos_x(i: usize) -> usize {
-        i%10
+    /// Number of rows on the grid
+    pub fn get_height(&self) -> i32 {
+        self.height
     }
 
-    fn get_arr_pos(axis: i32) -> usize {
-        (axis - 1).try_into().unwrap()
+    fn get_square(&self, grid_point: GridPoint) -> &Square {
+        &self.layout[self.get_index(grid_point)]
     }
 
-    fn get_grid_pos(axis: usize) -> i32 {
-        (axis + 1).try_into().unwrap()
+    fn get_square_mut(&mut self, grid_point: GridPoint) -> &mut Square {
+        let index = self.get_index(grid_point);
+        &mut self.layout[index]
     }
 
-    fn verify_allocation(&self, grid_point: GridPoint, orientation: Orientation, size: i32) -> bool {
-        match orientation {
-            Orientation::Horizontal => {
-                if (grid_point.x + size) > 10 {
-                    return false;
-                }
 
-                for length in 0..size {
-                    let grid = GridPoint { x: grid_point.x + length, y: grid_point.y };
-                    let square = self.get_square(grid);
-                    if square.has_ship() {
-                        return false;
-                    }
-                }
-            },
-            Orientation::Vertical => {
-                if (grid_point.y + size) > 10 {
-                    return false
-                }
-
-                for length in 0..size {
-                    let grid = GridPoint { x: grid_point.x, y: grid_point.y + length };
-                    let square = self.get_square(grid);
-                    if square.has_ship() {
-                        return false;
-                    }
-                }
-            },
-        }
+    fn initialize_layout(width: i32, height: i32) -> Grid{
+        let layout: Vec<Square> = (1..=height)
+            .flat_map(|y| (1..=width).map(move |x| Square::build(GridPoint { x, y })))
+            .collect();
 
-        true
+        Grid { layout, width, height, ships: Ship::create_ships(), template: None }
+    }
+
+    /// Index of the square in the layout, points outside the grid must be refused before they get here
+    fn get_index(&self, grid_point: GridPoint) -> usize {
+        assert!(self.is_on_grid(grid_point), "{} is not on the {}x{} grid", grid_point, self.width, self.height);
+        ((grid_point.y - 1) * self.width + (grid_point.x - 1)) as usize
+    }
+
+    ///Can a ship cover every one of `squares`, they must be open water with no ship on them
+    fn verify_allocation(&self, squares: &[GridPoint]) -> bool {
+        squares.iter().all(|square| {
+            self.is_on_grid(*square) && !self.get_square(*square).has_ship() && self.get_square(*square).kind == CellKind::Water
+        })
     }
 
     ///Add a ship to the grid
-    fn add_ship(&mut self, ship_type: ShipType, grid_point: GridPoint, orientation: Orientation, size: i32) -> bool {
-        match orientation {
-            Orientation::Horizontal => {
-                for length in 0..size {
-                    let grid = GridPoint { x: grid_point.x + length, y: grid_point.y };
-                    let square = Square{ origin: grid, ship: Some(ship_type) };
-                    self.set_square(square);
-                }
-            },
-            Orientation::Vertical => {
-                for length in 0..size {
-                    let grid = GridPoint { x: grid_point.x, y: grid_point.y + length };
-                    let square = Square{ origin: grid, ship: Some(ship_type) };
-                    self.set_square(square);
-                }
-            },
+    fn add_ship(&mut self, ship_type: ShipType, grid_point: GridPoint, orientation: Orientation) -> bool {
+        let ship = self.ships.get_mut(&ship_type).unwrap();
+        let squares = ship.get_squares_at(grid_point, orientation);
+        ship.origin = Some(grid_point);
+        ship.orientation = Some(orientation);
+
+        for grid in squares {
+            self.get_square_mut(grid).ship = Some(ship_type);
         }
-        self.ships.get_mut(&ship_type).unwrap().origin = Some(grid_point);
-        self.ships.get_mut(&ship_type).unwrap().orientation = Some(orientation);
         true
     }
 
     ///Remove a ship from the grid
-    fn remove_ship<'a>(&'a mut self, ship_type: ShipType, grid_point: GridPoint, orientation: Orientation, size: i32) -> Shot {
-        match orientation {
-            Orientation::Horizontal => {
-
-                for length in 0..size {
-                    let grid = GridPoint { x: grid_point.x + length, y: grid_point.y };
-                    let square = self.get_square(grid);
-                    if !square.has_ship() || square.get_ship().unwrap() != ship_type {
-                        return Shot{ status: ShotStatus::Miss, ship_type: None, point: None};
-                    }
-
-                    self.set_square(Square{ origin: grid, ship: None });
-                }
-            },
-            Orientation::Vertical => {
-
-                for length in 0..size {
-                    let grid = GridPoint { x: grid_point.x, y: grid_point.y + length };
-                    let square = self.get_square(grid);
-                    if !square.has_ship() || square.get_ship().unwrap() != ship_type {
-                        return Shot{ status: ShotStatus::Miss, ship_type: None, point: None};
-                    }
+    fn remove_ship(&mut self, ship_type: ShipType, grid_point: GridPoint, orientation: Orientation) -> Shot {
+        let squares = self.ships[&ship_type].get_squares_at(grid_point, orientation);
+        for grid in squares {
+            let square = self.get_square(grid);
+            if !square.has_ship() || square.get_ship().unwrap() != ship_type {
+                return Shot{ status: ShotStatus::Miss, ship_type: None, point: None, distance: None };
+            }
 
-                    self.set_square(Square{ origin: grid, ship: None });
-                }
-            },
+            self.get_square_mut(grid).ship = None;
         }
         self.ships.get_mut(&ship_type).unwrap().destroy();
-        Shot{ status: ShotStatus::Hit, ship_type: Some(ship_type), point: Some(self.ships.get(&ship_type).unwrap().get_point()) }
+        Shot{ status: ShotStatus::Hit, ship_type: Some(ship_type), point: Some(self.ships.get(&ship_type).unwrap().get_point()), distance: None }
+    }
+}
+
+/// Reasons a fleet can not be shuffled onto a grid
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlacementError {
+    /// The ship does not fit on the grid whichever way it is turned
+    ShipTooLarge(ShipType),
+    /// The ships cover more squares than the grid has open water
+    NotEnoughWater { needed: usize, open: usize },
+    /// No free squares were found for the ship in `MAX_PLACEMENT_ATTEMPTS` tries
+    NoRoom(ShipType),
+}
+
+impl fmt::Display for PlacementError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PlacementError::ShipTooLarge(ship_type) => write!(f, "the {} does not fit on the board", Ship::build(*ship_type).get_name()),
+            PlacementError::NotEnoughWater { needed, open } => write!(f, "the fleet needs {} squares but the board has {} of open water", needed, open),
+            PlacementError::NoRoom(ship_type) => write!(f, "no room was found for the {}", Ship::build(*ship_type).get_name()),
+        }
     }
 }
 
+impl std::error::Error for PlacementError {}
+
 
 #[cfg(test)]
 mod tests {
@@ -342,7 +581,7 @@ mod tests {
         let mut grid = Grid::build();
         let mut ship = Ship::build(ShipType::AircraftCarrier);
         ship.origin = Some(GridPoint { x: 1, y: 1 });
-        grid.add_ship(ship.get_type(),GridPoint { x: 1, y: 1 }, Orientation::Horizontal, ship.get_size());
+        grid.add_ship(ship.get_type(),GridPoint { x: 1, y: 1 }, Orientation::Horizontal);
         let hit_ship = grid.hit_ship(ship.origin.unwrap());
         assert_eq!(ShotStatus::Hit,hit_ship.status)
     }
@@ -352,7 +591,7 @@ mod tests {
         let mut grid = Grid::build();
         let mut ship = Ship::build(ShipType::AircraftCarrier);
         ship.origin = Some(GridPoint { x: 1, y: 1 });
-        grid.add_ship(ship.get_type(),GridPoint { x: 1, y: 1 }, Orientation::Horizontal, ship.get_size());
+        grid.add_ship(ship.get_type(),GridPoint { x: 1, y: 1 }, Orientation::Horizontal);
         let hit_ship = grid.hit_ship(GridPoint { x: 1 , y: 2 });
         assert_eq!(ShotStatus::Miss,hit_ship.status)
     }
@@ -362,8 +601,8 @@ mod tests {
         let mut grid = Grid::build();
         let mut ship = Ship::build(ShipType::AircraftCarrier);
         ship.origin = Some(GridPoint { x: 1, y: 1 });
-        grid.add_ship(ship.get_type(),GridPoint { x: 1, y: 1 }, Orientation::Horizontal, ship.get_size());
-        let ship_removed = grid.remove_ship(ship.get_type(),GridPoint { x: 1, y: 1 }, Orientation::Horizontal, ship.get_size());
+        grid.add_ship(ship.get_type(),GridPoint { x: 1, y: 1 }, Orientation::Horizontal);
+        let ship_removed = grid.remove_ship(ship.get_type(),GridPoint { x: 1, y: 1 }, Orientation::Horizontal);
         assert_eq!(ship.get_type(),ship_removed.ship_type.unwrap())
     }
 
@@ -372,12 +611,26 @@ mod tests {
         let mut grid = Grid::build();
         let mut ship = Ship::build(ShipType::AircraftCarrier);
         ship.origin = Some(GridPoint { x: 1, y: 1 });
-        grid.add_ship(ship.get_type(),GridPoint { x: 1, y: 1 }, Orientation::Horizontal, ship.get_size());
-        grid.remove_ship(ship.get_type(),GridPoint { x: 1, y: 1 }, Orientation::Horizontal, ship.get_size());
-        let ship_removed = grid.remove_ship(ship.get_type(),GridPoint { x: 1, y: 1 }, Orientation::Horizontal, ship.get_size());
+        grid.add_ship(ship.get_type(),GridPoint { x: 1, y: 1 }, Orientation::Horizontal);
+        grid.remove_ship(ship.get_type(),GridPoint { x: 1, y: 1 }, Orientation::Horizontal);
+        let ship_removed = grid.remove_ship(ship.get_type(),GridPoint { x: 1, y: 1 }, Orientation::Horizontal);
         assert_eq!(ShotStatus::Miss,ship_removed.status)
     }
 
+    #[test]
+    fn restore_ship_on_grid() {
+        let mut grid = Grid::build();
+        grid.add_ship(ShipType::Destroyer, GridPoint { x: 3, y: 3 }, Orientation::Vertical);
+        assert!(!grid.restore_ship(ShipType::Destroyer));
+
+        grid.hit_ship(GridPoint { x: 3, y: 4 });
+        assert_eq!(1, grid.get_destroyed_ships().len());
+        assert!(grid.restore_ship(ShipType::Destroyer));
+        assert!(grid.get_destroyed_ships().is_empty());
+        assert_eq!(Some(ShipType::Destroyer), grid.get_ship(GridPoint { x: 3, y: 3 }));
+        assert_eq!(Some(ShipType::Destroyer), grid.get_ship(GridPoint { x: 3, y: 4 }));
+    }
+
     #[test]
     fn add_mul_ships() {
         let mut grid = Grid::build();
@@ -387,21 +640,195 @@ mod tests {
         ac.orientation = Some(Orientation::Horizontal);
         bat.origin = Some(GridPoint { x: 1, y: 2 });
         bat.orientation = Some(Orientation::Horizontal);
-        grid.add_ship(ac.get_type(),GridPoint { x: 1, y: 1 }, Orientation::Horizontal, ac.get_size());
-        grid.add_ship(bat.get_type(),GridPoint { x: 1, y: 2 }, Orientation::Horizontal, bat.get_size());
+        grid.add_ship(ac.get_type(),GridPoint { x: 1, y: 1 }, Orientation::Horizontal);
+        grid.add_ship(bat.get_type(),GridPoint { x: 1, y: 2 }, Orientation::Horizontal);
         println!("{}", ac.get_debug_mode_string());
         println!("{}", bat.get_debug_mode_string());
         let hit_ship = grid.hit_ship(ac.origin.unwrap());
         assert_eq!(ac.get_type(), hit_ship.ship_type.unwrap())
     }
 
+    #[test]
+    fn hit_ship_away_from_origin() {
+        let mut grid = Grid::build();
+        let ship = Ship::build(ShipType::Battleship);
+        grid.add_ship(ship.get_type(),GridPoint { x: 2, y: 3 }, Orientation::Vertical);
+        let hit_ship = grid.hit_ship(GridPoint { x: 2, y: 5 });
+        assert_eq!(ShotStatus::Hit,hit_ship.status);
+        assert!(!grid.get_square(GridPoint { x: 2, y: 3 }).has_ship());
+        assert!(!grid.get_square(GridPoint { x: 2, y: 6 }).has_ship());
+    }
+
+    #[test]
+    fn build_view_of_grid() {
+        let mut grid = Grid::build();
+        grid.add_ship(ShipType::Destroyer, GridPoint { x: 1, y: 1 }, Orientation::Horizontal);
+        grid.add_ship(ShipType::PatrolBoat, GridPoint { x: 5, y: 5 }, Orientation::Horizontal);
+        grid.hit_ship(GridPoint { x: 2, y: 1 });
+        let shots = [GridPoint { x: 2, y: 1 }, GridPoint { x: 9, y: 9 }];
+
+        let view = grid.build_view(&shots, false);
+        assert_eq!(Some(CellState::Sunk), view.get_cell(GridPoint { x: 1, y: 1 }));
+        assert_eq!(Some(CellState::Hit), view.get_cell(GridPoint { x: 2, y: 1 }));
+        assert_eq!(Some(CellState::Miss), view.get_cell(GridPoint { x: 9, y: 9 }));
+        assert_eq!(Some(CellState::Unknown), view.get_cell(GridPoint { x: 5, y: 5 }));
+
+        let view = grid.build_view(&shots, true);
+        assert_eq!(Some(CellState::Ship), view.get_cell(GridPoint { x: 5, y: 5 }));
+    }
+
+    #[test]
+    fn hit_ship_off_grid() {
+        let mut grid = Grid::build();
+        grid.add_ship(ShipType::Destroyer, GridPoint { x: 1, y: 2 }, Orientation::Horizontal);
+        // (11, 1) would be the square after (10, 1) in the layout, the first square of the destroyer
+        assert_eq!(ShotStatus::OffBoard, grid.hit_ship(GridPoint { x: 11, y: 1 }).status);
+        assert_eq!(ShotStatus::OffBoard, grid.hit_ship(GridPoint { x: 1, y: 11 }).status);
+        assert_eq!(ShotStatus::OffBoard, grid.hit_ship(GridPoint { x: 0, y: 3 }).status);
+        assert_eq!((None, None), (grid.get_ship(GridPoint { x: 11, y: 1 }), grid.get_cell_kind(GridPoint { x: 1, y: 11 })));
+        assert!(grid.get_destroyed_ships().is_empty());
+    }
+
     #[test]
     fn shuffle_ship() {
         let mut grid = Grid::build();
-        grid.shuffle_ship_location();
+        grid.shuffle_ship_location().unwrap();
         println!("{}",grid.display_ships_location());
         //assert_eq!(false,ship_removed)
     }
+
+    #[test]
+    fn shuffle_ship_seeded() {
+        let mut first = Grid::build();
+        let mut second = Grid::build();
+        first.shuffle_ship_location_seeded(42).unwrap();
+        second.shuffle_ship_location_seeded(42).unwrap();
+        assert_eq!(first.get_fleet_layout(), second.get_fleet_layout());
+        assert_eq!(5, first.get_fleet_layout().len());
+    }
+
+    #[test]
+    fn shuffle_fleet_that_does_not_fit() {
+        let small = BoardTemplate { width: 4, height: 4, ..BoardTemplate::open_sea() };
+        let mut grid = Grid::build_from_template(&small);
+        grid.set_fleet(&[ShipType::AircraftCarrier]);
+        assert_eq!(Err(PlacementError::ShipTooLarge(ShipType::AircraftCarrier)), grid.shuffle_ship_location_seeded(1));
+
+        grid.set_fleet(&[ShipType::Battleship, ShipType::Submarine, ShipType::Destroyer, ShipType::Cruiser, ShipType::Frigate, ShipType::PatrolBoat]);
+        assert_eq!(Err(PlacementError::NotEnoughWater { needed: 18, open: 16 }), grid.check_fleet_fits());
+
+        // the battleship fits the board, but islands on the diagonal cut every row and column short
+        let islands = (1..=4).map(|n| GridPoint { x: n, y: n }).collect();
+        let mut grid = Grid::build_from_template(&BoardTemplate { islands, ..small });
+        grid.set_fleet(&[ShipType::Battleship]);
+        assert!(grid.check_fleet_fits().is_ok());
+        assert_eq!(Err(PlacementError::NoRoom(ShipType::Battleship)), grid.shuffle_ship_location_seeded(1));
+    }
+
+    #[test]
+    fn build_from_fleet_layout() {
+        let mut grid = Grid::build();
+        grid.shuffle_ship_location().unwrap();
+
+        let rebuilt = Grid::build_from_fleet(&grid.get_fleet_layout()).unwrap();
+        assert_eq!(grid.display_ships_location().len(), rebuilt.display_ships_location().len());
+        assert_eq!(grid.get_fleet_layout(), rebuilt.get_fleet_layout());
+    }
+
+    #[test]
+    fn move_ships_on_grid() {
+        let fleet = [
+            ShipPlacement { ship_type: ShipType::Destroyer, origin: GridPoint { x: 5, y: 5 }, orientation: Orientation::Horizontal },
+            ShipPlacement { ship_type: ShipType::PatrolBoat, origin: GridPoint { x: 1, y: 1 }, orientation: Orientation::Horizontal },
+        ];
+        let mut grid = Grid::build_from_fleet(&fleet).unwrap();
+        // the patrol boat is boxed in by shots and can not move
+        let fired = [GridPoint { x: 2, y: 1 }, GridPoint { x: 1, y: 2 }];
+
+        let moved = grid.move_ships(&mut StdRng::seed_from_u64(3), &fired);
+        assert_eq!(1, moved.len());
+        assert_eq!(ShipType::Destroyer, moved[0].ship_type);
+        assert_eq!(moved, grid.get_fleet_layout().into_iter().filter(|placement| placement.ship_type == ShipType::Destroyer).collect::<Vec<_>>());
+        assert_eq!(Some(ShipType::PatrolBoat), grid.get_ship(GridPoint { x: 1, y: 1 }));
+
+        let squares: Vec<GridPoint> = (1..=10).flat_map(|y| (1..=10).map(move |x| GridPoint { x, y })).collect();
+        let occupied = squares.iter().filter(|square| grid.get_ship(**square) == Some(ShipType::Destroyer)).count();
+        assert_eq!(2, occupied);
+    }
+
+    #[test]
+    fn polyomino_ships_on_grid() {
+        let mut grid = Grid::build_with_fleet(&[ShipType::Frigate, ShipType::Flagship]);
+        grid.add_ship(ShipType::Frigate, GridPoint { x: 1, y: 1 }, Orientation::Vertical);
+        // the flagship's bounding box overlaps the frigate's, but none of its squares do
+        assert!(grid.verify_allocation(&Ship::build(ShipType::Flagship).get_squares_at(GridPoint { x: 2, y: 3 }, Orientation::Horizontal)));
+        assert!(!grid.verify_allocation(&Ship::build(ShipType::Flagship).get_squares_at(GridPoint { x: 1, y: 2 }, Orientation::Horizontal)));
+        assert!(!grid.verify_allocation(&Ship::build(ShipType::Flagship).get_squares_at(GridPoint { x: 9, y: 5 }, Orientation::Horizontal)));
+
+        assert_eq!(Some(ShipType::Frigate), grid.get_ship(GridPoint { x: 1, y: 2 }));
+        assert_eq!(None, grid.get_ship(GridPoint { x: 1, y: 1 }));
+        let shot = grid.hit_ship(GridPoint { x: 2, y: 3 });
+        assert_eq!(Some(ShipType::Frigate), shot.ship_type);
+        assert!(grid.get_remaining_ships().iter().all(|ship| ship.get_type() == ShipType::Flagship));
+        assert!((1..=3).all(|y| grid.get_ship(GridPoint { x: 2, y }).is_none()));
+
+        let mut grid = Grid::build_with_fleet(&[ShipType::Cruiser, ShipType::Frigate, ShipType::Flagship, ShipType::PatrolBoat]);
+        grid.shuffle_ship_location_seeded(5).unwrap();
+        let rebuilt = Grid::build_from_fleet(&grid.get_fleet_layout()).unwrap();
+        assert_eq!(grid.get_fleet_layout(), rebuilt.get_fleet_layout());
+        assert_eq!(4, grid.get_fleet_layout().len());
+    }
+
+    #[test]
+    fn distance_to_ship() {
+        let mut grid = Grid::build_with_fleet(&[ShipType::Destroyer]);
+        grid.add_ship(ShipType::Destroyer, GridPoint { x: 5, y: 5 }, Orientation::Horizontal);
+        assert_eq!(Some(5), grid.distance_to_nearest_ship(GridPoint { x: 2, y: 3 }, DistanceMetric::Manhattan));
+        assert_eq!(Some(3), grid.distance_to_nearest_ship(GridPoint { x: 2, y: 3 }, DistanceMetric::Chebyshev));
+        assert_eq!(Some(1), grid.distance_to_nearest_ship(GridPoint { x: 7, y: 5 }, DistanceMetric::Manhattan));
+
+        grid.hit_ship(GridPoint { x: 5, y: 5 });
+        assert_eq!(None, grid.distance_to_nearest_ship(GridPoint { x: 2, y: 3 }, DistanceMetric::Manhattan));
+    }
+
+    #[test]
+    fn terrain_on_grid() {
+        let template = BoardTemplate::builtin("strait").unwrap();
+        let mut grid = Grid::build_from_template(&template);
+        assert_eq!(Some(CellKind::Island), grid.get_cell_kind(GridPoint { x: 1, y: 4 }));
+        assert_eq!(Some(CellKind::Reef), grid.get_cell_kind(GridPoint { x: 2, y: 5 }));
+        assert_eq!(10, grid.get_terrain_squares().len());
+        assert!(!grid.verify_allocation(&[GridPoint { x: 1, y: 3 }, GridPoint { x: 1, y: 4 }]));
+        assert!(!grid.verify_allocation(&[GridPoint { x: 2, y: 5 }]));
+
+        grid.shuffle_ship_location_seeded(9).unwrap();
+        let ships: Vec<GridPoint> = grid.get_fleet_layout().iter().flat_map(|placement| Ship::build(placement.ship_type).get_squares_at(placement.origin, placement.orientation)).collect();
+        assert!(ships.iter().all(|square| grid.get_cell_kind(*square) == Some(CellKind::Water)));
+        assert_eq!(Some(&template), grid.get_template());
+
+        let view = grid.build_view(&[GridPoint { x: 2, y: 5 }], false);
+        assert_eq!(Some(CellState::Island), view.get_cell(GridPoint { x: 10, y: 7 }));
+        assert_eq!(Some(CellState::Reef), view.get_cell(GridPoint { x: 9, y: 6 }));
+        assert_eq!(Some(CellState::Miss), view.get_cell(GridPoint { x: 2, y: 5 }));
+
+        let fleet = [ShipPlacement { ship_type: ShipType::Destroyer, origin: GridPoint { x: 1, y: 3 }, orientation: Orientation::Vertical }];
+        assert!(Grid::build_from_fleet(&fleet).is_some());
+        assert!(Grid::build_from_fleet_on(Some(&template), &fleet).is_none());
+    }
+
+    #[test]
+    fn build_from_fleet_rejects_overlap() {
+        let fleet = [
+            ShipPlacement { ship_type: ShipType::Battleship, origin: GridPoint { x: 1, y: 1 }, orientation: Orientation::Horizontal },
+            ShipPlacement { ship_type: ShipType::Destroyer, origin: GridPoint { x: 2, y: 1 }, orientation: Orientation::Vertical },
+        ];
+        assert!(Grid::build_from_fleet(&fleet).is_none());
+
+        let fleet = [ShipPlacement { ship_type: ShipType::AircraftCarrier, origin: GridPoint { x: 6, y: 1 }, orientation: Orientation::Horizontal }];
+        assert!(Grid::build_from_fleet(&fleet).is_some());
+        let fleet = [ShipPlacement { ship_type: ShipType::AircraftCarrier, origin: GridPoint { x: 7, y: 1 }, orientation: Orientation::Horizontal }];
+        assert!(Grid::build_from_fleet(&fleet).is_none());
+    }
 }
 
 
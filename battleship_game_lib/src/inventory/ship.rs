@@ -1,29 +1,62 @@
 #[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
-use std::{fmt, collections::HashMap};
+use std::{fmt, collections::{HashMap, HashSet}};
 use rand::{
     distributions::{Distribution, Standard},
     Rng,
 };
+use serde::{Deserialize, Serialize};
 use crate::runtime::GridPoint;
 
 /////////////////////////////////////////////////////////////////////////
 /// Orientation of ship
 /////////////////////////////////////////////////////////////////////////
 ///
-///Horizontal, Vertical
+///Horizontal, Vertical, HorizontalReversed, VerticalReversed
+///
+///The four rotations of a ship shape, each a quarter turn clockwise from the one before
 #[cfg_attr(feature = "wasm", wasm_bindgen)]
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Orientation {
+    /// The shape as it is defined
     Horizontal,
+    /// A quarter turn clockwise
     Vertical,
+    /// A half turn
+    HorizontalReversed,
+    /// Three quarter turns clockwise
+    VerticalReversed,
+}
+
+impl Orientation {
+    /// The next orientation, a quarter turn clockwise
+    pub fn rotate(&self) -> Orientation {
+        match self {
+            Orientation::Horizontal => Orientation::Vertical,
+            Orientation::Vertical => Orientation::HorizontalReversed,
+            Orientation::HorizontalReversed => Orientation::VerticalReversed,
+            Orientation::VerticalReversed => Orientation::Horizontal,
+        }
+    }
+
+    /// Number of quarter turns clockwise from `Orientation::Horizontal`
+    fn quarter_turns(&self) -> usize {
+        match self {
+            Orientation::Horizontal => 0,
+            Orientation::Vertical => 1,
+            Orientation::HorizontalReversed => 2,
+            Orientation::VerticalReversed => 3,
+        }
+    }
 }
 
 impl Distribution<Orientation> for Standard {
     fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Orientation {
-        match rng.gen_range(0..=1) {
+        match rng.gen_range(0..=3) {
             0 => Orientation::Horizontal,
             1 => Orientation::Vertical,
+            2 => Orientation::HorizontalReversed,
+            3 => Orientation::VerticalReversed,
             _ => Orientation::Horizontal
         }
     }
@@ -33,17 +66,28 @@ impl Distribution<Orientation> for Standard {
 /// Types of Ship
 /////////////////////////////////////////////////////////////////////////
 ///
-///AircraftCarrier, Battleship, Submarine, Destroyer, PatrolBoat
+///AircraftCarrier, Battleship, Submarine, Destroyer, PatrolBoat, Cruiser, Frigate, Flagship
+///
+///The first five are the classic straight ships, the others are only found in custom fleets
 #[cfg_attr(feature = "wasm", wasm_bindgen)]
-#[derive(Debug,Clone,Copy,PartialEq, Eq, Hash)]
+#[derive(Debug,Clone,Copy,PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum ShipType {
     AircraftCarrier,
     Battleship,
     Submarine,
     Destroyer,
-    PatrolBoat
+    PatrolBoat,
+    /// L shaped, four squares
+    Cruiser,
+    /// T shaped, four squares
+    Frigate,
+    /// Plus shaped, five squares
+    Flagship,
 }
 
+/// The five straight ships of the classic game
+pub const CLASSIC_FLEET: [ShipType; 5] = [ShipType::AircraftCarrier, ShipType::Battleship, ShipType::Submarine, ShipType::Destroyer, ShipType::PatrolBoat];
+
 /////////////////////////////////////////////////////////////////////////
 ///Ship
 /////////////////////////////////////////////////////////////////////////
@@ -51,7 +95,7 @@ pub enum ShipType {
 ///Fields
 ///pub origin: Option<GridPoint>,
 ///pub orientation: Option<Orientation>,
-///size: i32,
+///shape: Vec<(i32, i32)>,
 ///points: i32,
 ///name: String,
 ///ship_type: ShipType,
@@ -61,7 +105,8 @@ pub enum ShipType {
 pub struct Ship {
     pub origin: Option<GridPoint>,
     pub orientation: Option<Orientation>,
-    size: i32,
+    /// Offsets (x, y) of every square from the origin, in the `Orientation::Horizontal` rotation
+    shape: Vec<(i32, i32)>,
     points: i32,
     name: String,
     ship_type: ShipType,
@@ -78,19 +123,83 @@ impl Ship {
     /// # Examples
     ///
     /// ```
-    /// // You can have rust code between fences inside the comments
-    /// // If you pass --test to `rustdoc`, it will even test it for you!
-    /// use inventory::Ship;
+    /// use battleship_game_lib::inventory::ship::{Ship, ShipType};
+    ///
     /// let ship = Ship::build(ShipType::AircraftCarrier);
+    /// assert_eq!(5, ship.get_size());
     /// ```
     pub fn build(ship_type: ShipType) -> Ship {
         match ship_type {
-            ShipType::AircraftCarrier => Ship { ship_type, origin: None, orientation: None, size: 5, points: 2, name:String::from("Aircraft Carrier"), destroyed: false},
-            ShipType::Battleship => Ship { ship_type, origin: None, orientation: None, size: 4, points: 4, name: String::from("Battleship"), destroyed: false},
-            ShipType::Submarine => Ship { ship_type, origin: None, orientation: None, size: 3, points: 6, name: String::from("Submarine"), destroyed: false },
-            ShipType::Destroyer => Ship { ship_type, origin: None, orientation: None, size: 2, points: 8, name: String::from("Destroyer"), destroyed: false},
-            ShipType::PatrolBoat => Ship { ship_type, origin: None, orientation: None, size: 1, points: 10, name: String::from("Patrol Boat"), destroyed: false},
+            ShipType::AircraftCarrier => Ship::build_with_shape(ship_type, Self::straight(5), 2, "Aircraft Carrier"),
+            ShipType::Battleship => Ship::build_with_shape(ship_type, Self::straight(4), 4, "Battleship"),
+            ShipType::Submarine => Ship::build_with_shape(ship_type, Self::straight(3), 6, "Submarine"),
+            ShipType::Destroyer => Ship::build_with_shape(ship_type, Self::straight(2), 8, "Destroyer"),
+            ShipType::PatrolBoat => Ship::build_with_shape(ship_type, Self::straight(1), 10, "Patrol Boat"),
+            ShipType::Cruiser => Ship::build_with_shape(ship_type, vec![(0, 0), (0, 1), (0, 2), (1, 2)], 4, "Cruiser"),
+            ShipType::Frigate => Ship::build_with_shape(ship_type, vec![(0, 0), (1, 0), (2, 0), (1, 1)], 4, "Frigate"),
+            ShipType::Flagship => Ship::build_with_shape(ship_type, vec![(1, 0), (0, 1), (1, 1), (2, 1), (1, 2)], 2, "Flagship"),
+        }
+    }
+
+    /// Returns a ship of any shape, taking the place of `ship_type` in the fleet
+    ///
+    /// # Arguments
+    ///
+    /// * `shape` - Offsets (x, y) of every square, side by side with no square repeated
+    /// * `points` - Points for sinking the ship
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use battleship_game_lib::inventory::ship::{Ship, ShipType, ShapeError};
+    ///
+    /// let ship = Ship::with_shape(ShipType::Cruiser, vec![(0, 0), (1, 0), (1, 1), (2, 1)], 6, "Zigzag").unwrap();
+    /// assert_eq!(4, ship.get_size());
+    /// assert_eq!(Err(ShapeError::Disconnected), Ship::with_shape(ShipType::Cruiser, vec![(0, 0), (2, 0)], 6, "Gap"));
+    /// ```
+    pub fn with_shape(ship_type: ShipType, shape: Vec<(i32, i32)>, points: i32, name: &str) -> Result<Ship, ShapeError> {
+        let first = *shape.first().ok_or(ShapeError::Empty)?;
+        let mut cells = HashSet::new();
+        for cell in shape.iter() {
+            if !cells.insert(*cell) {
+                return Err(ShapeError::DuplicateCell(*cell));
+            }
+        }
+
+        // walk from the first square to every square next to one already reached
+        let mut reached = HashSet::from([first]);
+        let mut pending = vec![first];
+        while let Some((x, y)) = pending.pop() {
+            for next in [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)] {
+                if cells.contains(&next) && reached.insert(next) {
+                    pending.push(next);
+                }
+            }
+        }
+        if reached.len() != cells.len() {
+            return Err(ShapeError::Disconnected);
         }
+
+        Ok(Ship::build_with_shape(ship_type, shape, points, name))
+    }
+
+    /// Returns the ship described by `design`, checked like `with_shape`
+    pub fn from_design(design: &ShipDesign) -> Result<Ship, ShapeError> {
+        Ship::with_shape(design.ship_type, design.shape.clone(), design.points, &design.name)
+    }
+
+    /// Shape, points and name of the ship, without its place on the grid
+    pub fn get_design(&self) -> ShipDesign {
+        ShipDesign { ship_type: self.ship_type, name: self.name.clone(), shape: self.shape.clone(), points: self.points }
+    }
+
+    fn build_with_shape(ship_type: ShipType, shape: Vec<(i32, i32)>, points: i32, name: &str) -> Ship {
+        Ship { ship_type, origin: None, orientation: None, shape, points, name: String::from(name), destroyed: false }
+    }
+
+    ///a straight ship is a 1 by `size` shape
+    fn straight(size: i32) -> Vec<(i32, i32)> {
+        (0..size).map(|x| (x, 0)).collect()
     }
 
     pub fn get_point(&self) -> i32 {
@@ -105,6 +214,10 @@ impl Ship {
         self.destroyed = true;
     }
 
+    pub fn repair(&mut self) {
+        self.destroyed = false;
+    }
+
     ///returns orientation if given or defaults to horizontal
     pub fn get_orientation(&self) -> &Orientation {
         match &self.orientation {
@@ -129,9 +242,9 @@ impl Ship {
         }
     }
 
-    ///get size of ship
+    ///get size of ship, the number of squares it covers
     pub fn get_size(&self) -> i32 {
-        self.size
+        self.shape.len() as i32
     }
 
     ///get name of ship
@@ -144,59 +257,104 @@ impl Ship {
         self.ship_type
     }
 
+    ///get the squares the ship covers, empty if the ship has not been placed
+    pub fn get_squares(&self) -> Vec<GridPoint> {
+        match (&self.origin, &self.orientation) {
+            (Some(origin), Some(orientation)) => self.get_squares_at(*origin, *orientation),
+            _ => Vec::new(),
+        }
+    }
+
+    ///get the squares the ship would cover with its origin at `origin`, turned to `orientation`
+    pub fn get_squares_at(&self, origin: GridPoint, orientation: Orientation) -> Vec<GridPoint> {
+        self.get_cells(orientation).into_iter().map(|(x, y)| GridPoint { x: origin.x + x, y: origin.y + y }).collect()
+    }
+
+    ///get the offsets of the shape turned to `orientation`, moved so the smallest x and y are 0.
+    ///The origin is the top left corner of the box around the shape, which may be empty water.
+    pub fn get_cells(&self, orientation: Orientation) -> Vec<(i32, i32)> {
+        let mut cells = self.shape.clone();
+        for _ in 0..orientation.quarter_turns() {
+            cells = cells.into_iter().map(|(x, y)| (-y, x)).collect();
+        }
+        let min_x = cells.iter().map(|(x, _)| *x).min().unwrap_or(0);
+        let min_y = cells.iter().map(|(_, y)| *y).min().unwrap_or(0);
+        let mut cells: Vec<(i32, i32)> = cells.into_iter().map(|(x, y)| (x - min_x, y - min_y)).collect();
+        cells.sort_by_key(|(x, y)| (*y, *x));
+        cells
+    }
+
+    ///get every distinct way the shape can be turned, a straight ship has two and a plus only one
+    pub fn get_rotations(&self) -> Vec<Vec<(i32, i32)>> {
+        let mut rotations: Vec<Vec<(i32, i32)>> = Vec::new();
+        let mut orientation = Orientation::Horizontal;
+        for _ in 0..4 {
+            let cells = self.get_cells(orientation);
+            if !rotations.contains(&cells) {
+                rotations.push(cells);
+            }
+            orientation = orientation.rotate();
+        }
+        rotations
+    }
+
     ///get debug mode string of the ship
     pub fn get_debug_mode_string(&self) -> String {
         let mut output = format!("{}  ", &self.name);
-        match &self.orientation {
-            Some(o) => match o {
-                Orientation::Horizontal => output.push_str(&&self.get_horizontal_dms()),
-                Orientation::Vertical => output.push_str(&self.get_vertical_dms()),
-            },
-            None => (),
+        for square in self.get_squares() {
+            output.push_str(&format!("({},{}) ", square.x, square.y));
         }
 
         output
     }
 
     pub fn create_ships() -> HashMap<ShipType, Self> {
-        let mut ship_yard = HashMap::new();
-        
-        ship_yard.insert(ShipType::AircraftCarrier, Ship::build(ShipType::AircraftCarrier));
-        ship_yard.insert(ShipType::Battleship, Ship::build(ShipType::Battleship));
-        ship_yard.insert(ShipType::Submarine, Ship::build(ShipType::Submarine));
-        ship_yard.insert(ShipType::Destroyer, Ship::build(ShipType::Destroyer));
-        ship_yard.insert(ShipType::PatrolBoat, Ship::build(ShipType::PatrolBoat));
-
-        ship_yard
+        Self::create_fleet(&CLASSIC_FLEET)
     }
 
-    ///get ship with horizontal orientation debug mode string
-    fn get_horizontal_dms(&self) -> String {
-        let mut output = String::new();
-
-        for x in 0..self.size {
-            output.push_str(&format!("({},{}) ", &self.get_origin_x() + x, &self.get_origin_y()))
-        }
-
-        output
+    ///create the ships of a custom fleet, each type at most once
+    pub fn create_fleet(ship_types: &[ShipType]) -> HashMap<ShipType, Self> {
+        ship_types.iter().map(|ship_type| (*ship_type, Ship::build(*ship_type))).collect()
     }
+}
 
-    ///get ship with vertical orientation debug mode string
-    fn get_vertical_dms(&self) -> String {
-        let mut output = String::new();
+/// A ship of any shape as it is written into a replay, see `Ship::with_shape`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ShipDesign {
+    pub ship_type: ShipType,
+    pub name: String,
+    /// Offsets (x, y) of every square in the `Orientation::Horizontal` rotation
+    pub shape: Vec<(i32, i32)>,
+    pub points: i32,
+}
 
-        for y in 0..self.size {
-            output.push_str(format!("({},{}) ", &self.get_origin_x(), &self.get_origin_y() + y).as_str())
-        }
+/// Why a shape can not be a ship
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShapeError {
+    /// The shape has no squares
+    Empty,
+    /// The square is in the shape more than once
+    DuplicateCell((i32, i32)),
+    /// Some squares are not side by side with the rest
+    Disconnected,
+}
 
-        output
+impl fmt::Display for ShapeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShapeError::Empty => write!(f, "ship shape has no squares"),
+            ShapeError::DuplicateCell((x, y)) => write!(f, "ship shape has the square ({},{}) more than once", x, y),
+            ShapeError::Disconnected => write!(f, "ship shape is not in one piece"),
+        }
     }
 }
 
+impl std::error::Error for ShapeError {}
+
 ///ship cli display
 impl fmt::Display for Ship {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Ship(name = {}, starting point = {}, orientation = {:?}, size = {}, points = {})", &self.name, &self.origin.as_ref().unwrap(), &self.orientation.as_ref().unwrap(), &self.size, &self.points)
+        write!(f, "Ship(name = {}, starting point = {}, orientation = {:?}, size = {}, points = {})", &self.name, &self.origin.as_ref().unwrap(), &self.orientation.as_ref().unwrap(), &self.get_size(), &self.points)
     }
 }
 
@@ -206,6 +364,48 @@ mod tests {
 
     #[test]
     fn test_ship() {
-        
+        let ship = Ship::build(ShipType::Submarine);
+        assert_eq!(3, ship.get_size());
+        assert_eq!(6, ship.get_point());
+        assert!(!ship.is_destroyed());
+    }
+
+    #[test]
+    fn test_ship_squares() {
+        let mut ship = Ship::build(ShipType::Destroyer);
+        assert!(ship.get_squares().is_empty());
+
+        ship.origin = Some(GridPoint { x: 4, y: 2 });
+        ship.orientation = Some(Orientation::Vertical);
+        assert_eq!(vec![GridPoint { x: 4, y: 2 }, GridPoint { x: 4, y: 3 }], ship.get_squares());
+    }
+
+    #[test]
+    fn test_ship_shapes() {
+        let mut ship = Ship::build(ShipType::Cruiser);
+        assert_eq!(4, ship.get_size());
+        assert_eq!(4, ship.get_rotations().len());
+        assert_eq!(vec![(0, 0), (1, 0), (2, 0), (0, 1)], ship.get_cells(Orientation::Vertical));
+
+        ship.origin = Some(GridPoint { x: 2, y: 2 });
+        ship.orientation = Some(Orientation::HorizontalReversed);
+        assert_eq!(vec![GridPoint { x: 2, y: 2 }, GridPoint { x: 3, y: 2 }, GridPoint { x: 3, y: 3 }, GridPoint { x: 3, y: 4 }], ship.get_squares());
+        assert_eq!("Cruiser  (2,2) (3,2) (3,3) (3,4) ", ship.get_debug_mode_string());
+
+        assert_eq!(1, Ship::build(ShipType::Flagship).get_rotations().len());
+        assert_eq!(2, Ship::build(ShipType::Battleship).get_rotations().len());
+        assert_eq!(1, Ship::build(ShipType::PatrolBoat).get_rotations().len());
+    }
+
+    #[test]
+    fn test_custom_shapes() {
+        let ship = Ship::with_shape(ShipType::Destroyer, vec![(0, 0), (1, 0), (0, 1), (1, 1)], 3, "Barge").unwrap();
+        assert_eq!((4, 3, "Barge"), (ship.get_size(), ship.get_point(), ship.get_name()));
+        assert_eq!(1, ship.get_rotations().len());
+        assert_eq!(Ok(ship.clone()), Ship::from_design(&ship.get_design()));
+
+        assert_eq!(Err(ShapeError::Empty), Ship::with_shape(ShipType::Destroyer, Vec::new(), 3, "Nothing"));
+        assert_eq!(Err(ShapeError::DuplicateCell((1, 0))), Ship::with_shape(ShipType::Destroyer, vec![(0, 0), (1, 0), (1, 0)], 3, "Twice"));
+        assert_eq!(Err(ShapeError::Disconnected), Ship::with_shape(ShipType::Destroyer, vec![(0, 0), (1, 1)], 3, "Diagonal"));
     }
 }
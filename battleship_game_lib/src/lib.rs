@@ -1,12 +1,33 @@
 #[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
+use achievements::{Achievement, AchievementStatus};
+use bundle::{Bundle, BundleError, ImportReport, MergeStrategy};
+use campaign::{Campaign, CampaignError, LevelStatus};
+use rules::GameRules;
 use runtime::Play;
-use storage::Store;
+use stats::PlayerStats;
+use storage::{SaveInfo, Score, Store};
 
+pub mod achievements;
+pub mod bundle;
+pub mod campaign;
+pub mod commitment;
+pub mod inventory;
+pub mod notation;
+pub mod puzzle;
+pub mod replay;
+pub mod rules;
 pub mod runtime;
-mod inventory;
-mod storage;
+pub mod save;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_store;
+pub mod stats;
+pub mod strategy;
+pub mod storage;
+pub mod terrain;
+pub mod view;
+pub mod weapons;
 
 
 pub trait App {
@@ -16,19 +37,23 @@ pub trait App {
 #[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub struct GamePlay{
     play: Option<Play>,
-    store: Store
+    store: Store,
+    campaign: Campaign,
+    /// Campaign level being played, if the current play is one
+    level: Option<usize>
 }
 
 #[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl GamePlay {
     
     pub fn initialize()-> Self {
-        GamePlay{ play: None, store: Store::build() }
+        GamePlay{ play: None, store: Store::build(), campaign: Campaign::builtin(), level: None }
     }
 
     #[cfg(not(feature = "wasm-bindgen"))]
     pub fn start_new(&mut self, player_name: String) -> &mut Play {
         self.play = Some(Play::init(player_name));
+        self.level = None;
 
         self.play.as_mut().unwrap()
     }
@@ -36,43 +61,229 @@ impl GamePlay {
     #[cfg(feature = "wasm-bindgen")]
     pub fn start_new(&mut self, player_name: String) -> Play {
         self.play = Some(Play::init(player_name));
+        self.level = None;
 
         self.play.clone().unwrap()
     }
 
+    /// Start a game with rules other than the classic ones
     #[cfg(not(feature = "wasm-bindgen"))]
-    pub fn load(&mut self, player_name: String) -> Option<&mut Play> {
-        self.play = self.store.get_play(&player_name);
+    pub fn start_new_with_rules(&mut self, player_name: String, rules: GameRules) -> &mut Play {
+        self.play = Some(Play::init_with_rules(player_name, rules));
+        self.level = None;
 
-        self.play.as_mut()
+        self.play.as_mut().unwrap()
     }
 
+    /// Start a game with rules other than the classic ones
     #[cfg(feature = "wasm-bindgen")]
-    pub fn load(&mut self, player_name: String) -> Play {
-        self.play = self.store.get_play(&player_name);
+    pub fn start_new_with_rules(&mut self, player_name: String, rules: GameRules) -> Play {
+        self.play = Some(Play::init_with_rules(player_name, rules));
+        self.level = None;
 
         self.play.clone().unwrap()
     }
 
-    pub fn list_saved(&self) -> Vec<String> {
+    /// Load the saved game with the save id `id`, refusing saves changed since they were written
+    #[cfg(not(feature = "wasm-bindgen"))]
+    pub fn load(&mut self, id: String) -> Result<&mut Play, save::SaveError> {
+        self.play = Some(self.store.get_play(&id)?);
+        self.level = None;
+
+        Ok(self.play.as_mut().unwrap())
+    }
+
+    /// Load the saved game with the save id `id`
+    #[cfg(feature = "wasm-bindgen")]
+    pub fn load(&mut self, id: String) -> Play {
+        self.play = self.store.get_play(&id).ok();
+        self.level = None;
+
+        self.play.clone().unwrap()
+    }
+
+    /// Save ids of the games saved by the player, last played first
+    pub fn list_saved(&self, player_name: String) -> Vec<String> {
+        self.store.list_saves(&player_name).into_iter().map(|save| save.id).collect()
+    }
+
+    /// Players with at least one saved game
+    pub fn list_players(&self) -> Vec<String> {
         self.store.get_player_names()
     }
 
-    pub fn delete(&mut self,player_name: String) -> Option<Play> {
-        self.store.pop_play(&player_name)
+    /// Statistics of the player over every finished game
+    pub fn get_player_stats(&self, player_name: String) -> PlayerStats {
+        self.store.get_stats(&player_name)
+    }
+
+    /// Delete the saved game with the save id `id`
+    pub fn delete(&mut self, id: String) -> bool {
+        self.store.delete_play(&id)
     }
 
+    /// Save the game being played, to the slot it was loaded from or to a new one
     pub fn save(&mut self) {
-        match self.play.clone() {
-            Some(play) => self.store.save_play(play),
-            None => (),
+        if let Some(play) = self.play.as_mut() {
+            let id = self.store.save_play(play.clone());
+            play.set_save_id(id);
+        }
+    }
+
+    /// Save the game being played without putting its score on the high score table,
+    /// the score goes on the table once the game is finished, see `finish_game`
+    pub fn save_progress(&mut self) {
+        if let Some(play) = self.play.as_mut() {
+            let id = self.store.save_progress(play.clone());
+            play.set_save_id(id);
         }
-        
     }
 
     pub fn save_and_exit(&mut self) {
         self.save();
         self.play = None;
+        self.level = None;
+    }
+
+    /// Every saved game, high score and player record in one portable file, see `Bundle`.
+    /// The game being played is left out until it is saved.
+    pub fn export_all(&self) -> Vec<u8> {
+        self.store.export_bundle().to_json().into_bytes()
+    }
+}
+
+impl GamePlay {
+    /// The game being played, `None` before one is started or loaded
+    pub fn get_play(&self) -> Option<&Play> {
+        self.play.as_ref()
+    }
+
+    /// Start with an empty store signing its saves with `save_key`, the key kept on the device from an earlier run
+    pub fn initialize_with_key(save_key: Vec<u8>) -> Self {
+        GamePlay { store: Store::build_with_key(save_key), ..Self::initialize() }
+    }
+
+    /// Key the saves are signed with, to be kept on the device and never shared
+    pub fn get_save_key(&self) -> &[u8] {
+        self.store.get_save_key()
+    }
+
+    /// Signed JSON of a saved game for writing to disk, `None` if there is no save `id`
+    pub fn export_save(&self, id: &str) -> Option<String> {
+        self.store.export_play(id)
+    }
+
+    /// Load a save written by `export_save` with the same key, refusing saves changed since
+    /// Return: id of the save slot
+    pub fn import_save(&mut self, json: &str) -> Result<String, save::SaveError> {
+        self.store.import_play(json)
+    }
+
+    /// Keep new saves from taking the id of a save written by `export_save` that could not be loaded
+    pub fn reserve_save_id(&mut self, id: &str) {
+        self.store.reserve_save_id(id)
+    }
+
+    /// High scores and player records in the format of `export_all` without the saves, signed with the save key
+    pub fn export_records(&self) -> Vec<u8> {
+        self.store.export_records().into_bytes()
+    }
+
+    /// Load records written by `export_records` with the same key, refusing records changed since
+    pub fn import_records(&mut self, bytes: &[u8]) -> Result<ImportReport, BundleError> {
+        let json = std::str::from_utf8(bytes).map_err(|err| BundleError::Parse(err.to_string()))?;
+        self.store.import_records(json)
+    }
+
+    /// High score table, best first. Assisted games are left out unless `include_assisted` is set
+    pub fn get_high_scores(&self, include_assisted: bool) -> Vec<Score> {
+        self.store.get_high_scores(include_assisted)
+    }
+
+    /// Merge a file written by `export_all` into the store, settling conflicting save ids and player names with `strategy`.
+    /// With `dry_run` nothing changes and the report says what the import would do.
+    pub fn import(&mut self, bytes: &[u8], strategy: MergeStrategy, dry_run: bool) -> Result<ImportReport, BundleError> {
+        let json = std::str::from_utf8(bytes).map_err(|err| BundleError::Parse(err.to_string()))?;
+        self.store.import_bundle(Bundle::from_json(json)?, strategy, dry_run)
+    }
+
+    /// Saved games of the player with when they were created and last played, last played first
+    pub fn list_saves(&self, player_name: &str) -> Vec<SaveInfo> {
+        self.store.list_saves(player_name)
+    }
+
+    /// Play levels of `campaign` rather than the built-in one
+    pub fn set_campaign(&mut self, campaign: Campaign) {
+        self.campaign = campaign;
+        self.level = None;
+    }
+
+    pub fn get_campaign(&self) -> &Campaign {
+        &self.campaign
+    }
+
+    /// Every level of the campaign with whether the player has unlocked it and their best stars
+    pub fn list_levels(&self, player_name: &str) -> Vec<LevelStatus> {
+        let progress = self.store.get_campaign_progress(player_name, &self.campaign);
+        self.campaign.levels.iter().enumerate().map(|(n, level)| LevelStatus {
+            level: n,
+            name: level.name.clone(),
+            unlocked: progress.is_unlocked(n),
+            stars: progress.get_stars(n),
+        }).collect()
+    }
+
+    /// Start a level the player has unlocked
+    pub fn start_level(&mut self, player_name: String, level: usize) -> Result<&mut Play, CampaignError> {
+        let definition = self.campaign.levels.get(level).ok_or(CampaignError::NoSuchLevel(level))?;
+        if !self.store.get_campaign_progress(&player_name, &self.campaign).is_unlocked(level) {
+            return Err(CampaignError::Locked(level));
+        }
+
+        let session = definition.start(player_name, rand::random()).map_err(|err| CampaignError::FleetDoesNotFit(level, err))?;
+        self.play = Some(Play::init_with_session(session));
+        self.level = Some(level);
+        Ok(self.play.as_mut().unwrap())
+    }
+
+    /// Rate the finished level being played and keep the stars if they are the player's best.
+    /// Games where a shot was taken back earn no stars.
+    /// Return: stars earned, 0 if the level was not beaten
+    pub fn record_level(&mut self) -> Result<u8, CampaignError> {
+        let (level, play) = match (self.level, &self.play) {
+            (Some(level), Some(play)) => (level, play),
+            _ => return Err(CampaignError::NoLevelInPlay),
+        };
+        let session = play.get_session_as_ref();
+        if !session.is_over() {
+            return Err(CampaignError::NotFinished);
+        }
+
+        let stars = if session.is_undo_used() { 0 } else { self.campaign.levels[level].rate(session.get_points()) };
+        let player_name = session.get_player_name();
+        let mut progress = self.store.get_campaign_progress(&player_name, &self.campaign);
+        progress.record(level, stars);
+        self.store.save_campaign_progress(&player_name, progress);
+        self.level = None;
+        Ok(stars)
+    }
+
+    /// Count the finished game being played towards the player's statistics and achievements, then save and close it
+    /// Return: achievements unlocked by the game, `None` if no game is being played or it is not over yet
+    pub fn finish_game(&mut self) -> Option<Vec<Achievement>> {
+        let session = self.play.as_ref()?.get_session_as_ref().clone();
+        if !session.is_over() {
+            return None;
+        }
+
+        let unlocked = self.store.record_game(&session);
+        self.save_and_exit();
+        Some(unlocked)
+    }
+
+    /// Every achievement with whether the player has unlocked it and the progress towards it
+    pub fn list_achievements(&self, player_name: &str) -> Vec<AchievementStatus> {
+        self.store.get_achievements(player_name).list()
     }
 }
 
@@ -80,9 +291,44 @@ impl GamePlay {
 #[cfg(test)]
 mod tests {
     use crate::runtime::GridPoint;
+    use crate::view::CellState;
 
     use super::*;
 
+    #[test]
+    fn test_campaign_levels() {
+        let mut game = GamePlay::initialize();
+        let name = String::from("Adetayo");
+        assert_eq!(Err(CampaignError::Locked(1)), game.start_level(name.clone(), 1).map(|_| ()));
+        assert_eq!(Err(CampaignError::NoLevelInPlay), game.record_level());
+
+        let play = game.start_level(name.clone(), 0).unwrap();
+        play.get_session_as_mut().shoot_ship(GridPoint { x: 1, y: 1 });
+        assert_eq!(Err(CampaignError::NotFinished), game.record_level());
+
+        // sink the whole fleet with shots straight at it
+        let play = game.play.as_mut().unwrap();
+        let squares: Vec<GridPoint> = (1..=6).flat_map(|y| (1..=6).map(move |x| GridPoint { x, y })).collect();
+        for square in squares {
+            if play.get_session_as_ref().get_omniscient_view().get_cell(square) == Some(CellState::Ship) {
+                play.get_session_as_mut().shoot_ship(square);
+            }
+        }
+        assert_eq!(Ok(3), game.record_level());
+
+        let levels = game.list_levels(&name);
+        assert_eq!(3, levels[0].stars);
+        assert!(levels[1].unlocked && !levels[2].unlocked);
+        assert!(!game.list_levels("Tolu")[1].unlocked);
+
+        // the won level counts towards the achievements
+        assert!(game.finish_game().unwrap().contains(&Achievement::FirstWin));
+        assert_eq!(None, game.finish_game());
+        assert_eq!(1, game.get_player_stats(name.clone()).get_wins());
+        let first_win = game.list_achievements(&name).into_iter().find(|status| status.achievement == Achievement::FirstWin).unwrap();
+        assert!(first_win.unlocked_at.is_some());
+    }
+
     #[test]
     fn test_play() {
         let mut game = GamePlay::initialize();
@@ -103,4 +349,57 @@ mod tests {
         
         println!("{:?}", play.get_session_as_ref().get_destroyed_ships());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_save_slots() {
+        let mut game = GamePlay::initialize();
+        let name = String::from("Adetayo");
+        game.start_new(name.clone()).get_session_as_mut().shoot_ship(GridPoint { x: 1, y: 1 });
+        game.save_and_exit();
+        game.start_new(name.clone());
+        game.save();
+        let second = game.play.as_ref().unwrap().get_save_id().unwrap();
+        // saving again keeps the same slot
+        game.save_and_exit();
+
+        let saved = game.list_saved(name.clone());
+        assert_eq!(2, saved.len());
+
+        // saving part way through keeps the game off the high score table
+        let scores = game.get_high_scores(true).len();
+        game.start_new(name.clone());
+        game.save_progress();
+        game.save_progress();
+        assert_eq!(scores, game.get_high_scores(true).len());
+        let third = game.play.as_ref().unwrap().get_save_id().unwrap();
+        assert!(game.delete(third));
+        assert!(saved.contains(&second));
+        assert!(game.list_saved(String::from("Tolu")).is_empty());
+
+        let first = saved.into_iter().find(|id| *id != second).unwrap();
+        assert_eq!(1, game.load(first.clone()).unwrap().get_session_as_ref().get_shots_fired());
+        assert_eq!(vec![name.clone()], game.list_players());
+        assert!(game.delete(first));
+        assert_eq!(vec![second], game.list_saved(name));
+    }
+
+    #[test]
+    fn test_export_and_import_all() {
+        let mut game = GamePlay::initialize();
+        let name = String::from("Adetayo");
+        game.start_new(name.clone()).get_session_as_mut().shoot_ship(GridPoint { x: 1, y: 1 });
+        game.save_and_exit();
+        let bytes = game.export_all();
+
+        let mut other = GamePlay::initialize();
+        let report = other.import(&bytes, MergeStrategy::KeepBoth, true).unwrap();
+        assert_eq!(1, report.saves_added.len());
+        assert!(other.list_saved(name.clone()).is_empty());
+        other.import(&bytes, MergeStrategy::KeepBoth, false).unwrap();
+        let id = other.list_saved(name.clone()).pop().unwrap();
+        assert_eq!(1, other.load(id).unwrap().get_session_as_ref().get_shots_fired());
+
+        assert_eq!(Err(BundleError::UnsupportedVersion(2)), other.import(br#"{ "format_version": 2 }"#, MergeStrategy::KeepBoth, false).map(|_| ()));
+        assert!(matches!(other.import(&[0xff], MergeStrategy::KeepBoth, false), Err(BundleError::Parse(_))));
+    }
+}
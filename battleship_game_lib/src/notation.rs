@@ -0,0 +1,196 @@
+use std::fmt;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use crate::runtime::GridPoint;
+
+
+/////////
+/// Which axis of the board the letters of a square name
+/////////
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum LetterAxis {
+    /// B7 is column 2, row 7
+    #[default]
+    Columns,
+    /// B7 is row 2, column 7
+    Rows,
+}
+
+/// Letter-number notation of squares, such as B7.
+/// Letters run A to Z, then AA, AB and on for boards wider than 26 squares, and are read in any case.
+///
+/// ```
+/// use battleship_game_lib::notation::{LetterAxis, Notation};
+/// use battleship_game_lib::runtime::GridPoint;
+///
+/// assert_eq!(Ok(GridPoint::new(2, 7)), "b7".parse());
+/// assert_eq!("B7", format!("{:#}", GridPoint::new(2, 7)));
+///
+/// let rows = Notation { letters: LetterAxis::Rows };
+/// assert_eq!(Ok(GridPoint::new(7, 2)), rows.parse("B7"));
+/// assert_eq!("AA3", rows.format(GridPoint::new(3, 27)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub struct Notation {
+    pub letters: LetterAxis,
+}
+
+impl Notation {
+    /// Read a square such as B7, surrounding whitespace is ignored
+    pub fn parse(&self, square: &str) -> Result<GridPoint, NotationError> {
+        let square = square.trim();
+        let first = square.chars().next().ok_or(NotationError::Empty)?;
+        if !first.is_ascii_alphabetic() {
+            return Err(if first.is_ascii_digit() { NotationError::MissingLetters } else { NotationError::InvalidCharacter(first) });
+        }
+
+        let digits_at = square.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(square.len());
+        let (letters, digits) = square.split_at(digits_at);
+        if digits.is_empty() {
+            return Err(NotationError::MissingNumber);
+        }
+        if let Some(invalid) = digits.chars().find(|c| !c.is_ascii_digit()) {
+            return Err(NotationError::InvalidCharacter(invalid));
+        }
+
+        let lettered = letters_to_number(letters)?;
+        let numbered: i32 = digits.parse().map_err(|_| NotationError::OutOfRange)?;
+        if numbered == 0 {
+            return Err(NotationError::OutOfRange);
+        }
+        Ok(match self.letters {
+            LetterAxis::Columns => GridPoint { x: lettered, y: numbered },
+            LetterAxis::Rows => GridPoint { x: numbered, y: lettered },
+        })
+    }
+
+    /// Write a square such as B7. Squares off the board, below 1, have no letters and are written as (x, y).
+    pub fn format(&self, point: GridPoint) -> String {
+        if point.x < 1 || point.y < 1 {
+            return format!("({}, {})", point.x, point.y);
+        }
+        match self.letters {
+            LetterAxis::Columns => format!("{}{}", axis_letters(point.x), point.y),
+            LetterAxis::Rows => format!("{}{}", axis_letters(point.y), point.x),
+        }
+    }
+}
+
+/// Letters of column or row `n`, counting from 1: A to Z, then AA, AB and on
+///
+/// Panics if `n` is below 1
+pub fn axis_letters(n: i32) -> String {
+    assert!(n >= 1, "squares are counted from 1");
+    let mut n = n as u32;
+    let mut letters = Vec::new();
+    while n > 0 {
+        n -= 1;
+        letters.push(b'A' + (n % 26) as u8);
+        n /= 26;
+    }
+    letters.reverse();
+    String::from_utf8(letters).expect("letters are ascii")
+}
+
+fn letters_to_number(letters: &str) -> Result<i32, NotationError> {
+    letters.bytes().try_fold(0i32, |n, letter| {
+        let digit = i32::from(letter.to_ascii_uppercase() - b'A') + 1;
+        n.checked_mul(26).and_then(|n| n.checked_add(digit)).ok_or(NotationError::OutOfRange)
+    })
+}
+
+impl FromStr for GridPoint {
+    type Err = NotationError;
+
+    /// Read a square such as B7, the letters naming the column
+    fn from_str(square: &str) -> Result<GridPoint, NotationError> {
+        Notation::default().parse(square)
+    }
+}
+
+/// Reasons a square can not be read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotationError {
+    /// There is nothing to read
+    Empty,
+    /// The square starts with its number, B7 not 7B
+    MissingLetters,
+    /// The square has letters but no number
+    MissingNumber,
+    /// Something other than letters followed by digits
+    InvalidCharacter(char),
+    /// The number is 0 or the square is too far out to be counted
+    OutOfRange,
+}
+
+impl fmt::Display for NotationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NotationError::Empty => write!(f, "no square given, try one such as B7"),
+            NotationError::MissingLetters => write!(f, "a square starts with its letters, such as B7"),
+            NotationError::MissingNumber => write!(f, "a square ends with its number, such as B7"),
+            NotationError::InvalidCharacter(c) => write!(f, "'{}' can not be part of a square such as B7", c),
+            NotationError::OutOfRange => write!(f, "the square is off the board"),
+        }
+    }
+}
+
+impl std::error::Error for NotationError {}
+
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    const ROWS: Notation = Notation { letters: LetterAxis::Rows };
+
+    #[test]
+    fn classic_squares() {
+        assert_eq!(Ok(GridPoint { x: 1, y: 1 }), "A1".parse());
+        assert_eq!(Ok(GridPoint { x: 10, y: 10 }), " j10 ".parse());
+        assert_eq!(Ok(GridPoint { x: 27, y: 3 }), "aA3".parse());
+        assert_eq!("Z26", format!("{:#}", GridPoint { x: 26, y: 26 }));
+        assert_eq!("AZ", axis_letters(52));
+        assert_eq!("GridPoint(x = 2, y = 7)", GridPoint { x: 2, y: 7 }.to_string());
+        assert_eq!("(0, 3)", ROWS.format(GridPoint { x: 0, y: 3 }));
+    }
+
+    #[test]
+    fn typed_errors() {
+        assert_eq!(Err(NotationError::Empty), "  ".parse::<GridPoint>());
+        assert_eq!(Err(NotationError::MissingLetters), "7B".parse::<GridPoint>());
+        assert_eq!(Err(NotationError::MissingNumber), "B".parse::<GridPoint>());
+        assert_eq!(Err(NotationError::InvalidCharacter('-')), "B-7".parse::<GridPoint>());
+        assert_eq!(Err(NotationError::InvalidCharacter('C')), "B7C".parse::<GridPoint>());
+        assert_eq!(Err(NotationError::InvalidCharacter('é')), "é7".parse::<GridPoint>());
+        assert_eq!(Err(NotationError::OutOfRange), "B0".parse::<GridPoint>());
+        assert_eq!(Err(NotationError::OutOfRange), "B99999999999".parse::<GridPoint>());
+        assert_eq!(Err(NotationError::OutOfRange), "ZZZZZZZZ1".parse::<GridPoint>());
+    }
+
+    proptest! {
+        #[test]
+        fn round_trip(x in 1..=i32::MAX, y in 1..=i32::MAX, rows: bool) {
+            let notation = if rows { ROWS } else { Notation::default() };
+            let point = GridPoint { x, y };
+            let square = notation.format(point);
+            prop_assert_eq!(Ok(point), notation.parse(&square));
+            prop_assert_eq!(Ok(point), notation.parse(&square.to_lowercase()));
+        }
+
+        #[test]
+        fn round_trip_text(letters in "[A-Z]{1,4}", number in 1..=100_000i32) {
+            let square = format!("{}{}", letters, number);
+            let point: GridPoint = square.parse().unwrap();
+            prop_assert_eq!(square, format!("{:#}", point));
+        }
+
+        #[test]
+        fn never_panics(square in "\\PC*") {
+            let _ = square.parse::<GridPoint>();
+            let _ = ROWS.parse(&square);
+        }
+    }
+}
@@ -0,0 +1,597 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use crate::inventory::{grid::Grid, ship::{Orientation, Ship, ShipType}};
+use crate::replay::ShipPlacement;
+use crate::runtime::GridPoint;
+use crate::terrain::MAX_BOARD_SIZE;
+
+
+/// Version written by `Puzzle::to_json`, bumped whenever the format changes
+///
+/// 1. the counts, revealed squares and solution of the puzzle
+/// 2. without the solution, it is found again when the puzzle is loaded
+pub const PUZZLE_FORMAT_VERSION: u32 = 2;
+
+/// A square whose content is given away at the start of the puzzle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RevealedSquare {
+    pub point: GridPoint,
+    /// The square is part of a ship, water otherwise
+    pub ship: bool,
+}
+
+/// Battleship solitaire: the fleet is hidden, the number of ship squares in every row
+/// and column is given together with a few revealed squares, and there is exactly one
+/// way to mark the ships that fits them
+///
+/// ```
+/// use battleship_game_lib::puzzle::{Puzzle, PuzzleSession};
+///
+/// let puzzle = Puzzle::generate(7);
+/// assert!(puzzle.is_unique());
+///
+/// let mut session = PuzzleSession::build(Puzzle::from_json(&puzzle.to_json()).unwrap());
+/// for point in puzzle.get_solution_squares() {
+///     session.set_mark(point, true);
+/// }
+/// assert!(session.is_solved());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Puzzle {
+    pub format_version: u32,
+    pub width: i32,
+    pub height: i32,
+    /// Ships to find
+    pub fleet: Vec<ShipType>,
+    /// Ship squares in each row, from the top
+    pub row_counts: Vec<i32>,
+    /// Ship squares in each column, from the left
+    pub column_counts: Vec<i32>,
+    pub revealed: Vec<RevealedSquare>,
+    /// Where the ships were placed, only known for generated puzzles and never written to JSON
+    #[serde(default, skip_serializing)]
+    pub solution: Option<Vec<ShipPlacement>>,
+}
+
+impl Puzzle {
+    /// Generate a puzzle with the classic fleet on a 10 by 10 grid, the same seed always gives the same puzzle
+    pub fn generate(seed: u64) -> Puzzle {
+        let mut grid = Grid::build();
+        grid.shuffle_ship_location_seeded(seed).expect("the classic fleet always fits");
+        Self::build_from_grid(&grid)
+    }
+
+    /// Generate a puzzle from a grid whose ships have been placed. Squares are revealed
+    /// until the solver finds a single solution.
+    pub fn build_from_grid(grid: &Grid) -> Puzzle {
+        let solution = grid.get_fleet_layout();
+        let squares: Vec<GridPoint> = solution.iter()
+            .flat_map(|placement| Ship::build(placement.ship_type).get_squares_at(placement.origin, placement.orientation))
+            .collect();
+
+        let mut puzzle = Puzzle {
+            format_version: PUZZLE_FORMAT_VERSION,
+            width: grid.get_width(),
+            height: grid.get_height(),
+            fleet: solution.iter().map(|placement| placement.ship_type).collect(),
+            row_counts: (1..=grid.get_height()).map(|y| squares.iter().filter(|square| square.y == y).count() as i32).collect(),
+            column_counts: (1..=grid.get_width()).map(|x| squares.iter().filter(|square| square.x == x).count() as i32).collect(),
+            revealed: Vec::new(),
+            solution: Some(solution),
+        };
+
+        // every other solution differs from the real one somewhere, revealing the first such
+        // square rules it out, so this always ends with a unique puzzle
+        let layout = puzzle.solution_layout();
+        loop {
+            let other = puzzle.find_solutions(2).into_iter().find(|found| *found != layout);
+            let point = match other {
+                Some(found) => (1..=puzzle.height)
+                    .flat_map(|y| (1..=puzzle.width).map(move |x| GridPoint { x, y }))
+                    .find(|point| found[puzzle.index(*point)] != layout[puzzle.index(*point)])
+                    .unwrap(),
+                None => break,
+            };
+            puzzle.revealed.push(RevealedSquare { point, ship: layout[puzzle.index(point)] });
+        }
+
+        puzzle
+    }
+
+    /// Export the puzzle as pretty printed JSON, leaving out the solution
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("puzzle is always serialisable")
+    }
+
+    /// Import a puzzle exported with `to_json` by this or an older version, solving it to check it has exactly one solution.
+    /// The size of the board and the fleet are checked first, so the search stays within the bounds of a real game.
+    pub fn from_json(json: &str) -> Result<Puzzle, PuzzleError> {
+        let mut puzzle: Puzzle = serde_json::from_str(json).map_err(|err| PuzzleError::Parse(err.to_string()))?;
+        if puzzle.format_version > PUZZLE_FORMAT_VERSION {
+            return Err(PuzzleError::UnsupportedVersion(puzzle.format_version));
+        }
+        puzzle.format_version = PUZZLE_FORMAT_VERSION;
+        let sized = (1..=MAX_BOARD_SIZE).contains(&puzzle.width) && (1..=MAX_BOARD_SIZE).contains(&puzzle.height)
+            && puzzle.row_counts.len() == puzzle.height as usize
+            && puzzle.column_counts.len() == puzzle.width as usize;
+        // each ship type at most once
+        let mut fleet = puzzle.fleet.clone();
+        fleet.sort();
+        fleet.dedup();
+        let distinct = fleet.len() == puzzle.fleet.len();
+        let on_board = puzzle.revealed.iter().all(|square| puzzle.is_on_board(square.point));
+        // the ships fill the counts exactly
+        let ship_squares: i32 = puzzle.fleet.iter().map(|ship_type| Ship::build(*ship_type).get_size()).sum();
+        let counted = puzzle.row_counts.iter().chain(puzzle.column_counts.iter()).all(|count| *count >= 0)
+            && puzzle.row_counts.iter().sum::<i32>() == ship_squares
+            && puzzle.column_counts.iter().sum::<i32>() == ship_squares;
+        if !sized || !distinct || !on_board || !counted {
+            return Err(PuzzleError::Inconsistent);
+        }
+
+        // puzzles written before the solution was left out still carry it
+        if let Some(solution) = &puzzle.solution {
+            let mut solved: Vec<ShipType> = solution.iter().map(|placement| placement.ship_type).collect();
+            solved.sort();
+            let placed = solution.iter()
+                .flat_map(|placement| Ship::build(placement.ship_type).get_squares_at(placement.origin, placement.orientation))
+                .all(|point| puzzle.is_on_board(point));
+            if solved != fleet || !placed || !puzzle.matches(&puzzle.solution_layout()) {
+                return Err(PuzzleError::Inconsistent);
+            }
+        }
+
+        match puzzle.find_solutions(2).len() {
+            0 => Err(PuzzleError::Inconsistent),
+            1 => Ok(puzzle),
+            _ => Err(PuzzleError::NotUnique),
+        }
+    }
+
+    /// Squares covered by the solution, found by the solver if the puzzle was loaded without it
+    pub fn get_solution_squares(&self) -> Vec<GridPoint> {
+        let layout = self.solution_layout();
+        (1..=self.height).flat_map(|y| (1..=self.width).map(move |x| GridPoint { x, y }))
+            .filter(|point| layout[self.index(*point)])
+            .collect()
+    }
+
+    /// Exactly one marking fits the counts and the revealed squares
+    pub fn is_unique(&self) -> bool {
+        self.find_solutions(2).len() == 1
+    }
+
+    /// Up to `limit` distinct markings that fit the counts and the revealed squares, one flag per
+    /// square in row-major order. Only the marked squares count, so two fleets covering the
+    /// same squares are the same solution.
+    pub fn find_solutions(&self, limit: usize) -> Vec<Vec<bool>> {
+        let mut ships: Vec<Ship> = self.fleet.iter().map(|ship_type| Ship::build(*ship_type)).collect();
+        ships.sort_by_key(|ship| std::cmp::Reverse(ship.get_size()));
+
+        let placements: Vec<Vec<Vec<GridPoint>>> = ships.iter().map(|ship| self.get_placements(ship)).collect();
+        let mut search = Search {
+            puzzle: self,
+            placements: &placements,
+            occupied: vec![false; (self.width * self.height) as usize],
+            rows: self.row_counts.clone(),
+            columns: self.column_counts.clone(),
+            solutions: Vec::new(),
+            limit,
+        };
+        search.place(0);
+        search.solutions
+    }
+
+    /// Do the marked squares fit the counts and the revealed squares
+    fn matches(&self, marked: &[bool]) -> bool {
+        let count = |filter: &dyn Fn(GridPoint) -> bool| {
+            (1..=self.height).flat_map(|y| (1..=self.width).map(move |x| GridPoint { x, y }))
+                .filter(|point| filter(*point) && marked[self.index(*point)])
+                .count() as i32
+        };
+        (1..=self.height).all(|y| count(&|point| point.y == y) == self.row_counts[(y - 1) as usize])
+            && (1..=self.width).all(|x| count(&|point| point.x == x) == self.column_counts[(x - 1) as usize])
+            && self.revealed.iter().all(|square| self.is_on_board(square.point) && marked[self.index(square.point)] == square.ship)
+    }
+
+    /// Marked squares of the solution, the first one the solver finds if the placements are not known
+    fn solution_layout(&self) -> Vec<bool> {
+        let Some(solution) = &self.solution else {
+            return self.find_solutions(1).pop().unwrap_or_else(|| vec![false; (self.width * self.height) as usize]);
+        };
+        let mut marked = vec![false; (self.width * self.height) as usize];
+        let squares = solution.iter()
+            .flat_map(|placement| Ship::build(placement.ship_type).get_squares_at(placement.origin, placement.orientation));
+        for point in squares {
+            if self.is_on_board(point) {
+                marked[self.index(point)] = true;
+            }
+        }
+        marked
+    }
+
+    /// Every placement of `ship` on the board that avoids revealed water
+    fn get_placements(&self, ship: &Ship) -> Vec<Vec<GridPoint>> {
+        let mut placements = Vec::new();
+        let mut orientation = Orientation::Horizontal;
+        let mut seen = Vec::new();
+        for _ in 0..4 {
+            let cells = ship.get_cells(orientation);
+            if !seen.contains(&cells) {
+                for y in 1..=self.height {
+                    for x in 1..=self.width {
+                        let squares = ship.get_squares_at(GridPoint { x, y }, orientation);
+                        let fits = squares.iter().all(|point| {
+                            self.is_on_board(*point) && !self.revealed.contains(&RevealedSquare { point: *point, ship: false })
+                        });
+                        if fits {
+                            placements.push(squares);
+                        }
+                    }
+                }
+                seen.push(cells);
+            }
+            orientation = orientation.rotate();
+        }
+        placements
+    }
+
+    fn is_on_board(&self, point: GridPoint) -> bool {
+        (1..=self.width).contains(&point.x) && (1..=self.height).contains(&point.y)
+    }
+
+    fn index(&self, point: GridPoint) -> usize {
+        ((point.y - 1) * self.width + (point.x - 1)) as usize
+    }
+}
+
+/// Backtracking over the ships, largest first, keeping the row and column counts still to fill
+struct Search<'a> {
+    puzzle: &'a Puzzle,
+    placements: &'a [Vec<Vec<GridPoint>>],
+    occupied: Vec<bool>,
+    rows: Vec<i32>,
+    columns: Vec<i32>,
+    solutions: Vec<Vec<bool>>,
+    limit: usize,
+}
+
+impl Search<'_> {
+    fn place(&mut self, ship: usize) {
+        if self.solutions.len() >= self.limit {
+            return;
+        }
+        if ship == self.placements.len() {
+            let complete = self.rows.iter().all(|count| *count == 0) && self.columns.iter().all(|count| *count == 0);
+            let revealed = self.puzzle.revealed.iter()
+                .all(|square| self.puzzle.is_on_board(square.point) && self.occupied[self.puzzle.index(square.point)] == square.ship);
+            if complete && revealed && !self.solutions.contains(&self.occupied) {
+                self.solutions.push(self.occupied.clone());
+            }
+            return;
+        }
+
+        for squares in self.placements[ship].iter() {
+            if !self.fits(squares) {
+                continue;
+            }
+            self.mark(squares, true);
+            self.place(ship + 1);
+            self.mark(squares, false);
+        }
+    }
+
+    fn fits(&self, squares: &[GridPoint]) -> bool {
+        let mut rows = self.rows.clone();
+        let mut columns = self.columns.clone();
+        for point in squares {
+            if self.occupied[self.puzzle.index(*point)] {
+                return false;
+            }
+            rows[(point.y - 1) as usize] -= 1;
+            columns[(point.x - 1) as usize] -= 1;
+        }
+        rows.iter().chain(columns.iter()).all(|count| *count >= 0)
+    }
+
+    fn mark(&mut self, squares: &[GridPoint], occupied: bool) {
+        let change = if occupied { -1 } else { 1 };
+        for point in squares {
+            let index = self.puzzle.index(*point);
+            self.occupied[index] = occupied;
+            self.rows[(point.y - 1) as usize] += change;
+            self.columns[(point.x - 1) as usize] += change;
+        }
+    }
+}
+
+/// Reasons a puzzle can not be imported
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PuzzleError {
+    /// The JSON could not be read
+    Parse(String),
+    /// The puzzle was written by a newer version of the game
+    UnsupportedVersion(u32),
+    /// The counts, fleet, revealed squares and solution do not agree, a square is not on the board,
+    /// the board is larger than `MAX_BOARD_SIZE` or a ship type is repeated
+    Inconsistent,
+    /// More than one marking fits the counts and the revealed squares
+    NotUnique,
+}
+
+impl fmt::Display for PuzzleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PuzzleError::Parse(err) => write!(f, "could not read puzzle: {}", err),
+            PuzzleError::UnsupportedVersion(version) => write!(f, "puzzle format version {} is newer than {}", version, PUZZLE_FORMAT_VERSION),
+            PuzzleError::Inconsistent => write!(f, "puzzle counts, revealed squares and solution do not agree"),
+            PuzzleError::NotUnique => write!(f, "puzzle has more than one solution"),
+        }
+    }
+}
+
+impl std::error::Error for PuzzleError {}
+
+/////////////////////////////////////////////////////////////////////////
+/// Rule broken by the player's markings
+/////////////////////////////////////////////////////////////////////////
+///
+///TooManyInRow, TooManyInColumn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MarkingError {
+    /// More squares are marked in the row (from 1) than it holds
+    TooManyInRow(i32),
+    /// More squares are marked in the column (from 1) than it holds
+    TooManyInColumn(i32),
+}
+
+/// A puzzle being solved: the player marks the squares they think hold a ship.
+/// Revealed squares are fixed, revealed ships start marked.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, Clone)]
+pub struct PuzzleSession {
+    puzzle: Puzzle,
+    marks: Vec<bool>,
+    /// Marks of the solution, found once when the session starts
+    solution: Vec<bool>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl PuzzleSession {
+    /// Start a new generated puzzle, see `Puzzle::generate`
+    pub fn generate(seed: u64) -> PuzzleSession {
+        Self::build(Puzzle::generate(seed))
+    }
+
+    /// Load a puzzle exported with `export`, `None` if it can not be read
+    pub fn load(json: String) -> Option<PuzzleSession> {
+        Self::from_json(&json).ok()
+    }
+
+    /// Export the puzzle, without the player's marks, as JSON
+    pub fn export(&self) -> String {
+        self.puzzle.to_json()
+    }
+
+    pub fn get_width(&self) -> i32 {
+        self.puzzle.width
+    }
+
+    pub fn get_height(&self) -> i32 {
+        self.puzzle.height
+    }
+
+    /// Ship squares in row `y`, 0 for a row not on the board
+    pub fn get_row_count(&self, y: i32) -> i32 {
+        if !(1..=self.puzzle.height).contains(&y) {
+            return 0;
+        }
+        self.puzzle.row_counts[(y - 1) as usize]
+    }
+
+    /// Ship squares in column `x`, 0 for a column not on the board
+    pub fn get_column_count(&self, x: i32) -> i32 {
+        if !(1..=self.puzzle.width).contains(&x) {
+            return 0;
+        }
+        self.puzzle.column_counts[(x - 1) as usize]
+    }
+
+    pub fn is_revealed(&self, point: GridPoint) -> bool {
+        self.puzzle.revealed.iter().any(|square| square.point == point)
+    }
+
+    /// Is the square marked, never for a point off the board
+    pub fn is_marked(&self, point: GridPoint) -> bool {
+        self.puzzle.is_on_board(point) && self.marks[self.puzzle.index(point)]
+    }
+
+    /// Mark or clear a square, revealed squares and points off the board can not be changed.
+    /// Return: whether the square is marked afterwards
+    pub fn set_mark(&mut self, point: GridPoint, marked: bool) -> bool {
+        if self.puzzle.is_on_board(point) && !self.is_revealed(point) {
+            let index = self.puzzle.index(point);
+            self.marks[index] = marked;
+        }
+        self.is_marked(point)
+    }
+
+    /// Flip the mark of a square, see `set_mark`
+    pub fn toggle_mark(&mut self, point: GridPoint) -> bool {
+        self.set_mark(point, !self.is_marked(point))
+    }
+
+    /// Every ship square is marked and nothing else
+    pub fn is_solved(&self) -> bool {
+        self.puzzle.matches(&self.marks) && self.marks == self.solution
+    }
+}
+
+impl PuzzleSession {
+    pub fn build(puzzle: Puzzle) -> PuzzleSession {
+        let mut marks = vec![false; (puzzle.width * puzzle.height) as usize];
+        for square in puzzle.revealed.iter().filter(|square| square.ship && puzzle.is_on_board(square.point)) {
+            marks[puzzle.index(square.point)] = true;
+        }
+        let solution = puzzle.solution_layout();
+        PuzzleSession { puzzle, marks, solution }
+    }
+
+    /// Load a puzzle exported with `Puzzle::to_json`
+    pub fn from_json(json: &str) -> Result<PuzzleSession, PuzzleError> {
+        Ok(Self::build(Puzzle::from_json(json)?))
+    }
+
+    pub fn get_puzzle(&self) -> &Puzzle {
+        &self.puzzle
+    }
+
+    /// Rows and columns with more marks than ship squares
+    pub fn validate(&self) -> Vec<MarkingError> {
+        let marked = |filter: &dyn Fn(GridPoint) -> bool| {
+            (1..=self.puzzle.height).flat_map(|y| (1..=self.puzzle.width).map(move |x| GridPoint { x, y }))
+                .filter(|point| filter(*point) && self.is_marked(*point))
+                .count() as i32
+        };
+        let rows = (1..=self.puzzle.height)
+            .filter(|y| marked(&|point| point.y == *y) > self.get_row_count(*y))
+            .map(MarkingError::TooManyInRow);
+        let columns = (1..=self.puzzle.width)
+            .filter(|x| marked(&|point| point.x == *x) > self.get_column_count(*x))
+            .map(MarkingError::TooManyInColumn);
+        rows.chain(columns).collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// JSON of the puzzle as version 1 wrote it, with the solution
+    fn with_solution(puzzle: &Puzzle) -> String {
+        let mut value = serde_json::to_value(puzzle).unwrap();
+        value["format_version"] = serde_json::Value::from(1);
+        value["solution"] = serde_json::to_value(&puzzle.solution).unwrap();
+        value.to_string()
+    }
+
+    #[test]
+    fn exported_puzzles_hide_the_solution() {
+        let puzzle = Puzzle::generate(5);
+        let json = puzzle.to_json();
+        assert!(!json.contains("solution"));
+
+        let loaded = Puzzle::from_json(&json).unwrap();
+        assert_eq!(None, loaded.solution);
+        let mut squares = puzzle.get_solution_squares();
+        squares.sort_by_key(|point| (point.y, point.x));
+        assert_eq!(squares, loaded.get_solution_squares());
+
+        // older puzzles with the solution still load
+        assert_eq!(puzzle, Puzzle::from_json(&with_solution(&puzzle)).unwrap());
+    }
+
+    #[test]
+    fn generated_puzzles_are_unique() {
+        for seed in 0..5 {
+            let puzzle = Puzzle::generate(seed);
+            assert!(puzzle.is_unique());
+            assert_eq!(15, puzzle.row_counts.iter().sum::<i32>());
+            assert_eq!(puzzle, Puzzle::generate(seed));
+        }
+    }
+
+    #[test]
+    fn solver_finds_every_solution() {
+        // the patrol boat could be at (1,1) or (3,1), with the destroyer on the other two squares of row 2
+        let fleet = [
+            ShipPlacement { ship_type: ShipType::Destroyer, origin: GridPoint { x: 2, y: 2 }, orientation: Orientation::Horizontal },
+            ShipPlacement { ship_type: ShipType::PatrolBoat, origin: GridPoint { x: 1, y: 1 }, orientation: Orientation::Horizontal },
+        ];
+        let grid = Grid::build_from_fleet(&fleet).unwrap();
+        let mut puzzle = Puzzle::build_from_grid(&grid);
+        assert_eq!(1, puzzle.revealed.len());
+        assert!(puzzle.is_unique());
+
+        puzzle.revealed.clear();
+        assert_eq!(2, puzzle.find_solutions(5).len());
+        puzzle.row_counts[0] = 2;
+        assert!(puzzle.find_solutions(5).is_empty());
+    }
+
+    #[test]
+    fn mark_puzzle() {
+        let puzzle = Puzzle::generate(11);
+        let mut session = PuzzleSession::load(PuzzleSession::generate(11).export()).unwrap();
+        let squares = puzzle.get_solution_squares();
+        let water = (1..=10).flat_map(|y| (1..=10).map(move |x| GridPoint { x, y }))
+            .find(|point| !squares.contains(point) && !session.is_revealed(*point))
+            .unwrap();
+
+        assert!(session.toggle_mark(water));
+        for point in squares.iter() {
+            session.set_mark(*point, true);
+        }
+        assert!(!session.is_solved());
+        assert!(session.validate().contains(&MarkingError::TooManyInRow(water.y)));
+        assert!(!session.toggle_mark(water));
+        assert!(session.validate().is_empty());
+        assert!(session.is_solved());
+    }
+
+    #[test]
+    fn reject_inconsistent_puzzle() {
+        let mut puzzle = Puzzle::generate(2);
+        puzzle.row_counts[0] += 1;
+        assert_eq!(Err(PuzzleError::Inconsistent), Puzzle::from_json(&puzzle.to_json()));
+        assert!(matches!(PuzzleSession::from_json("{"), Err(PuzzleError::Parse(_))));
+
+        let mut puzzle = Puzzle::generate(2);
+        puzzle.revealed.push(RevealedSquare { point: GridPoint { x: 11, y: 1 }, ship: false });
+        assert_eq!(Err(PuzzleError::Inconsistent), Puzzle::from_json(&puzzle.to_json()));
+
+        let mut puzzle = Puzzle::generate(2);
+        puzzle.solution.as_mut().unwrap()[0].origin = GridPoint { x: 0, y: 0 };
+        assert_eq!(Err(PuzzleError::Inconsistent), Puzzle::from_json(&with_solution(&puzzle)));
+
+        // a fleet that is not the one of the solution, or repeats a ship
+        let mut puzzle = Puzzle::generate(2);
+        let battleship = puzzle.fleet.iter().position(|ship_type| *ship_type == ShipType::Battleship).unwrap();
+        puzzle.fleet[battleship] = ShipType::Cruiser;
+        assert_eq!(Err(PuzzleError::Inconsistent), Puzzle::from_json(&with_solution(&puzzle)));
+        let mut puzzle = Puzzle::generate(2);
+        puzzle.fleet.push(ShipType::PatrolBoat);
+        puzzle.row_counts[0] += 1;
+        puzzle.column_counts[0] += 1;
+        assert_eq!(Err(PuzzleError::Inconsistent), Puzzle::from_json(&puzzle.to_json()));
+
+        // boards larger than any game are refused before the search
+        let mut puzzle = Puzzle::generate(2);
+        puzzle.width = MAX_BOARD_SIZE + 1;
+        puzzle.column_counts.resize(puzzle.width as usize, 0);
+        assert_eq!(Err(PuzzleError::Inconsistent), Puzzle::from_json(&puzzle.to_json()));
+
+        let fleet = [
+            ShipPlacement { ship_type: ShipType::Destroyer, origin: GridPoint { x: 2, y: 2 }, orientation: Orientation::Horizontal },
+            ShipPlacement { ship_type: ShipType::PatrolBoat, origin: GridPoint { x: 1, y: 1 }, orientation: Orientation::Horizontal },
+        ];
+        let mut puzzle = Puzzle::build_from_grid(&Grid::build_from_fleet(&fleet).unwrap());
+        puzzle.revealed.clear();
+        assert_eq!(Err(PuzzleError::NotUnique), Puzzle::from_json(&puzzle.to_json()));
+    }
+
+    #[test]
+    fn marks_off_the_board_are_ignored() {
+        let mut session = PuzzleSession::generate(3);
+        let outside = GridPoint { x: 11, y: 1 };
+        assert!(!session.set_mark(outside, true));
+        assert!(!session.toggle_mark(outside));
+        assert!(!session.is_marked(GridPoint { x: 0, y: 5 }));
+        assert_eq!((0, 0), (session.get_row_count(11), session.get_column_count(0)));
+        assert!(session.validate().is_empty());
+    }
+}
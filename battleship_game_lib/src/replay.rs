@@ -0,0 +1,414 @@
+use std::fmt;
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::inventory::{grid::Grid, ship::{Orientation, Ship, ShipDesign, ShipType}};
+use crate::rules::GameRules;
+use crate::runtime::{GridPoint, ManualClock, Session, ShotStatus};
+use crate::terrain::BoardTemplate;
+use crate::weapons::{Direction, Weapon};
+
+
+/// Version written by `Replay::to_json`, bumped whenever the format changes
+///
+/// 1. the shot budget and the hint cost of the game
+/// 2. the rules of the game, see `GameRules`, with terrain, practice mode, moving fleets, special weapons and time limits.
+///    Ships of a custom shape were added to it later in `Replay::designs`, left out when there are none so the
+///    replays and signed saves written before stay the same
+pub const REPLAY_FORMAT_VERSION: u32 = 2;
+
+/// Upgrades from one version to the next, `MIGRATIONS[n]` turns version `n + 1` into version `n + 2`
+const MIGRATIONS: [fn(Value) -> Result<Value, ReplayError>; (REPLAY_FORMAT_VERSION - 1) as usize] = [
+    migrate_v1_to_v2,
+];
+
+/// Where a ship sits on the grid at the start of a game
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ShipPlacement {
+    pub ship_type: ShipType,
+    pub origin: GridPoint,
+    pub orientation: Orientation,
+}
+
+/// One shot and what it produced
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShotRecord {
+    pub point: GridPoint,
+    pub status: ShotStatus,
+    pub ship_type: Option<ShipType>,
+    /// Points the shot scored
+    pub points: i32,
+    /// Distance to the nearest ship reported for a miss, see `GameRules::miss_distance`
+    #[serde(default)]
+    pub distance: Option<i32>,
+    /// Milliseconds since the unix epoch
+    pub timestamp: u64,
+}
+
+/// One use of a special weapon
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WeaponRecord {
+    pub weapon: Weapon,
+    /// Centre of a sonar scan, start of a torpedo run or top left square of a bomb
+    pub target: GridPoint,
+    /// Direction of a torpedo run, kept under the name `orientation` in the replay
+    #[serde(rename = "orientation")]
+    pub direction: Option<Direction>,
+    /// Points the weapon scored
+    pub points: i32,
+    /// Milliseconds since the unix epoch
+    pub timestamp: u64,
+}
+
+/// Something the player did, or that happened to the session
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ReplayEvent {
+    Shot(ShotRecord),
+    Hint { point: GridPoint, timestamp: u64 },
+    /// The last shot, at `point`, was taken back in practice mode
+    Undo { point: GridPoint, timestamp: u64 },
+    Weapon(WeaponRecord),
+    /// Ships moved in the movement phase of a moving-fleet game, with their new placements
+    Move { placements: Vec<ShipPlacement>, timestamp: u64 },
+    /// The time for a shot ran out, the shot was forfeited as a miss
+    Timeout { timestamp: u64 },
+    /// The time for the whole game ran out
+    TimeUp { timestamp: u64 },
+}
+
+/// Everything needed to rebuild a game: the starting fleet, the rules and every
+/// event in the order it happened
+///
+/// ```
+/// use battleship_game_lib::replay::{Replay, Replayer};
+/// use battleship_game_lib::runtime::{GridPoint, Session};
+///
+/// let mut session = Session::start(String::from("Adetayo"));
+/// session.shoot_ship(GridPoint::new(3, 4));
+///
+/// let json = session.get_replay().to_json();
+/// let replayer = Replayer::new(Replay::from_json(&json).unwrap()).unwrap();
+/// assert_eq!(session.get_points(), replayer.final_session().get_points());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Replay {
+    pub format_version: u32,
+    pub player_name: String,
+    /// Seed the fleet was shuffled with, if it was generated from one
+    pub seed: Option<u64>,
+    pub fleet: Vec<ShipPlacement>,
+    /// Shapes of the ships that are not the standard ship of their type, see `Ship::with_shape`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub designs: Vec<ShipDesign>,
+    /// Terrain of the board, `None` for open sea
+    #[serde(default)]
+    pub board: Option<BoardTemplate>,
+    pub rules: GameRules,
+    /// Shots could be taken back
+    #[serde(default)]
+    pub practice: bool,
+    /// Seed of the movement phases in a moving-fleet game
+    #[serde(default)]
+    pub movement_seed: u64,
+    pub events: Vec<ReplayEvent>,
+}
+
+impl Replay {
+    /// Start a replay for a game that has not had any events yet
+    pub fn build(player_name: String, seed: Option<u64>, grid: &Grid, rules: GameRules) -> Replay {
+        Replay { format_version: REPLAY_FORMAT_VERSION, player_name, seed, fleet: grid.get_fleet_layout(), designs: grid.get_designs(), board: grid.get_template().cloned(), rules, practice: false, movement_seed: 0, events: Vec::new() }
+    }
+
+    /// Export the replay as pretty printed JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("replay is always serialisable")
+    }
+
+    /// Import a replay exported with `to_json` by this or any older version, upgrading it to the current format
+    pub fn from_json(json: &str) -> Result<Replay, ReplayError> {
+        let value: Value = serde_json::from_str(json).map_err(|err| ReplayError::Parse(err.to_string()))?;
+        Self::from_value(value)
+    }
+
+    /// Read a replay already parsed as JSON, such as the one inside a save, upgrading it to the current format
+    pub(crate) fn from_value(mut value: Value) -> Result<Replay, ReplayError> {
+        let version = value.get("format_version").and_then(Value::as_u64).unwrap_or(0) as u32;
+        if version == 0 {
+            return Err(ReplayError::Parse(String::from("replay has no format version")));
+        }
+        if version > REPLAY_FORMAT_VERSION {
+            return Err(ReplayError::UnsupportedVersion(version));
+        }
+
+        for migrate in MIGRATIONS[(version - 1) as usize..].iter() {
+            value = migrate(value)?;
+        }
+        serde_json::from_value(value).map_err(|err| ReplayError::Parse(err.to_string()))
+    }
+
+    /// Check the fleet is the one shuffled from the seed and that replaying the events scores `claimed` points
+    /// Return: the replayed session
+    pub fn verify_score(&self, claimed: i32) -> Result<Session, ReplayError> {
+        let seed = self.seed.ok_or(ReplayError::Unseeded)?;
+        let ships = self.fleet.iter()
+            .map(|placement| match self.designs.iter().find(|design| design.ship_type == placement.ship_type) {
+                Some(design) => Ship::from_design(design).map_err(|_| ReplayError::InvalidFleet),
+                None => Ok(Ship::build(placement.ship_type)),
+            })
+            .collect::<Result<Vec<Ship>, ReplayError>>()?;
+        let mut grid = match &self.board {
+            Some(board) => Grid::build_from_template(board),
+            None => Grid::build(),
+        };
+        grid.set_ships(ships);
+        grid.shuffle_ship_location_seeded(seed).map_err(|_| ReplayError::FleetMismatch)?;
+        if grid.get_fleet_layout() != self.fleet {
+            return Err(ReplayError::FleetMismatch);
+        }
+
+        let session = Replayer::new(self.clone())?.final_session();
+        if session.get_points() != claimed {
+            return Err(ReplayError::ScoreMismatch { claimed, replayed: session.get_points() });
+        }
+        Ok(session)
+    }
+
+    /// Shot records only, in order
+    pub fn get_shots(&self) -> Vec<ShotRecord> {
+        self.events.iter().filter_map(|event| match event {
+            ReplayEvent::Shot(record) => Some(*record),
+            _ => None,
+        }).collect()
+    }
+}
+
+/// Version 1 kept the shot budget and the hint cost of a classic game, they become its rules.
+/// Replays written before the version was bumped may already have rules, those are kept.
+fn migrate_v1_to_v2(mut value: Value) -> Result<Value, ReplayError> {
+    let object = value.as_object_mut().ok_or_else(|| ReplayError::Parse(String::from("replay is not an object")))?;
+    if !object.contains_key("rules") {
+        let mut rules = GameRules::classic();
+        if let Some(shot_budget) = object.remove("shot_budget") {
+            rules.shot_budget = Some(shot_budget.as_i64().ok_or_else(|| ReplayError::Parse(String::from("shot budget is not a number")))? as i32);
+        }
+        if let Some(hint_cost) = object.remove("hint_cost") {
+            rules.hint_cost = hint_cost.as_i64().ok_or_else(|| ReplayError::Parse(String::from("hint cost is not a number")))? as i32;
+        }
+        object.insert(String::from("rules"), serde_json::to_value(rules).expect("rules are always serialisable"));
+    }
+    object.insert(String::from("format_version"), Value::from(2));
+    Ok(value)
+}
+
+/// Reasons a replay can not be imported or rebuilt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    /// The JSON could not be read
+    Parse(String),
+    /// The replay was written by a newer version of the game
+    UnsupportedVersion(u32),
+    /// The fleet overlaps, repeats a ship, leaves the grid or sits on terrain
+    InvalidFleet,
+    /// The fleet was not shuffled from a seed, so it can not be checked
+    Unseeded,
+    /// The fleet is not the one shuffled from the seed
+    FleetMismatch,
+    /// Replaying the events does not score the points claimed
+    ScoreMismatch { claimed: i32, replayed: i32 },
+    /// Shots were taken back during the game
+    UndoUsed,
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayError::Parse(err) => write!(f, "could not read replay: {}", err),
+            ReplayError::UnsupportedVersion(version) => write!(f, "replay format version {} is newer than {}", version, REPLAY_FORMAT_VERSION),
+            ReplayError::InvalidFleet => write!(f, "replay fleet layout is not valid"),
+            ReplayError::Unseeded => write!(f, "replay fleet was not shuffled from a seed"),
+            ReplayError::FleetMismatch => write!(f, "replay fleet is not the one shuffled from its seed"),
+            ReplayError::ScoreMismatch { claimed, replayed } => write!(f, "replay scores {} points, not the {} claimed", replayed, claimed),
+            ReplayError::UndoUsed => write!(f, "shots were taken back during the game"),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+/// Rebuilds the `Session` of a replay at any step
+pub struct Replayer {
+    replay: Replay,
+    grid: Grid,
+}
+
+impl Replayer {
+    pub fn new(replay: Replay) -> Result<Replayer, ReplayError> {
+        let grid = Grid::build_from_designs_on(replay.board.as_ref(), &replay.designs, &replay.fleet).ok_or(ReplayError::InvalidFleet)?;
+        Ok(Replayer { replay, grid })
+    }
+
+    /// Number of events in the replay
+    pub fn len(&self) -> usize {
+        self.replay.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.replay.events.is_empty()
+    }
+
+    pub fn get_replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    /// Session after the first `step` events, `None` if the replay is shorter than that
+    pub fn session_at(&self, step: usize) -> Option<Session> {
+        let events = self.replay.events.get(..step)?;
+        let mut session = Session::build_with_rules(self.replay.player_name.clone(), self.grid.clone(), self.replay.rules);
+        if self.replay.practice {
+            session.enable_practice_mode();
+        }
+        session.set_movement_seed(self.replay.movement_seed);
+        // time only passes through the recorded timeouts, never while rebuilding
+        session.set_clock(Arc::new(ManualClock::new(0)));
+
+        for event in events {
+            match event {
+                ReplayEvent::Shot(record) => { session.shoot_single(record.point); },
+                ReplayEvent::Hint { .. } => { session.suggest_shot(); },
+                ReplayEvent::Undo { .. } => { session.undo_last_shot(); },
+                ReplayEvent::Weapon(record) => {
+                    let _ = match record.weapon {
+                        Weapon::Sonar => session.scan_sonar(record.target),
+                        Weapon::Torpedo => session.fire_torpedo(record.target, record.direction.unwrap_or(Direction::Right)),
+                        Weapon::Bomb => session.drop_bomb(record.target),
+                    };
+                },
+                // movement is seeded, replaying the turns moves the ships the same way again
+                ReplayEvent::Move { .. } => (),
+                ReplayEvent::Timeout { timestamp } => session.forfeit_shot(*timestamp),
+                ReplayEvent::TimeUp { timestamp } => session.run_out_of_time(*timestamp),
+            }
+        }
+
+        // keep the recorded timestamps rather than the ones from rebuilding
+        let mut replay = self.replay.clone();
+        replay.events.truncate(step);
+        session.set_replay(replay);
+        Some(session)
+    }
+
+    /// Session after every event
+    pub fn final_session(&self) -> Session {
+        self.session_at(self.len()).unwrap()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Replay of three shots and a hint as written before the rules were added
+    const V1: &str = include_str!("../fixtures/replays/v1.json");
+
+    #[test]
+    fn load_version_1_fixture() {
+        let replay = Replay::from_json(V1).unwrap();
+        assert_eq!(REPLAY_FORMAT_VERSION, replay.format_version);
+        assert_eq!(GameRules { hint_cost: 2, ..GameRules::classic() }, replay.rules);
+
+        let session = Replayer::new(replay).unwrap().final_session();
+        assert_eq!((3, 10, 1), (session.get_shots_fired(), session.get_points(), session.get_hints_used()));
+
+        let newer = V1.replacen("\"format_version\": 1", "\"format_version\": 3", 1);
+        assert_eq!(Err(ReplayError::UnsupportedVersion(3)), Replay::from_json(&newer));
+    }
+
+    #[test]
+    fn replay_round_trip() {
+        let mut session = Session::start_with_seed(String::from("Adetayo"), 7, GameRules::default());
+        for n in 1..=6 {
+            session.shoot_ship(GridPoint { x: n, y: n });
+        }
+        session.suggest_shot();
+
+        let json = session.get_replay().to_json();
+        let replay = Replay::from_json(&json).unwrap();
+        assert_eq!(session.get_replay(), &replay);
+        assert_eq!(Some(7), replay.seed);
+        assert_eq!(6, replay.get_shots().len());
+        assert_eq!(7, replay.events.len());
+    }
+
+    #[test]
+    fn step_through_replay() {
+        let mut session = Session::start(String::from("Adetayo"));
+        let mut points = vec![0];
+        for n in 1..=10 {
+            session.shoot_ship(GridPoint { x: n, y: 11 - n });
+            points.push(session.get_points());
+        }
+
+        let replayer = Replayer::new(session.get_replay().clone()).unwrap();
+        assert_eq!(10, replayer.len());
+        for (step, expected) in points.iter().enumerate() {
+            let replayed = replayer.session_at(step).unwrap();
+            assert_eq!(*expected, replayed.get_points());
+            assert_eq!(10 - step as i32, replayed.get_remaining_shots());
+        }
+        assert!(replayer.session_at(11).is_none());
+        assert_eq!(session.get_destroyed_ships().len(), replayer.final_session().get_destroyed_ships().len());
+    }
+
+    #[test]
+    fn replay_with_undo() {
+        let mut session = Session::start(String::from("Adetayo"));
+        session.enable_practice_mode();
+        session.shoot_ship(GridPoint { x: 1, y: 1 });
+        session.shoot_ship(GridPoint { x: 2, y: 2 });
+        session.undo_last_shot();
+
+        let replayer = Replayer::new(session.get_replay().clone()).unwrap();
+        let replayed = replayer.final_session();
+        assert!(replayed.is_undo_used());
+        assert_eq!(9, replayed.get_remaining_shots());
+        assert_eq!(session.get_points(), replayed.get_points());
+    }
+
+    #[test]
+    fn replay_with_weapons() {
+        let mut session = Session::start_with_rules(String::from("Adetayo"), GameRules::tactical());
+        session.drop_bomb(GridPoint { x: 4, y: 4 }).unwrap();
+        session.fire_torpedo(GridPoint { x: 1, y: 7 }, Direction::Right).unwrap();
+        session.scan_sonar(GridPoint { x: 9, y: 2 }).unwrap();
+
+        let replayed = Replayer::new(session.get_replay().clone()).unwrap().final_session();
+        assert_eq!(session.get_points(), replayed.get_points());
+        assert_eq!(session.get_board_view(), replayed.get_board_view());
+        assert_eq!(0, replayed.get_weapons().get_charges(Weapon::Bomb));
+    }
+
+    #[test]
+    fn replay_moving_fleet() {
+        let mut session = Session::start_with_rules(String::from("Adetayo"), GameRules::moving_fleet());
+        for n in 1..=10 {
+            session.shoot_ship(GridPoint { x: n, y: n });
+        }
+        let moves = session.get_replay().events.iter().filter(|event| matches!(event, ReplayEvent::Move { .. })).count();
+        assert!(moves > 0);
+
+        let replay = Replay::from_json(&session.get_replay().to_json()).unwrap();
+        let replayed = Replayer::new(replay).unwrap().final_session();
+        assert_eq!(session.get_omniscient_view(), replayed.get_omniscient_view());
+        assert_eq!(session.get_replay().events.len(), replayed.get_replay().events.len());
+    }
+
+    #[test]
+    fn reject_newer_version() {
+        let mut replay = Session::start(String::from("Adetayo")).get_replay().clone();
+        replay.format_version = REPLAY_FORMAT_VERSION + 1;
+        assert_eq!(Err(ReplayError::UnsupportedVersion(REPLAY_FORMAT_VERSION + 1)), Replay::from_json(&replay.to_json()));
+        assert!(matches!(Replay::from_json("{"), Err(ReplayError::Parse(_))));
+    }
+}
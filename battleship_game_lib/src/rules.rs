@@ -0,0 +1,202 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use serde::{Deserialize, Serialize};
+use crate::runtime::GridPoint;
+use crate::weapons::WeaponInventory;
+
+
+/////////////////////////////////////////////////////////////////////////
+/// How the score of a session is worked out
+/////////////////////////////////////////////////////////////////////////
+///
+///ShipPoints, Efficiency
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Scoring {
+    /// Sum of the points of every ship sunk, plus bonuses and minus penalties
+    ShipPoints,
+    /// The `ShipPoints` total times 10, divided by the number of shots fired
+    Efficiency,
+}
+
+/////////////////////////////////////////////////////////////////////////
+/// When a session counts as won
+/////////////////////////////////////////////////////////////////////////
+///
+///SinkAllShips, ReachScore
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum WinCondition {
+    /// Every ship in the fleet is sunk
+    SinkAllShips,
+    /// The score reaches `GameRules::target_score`
+    ReachScore,
+}
+
+/////////////////////////////////////////////////////////////////////////
+/// What happens when the player fires at a square twice
+/////////////////////////////////////////////////////////////////////////
+///
+///Free, Wasted
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum RepeatShot {
+    /// The shot is refused and does not use up the budget
+    Free,
+    /// The shot uses up the budget and counts as a miss
+    Wasted,
+}
+
+/////////////////////////////////////////////////////////////////////////
+/// How the distance from a miss to the nearest ship is measured
+/////////////////////////////////////////////////////////////////////////
+///
+///Manhattan, Chebyshev
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DistanceMetric {
+    /// Squares along the row plus squares along the column
+    Manhattan,
+    /// The larger of the squares along the row and along the column, so diagonals count as one
+    Chebyshev,
+}
+
+impl DistanceMetric {
+    /// Distance between two squares
+    pub fn distance(&self, from: GridPoint, to: GridPoint) -> i32 {
+        let (dx, dy) = ((from.x - to.x).abs(), (from.y - to.y).abs());
+        match self {
+            DistanceMetric::Manhattan => dx + dy,
+            DistanceMetric::Chebyshev => dx.max(dy),
+        }
+    }
+}
+
+/// Rules of a game, chosen when the game starts and kept with the session
+///
+/// ```
+/// use battleship_game_lib::rules::{GameRules, Scoring};
+///
+/// let mut rules = GameRules::unlimited();
+/// rules.fleet_bonus = 20;
+/// assert_eq!(Scoring::Efficiency, rules.scoring);
+/// ```
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct GameRules {
+    /// Shots per game, `None` for unlimited shots
+    pub shot_budget: Option<i32>,
+    pub scoring: Scoring,
+    /// Points added once the whole fleet is sunk
+    pub fleet_bonus: i32,
+    /// Points taken off for every miss
+    pub miss_penalty: i32,
+    pub win_condition: WinCondition,
+    /// Score needed with `WinCondition::ReachScore`
+    pub target_score: i32,
+    pub repeat_shot: RepeatShot,
+    /// Points taken off for every hint
+    pub hint_cost: i32,
+    /// Shots are fired in volleys of one shot per surviving ship, see `Session::fire_salvo`
+    #[serde(default)]
+    pub salvo: bool,
+    /// Special weapon charges at the start of the game
+    #[serde(default)]
+    pub weapons: WeaponInventory,
+    /// Ships still afloat move one square or rotate after every turn
+    #[serde(default)]
+    pub moving_fleet: bool,
+    /// A miss reports the distance to the nearest ship still afloat, `None` to report nothing
+    #[serde(default)]
+    pub miss_distance: Option<DistanceMetric>,
+    /// Milliseconds allowed for each shot, a shot not taken in time is forfeited as a miss. `None` for no limit
+    #[serde(default)]
+    pub shot_time_limit: Option<u64>,
+    /// Milliseconds allowed for the whole game, the game is over once they are used up. `None` for no limit
+    #[serde(default)]
+    pub total_time_limit: Option<u64>,
+}
+
+/// Default rules are the classic game from the README
+impl Default for GameRules {
+    fn default() -> Self {
+        GameRules::classic()
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl GameRules {
+    /// Ten shots, ship points only, the game is won by sinking every ship
+    pub fn classic() -> GameRules {
+        GameRules {
+            shot_budget: Some(10),
+            scoring: Scoring::ShipPoints,
+            fleet_bonus: 0,
+            miss_penalty: 0,
+            win_condition: WinCondition::SinkAllShips,
+            target_score: 0,
+            repeat_shot: RepeatShot::Free,
+            hint_cost: 0,
+            salvo: false,
+            weapons: WeaponInventory::default(),
+            moving_fleet: false,
+            miss_distance: None,
+            shot_time_limit: None,
+            total_time_limit: None,
+        }
+    }
+
+    /// Unlimited shots, the score rewards sinking the fleet in as few shots as possible
+    pub fn unlimited() -> GameRules {
+        GameRules { shot_budget: None, scoring: Scoring::Efficiency, ..GameRules::classic() }
+    }
+
+    /// Salvo variant, one shot per surviving ship each turn with no shot budget
+    pub fn salvo() -> GameRules {
+        GameRules { shot_budget: None, salvo: true, ..GameRules::classic() }
+    }
+
+    /// Classic rules with one sonar scan, one torpedo and one bomb
+    pub fn tactical() -> GameRules {
+        GameRules { weapons: WeaponInventory::new(1, 1, 1), ..GameRules::classic() }
+    }
+
+    /// Advanced variant where the fleet moves after every turn, with unlimited shots
+    pub fn moving_fleet() -> GameRules {
+        GameRules { shot_budget: None, moving_fleet: true, ..GameRules::classic() }
+    }
+
+    /// Warmer/colder variant of the classic game, every miss reports the Manhattan distance to the nearest ship
+    pub fn warmer_colder() -> GameRules {
+        GameRules { miss_distance: Some(DistanceMetric::Manhattan), ..GameRules::classic() }
+    }
+
+    /// Blitz variant of the classic game, five seconds per shot and a minute for the whole game
+    pub fn blitz() -> GameRules {
+        GameRules { shot_time_limit: Some(5_000), total_time_limit: Some(60_000), ..GameRules::classic() }
+    }
+
+    /// Score for `ship_points` (ship points, bonuses and penalties) after `shots_fired` shots
+    pub fn score(&self, ship_points: i32, shots_fired: i32) -> i32 {
+        match self.scoring {
+            Scoring::ShipPoints => ship_points,
+            Scoring::Efficiency => ship_points * 10 / shots_fired.max(1),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn efficiency_score() {
+        let rules = GameRules::unlimited();
+        assert_eq!(60, rules.score(30, 5));
+        assert_eq!(3, rules.score(30, 100));
+        assert_eq!(0, rules.score(0, 0));
+        assert_eq!(30, GameRules::classic().score(30, 100));
+    }
+}
@@ -2,22 +2,35 @@
 use wasm_bindgen::prelude::*;
 
 use std::fmt;
-use crate::inventory::{ship::{ShipType, Ship}, grid::Grid};
+use std::sync::{Arc, atomic::{AtomicU64, Ordering}};
+use rand::{rngs::StdRng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use crate::inventory::{ship::{ShipType, Ship}, grid::{Grid, PlacementError}};
+use crate::notation::Notation;
+use crate::replay::{Replay, ReplayEvent, ShipPlacement, ShotRecord, WeaponRecord};
+use crate::rules::{GameRules, RepeatShot, WinCondition};
+use crate::weapons::{Direction, SonarStatus, Weapon, WeaponError, WeaponInventory, WeaponOutcome};
+use crate::strategy::DensityMap;
+use crate::terrain::{BoardTemplate, CellKind};
+use crate::view::BoardView;
 
 
 ///Gridpoint representation of the 2 dimensional array
 /// x > 0, x < 11 ,1-10 inclusive
 /// y > 0, y < 11 ,1-10 inclusive
 #[cfg_attr(feature = "wasm", wasm_bindgen)]
-#[derive(Debug,Clone,Copy, PartialEq, Eq, Hash)]
+#[derive(Debug,Clone,Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct GridPoint {
     pub x: i32,
     pub y: i32,
 }
 
-///Display GridPoint
+///Display GridPoint, the alternate form `{:#}` writes the square in letter-number notation such as B7
 impl fmt::Display for GridPoint {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if f.alternate() {
+            return write!(f, "{}", Notation::default().format(*self));
+        }
         write!(f, "GridPoint(x = {}, y = {})", &self.x, &self.y)
     }
 }
@@ -42,18 +55,152 @@ impl GridPoint {
 }
 
 #[cfg_attr(feature = "wasm", wasm_bindgen)]
-#[derive(Debug,Clone, Copy,PartialEq, Eq, Hash)]
+#[derive(Debug,Clone, Copy,PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ShotStatus {
     Hit,
     Miss,
-    Repeat
+    Repeat,
+    /// The square is an island, the shot is refused and does not use up the budget
+    Blocked,
+    /// The game is over, the shot is refused
+    Over,
+    /// The square is not on the board, the shot is refused and does not use up the budget
+    OffBoard,
+    /// The game is played in salvos, single shots are refused and do not use up the budget
+    Salvo
 }
 
 #[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug,Clone,Copy,PartialEq, Eq)]
 pub struct Shot {
     pub status: ShotStatus,
     pub ship_type: Option<ShipType>,
-    pub point: Option<i32>
+    pub point: Option<i32>,
+    /// Distance from a miss to the nearest ship still afloat, only with `GameRules::miss_distance`
+    pub distance: Option<i32>
+}
+
+///Outcome of a whole salvo, revealed once every shot of the volley has been resolved
+#[derive(Debug,Clone,PartialEq, Eq)]
+pub struct SalvoResult {
+    pub shots: Vec<Shot>,
+    pub hits: i32,
+    /// Ship points scored by the volley
+    pub points: i32
+}
+
+///Reasons a salvo is refused
+#[derive(Debug,Clone,Copy,PartialEq, Eq)]
+pub enum SalvoError {
+    /// The rules of the game are not the salvo rules
+    NotSalvoGame,
+    /// The volley must have one shot per surviving ship
+    WrongVolleySize { expected: usize, fired: usize },
+    /// The same square appears more than once in the volley
+    DuplicatePoint(GridPoint),
+    /// A square of the volley is an island
+    BlockedPoint(GridPoint),
+    /// A square of the volley is not on the board
+    OffGrid(GridPoint),
+    /// The game is already over
+    GameOver,
+}
+
+impl fmt::Display for SalvoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SalvoError::NotSalvoGame => write!(f, "salvos can only be fired in salvo games"),
+            SalvoError::WrongVolleySize { expected, fired } => write!(f, "salvo must have {} shots, {} were fired", expected, fired),
+            SalvoError::DuplicatePoint(point) => write!(f, "{} appears more than once in the salvo", point),
+            SalvoError::BlockedPoint(point) => write!(f, "{} is an island", point),
+            SalvoError::OffGrid(point) => write!(f, "{} is not on the grid", point),
+            SalvoError::GameOver => write!(f, "the game is over"),
+        }
+    }
+}
+
+impl std::error::Error for SalvoError {}
+
+///Suggested square to fire at, with the estimated probability (0.0 - 1.0) of a hit
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Hint {
+    pub point: GridPoint,
+    pub confidence: f64
+}
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = Date, js_name = now)]
+    fn date_now() -> f64;
+}
+
+///Milliseconds since the unix epoch
+pub(crate) fn now_millis() -> u64 {
+    #[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+    return date_now() as u64;
+
+    #[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+///Source of the time for the clocks of timed games and the timestamps of the replay
+pub trait Clock: fmt::Debug + Send + Sync {
+    /// Milliseconds since a fixed point in the past
+    fn now_millis(&self) -> u64;
+}
+
+///Wall clock time, milliseconds since the unix epoch
+#[derive(Debug,Clone,Copy,Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_millis(&self) -> u64 {
+        now_millis()
+    }
+}
+
+///Clock that only moves when told to, for tests and for front-ends that keep their own time.
+///Clones share the same time.
+#[derive(Debug,Clone,Default)]
+pub struct ManualClock {
+    millis: Arc<AtomicU64>
+}
+
+impl ManualClock {
+    pub fn new(millis: u64) -> ManualClock {
+        ManualClock { millis: Arc::new(AtomicU64::new(millis)) }
+    }
+
+    /// Move the time forward by `millis`
+    pub fn advance(&self, millis: u64) {
+        self.millis.fetch_add(millis, Ordering::Relaxed);
+    }
+
+    pub fn set(&self, millis: u64) {
+        self.millis.store(millis, Ordering::Relaxed);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now_millis(&self) -> u64 {
+        self.millis.load(Ordering::Relaxed)
+    }
+}
+
+///Outcome of a game, kept once the game is over
+#[derive(Debug,Clone,PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameSummary {
+    pub player_name: String,
+    pub points: i32,
+    pub shots_fired: i32,
+    pub ships_sunk: i32,
+    pub won: bool,
+    /// Shots forfeited because their time ran out
+    pub timeouts: i32,
+    /// Milliseconds from the start of the game to its end, or to now if it is still going
+    pub time_used: u64
 }
 
 #[cfg_attr(feature = "wasm", wasm_bindgen)]
@@ -62,26 +209,70 @@ pub struct Session {
     pub debug: bool,
     shot_history: Vec<GridPoint>,
     points: i32,
-    remaining_shots: i32,
+    shots_fired: i32,
+    hints_used: i32,
+    rules: GameRules,
+    weapons: WeaponInventory,
+    practice: bool,
+    undo_used: bool,
+    movement_seed: u64,
+    movement_phases: u64,
     player_name: String,
-    grid: Grid
+    grid: Grid,
+    replay: Replay,
+    clock: Arc<dyn Clock>,
+    started_at: u64,
+    turn_started: u64,
+    stopped_at: Option<u64>,
+    time_up: bool,
+    timeouts: i32
 }
 
 #[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl Session {
 
     pub fn build() -> Session {
-        Session { points: 0, shot_history: Vec::new(), remaining_shots: 10, player_name: String::new(), grid: Grid::build(), debug: false }
+        Self::build_from_allocation(String::new(), Grid::build())
     }
     pub fn build_from_allocation(player_name: String, grid: Grid) -> Session {
-        Session { points: 0, shot_history: Vec::new(), remaining_shots: 10, player_name, grid, debug: false }
+        Self::build_with_rules(player_name, grid, GameRules::default())
+    }
+
+    pub fn build_with_rules(player_name: String, grid: Grid, rules: GameRules) -> Session {
+        let mut replay = Replay::build(player_name.clone(), None, &grid, rules);
+        let movement_seed = rand::random();
+        replay.movement_seed = movement_seed;
+        let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+        let started_at = clock.now_millis();
+        Session { points: 0, shot_history: Vec::new(), shots_fired: 0, hints_used: 0, rules, weapons: rules.weapons, practice: false, undo_used: false, movement_seed, movement_phases: 0, player_name, grid, debug: false, replay,
+            clock, started_at, turn_started: started_at, stopped_at: None, time_up: false, timeouts: 0 }
     }
 
     pub fn start(player_name: String) -> Session{
+        Self::start_with_rules(player_name, GameRules::default())
+    }
+
+    pub fn start_with_rules(player_name: String, rules: GameRules) -> Session{
+        Self::start_with_seed(player_name, rand::random(), rules)
+    }
+
+    /// Start a session whose fleet is shuffled from `seed`, the same seed always gives the same fleet
+    pub fn start_with_seed(player_name: String, seed: u64, rules: GameRules) -> Session{
         let mut grid = Grid::build();
-        grid.shuffle_ship_location();
+        grid.shuffle_ship_location_seeded(seed).expect("the classic fleet always fits");
+
+        let mut session = Self::build_with_rules(player_name, grid, rules);
+        session.replay.seed = Some(seed);
+        session
+    }
 
-        Self::build_from_allocation(player_name, grid)
+    pub fn get_rules(&self) -> GameRules {
+        self.rules
+    }
+
+    /// Special weapon charges left
+    pub fn get_weapons(&self) -> WeaponInventory {
+        self.weapons
     }
 
     pub fn get_player_name(&self) -> String {
@@ -92,42 +283,578 @@ impl Session {
         self.grid.display_ships_location()
     }
 
+    /// Board as the player sees it, ships still afloat are only shown in debug mode
+    pub fn get_board_view(&self) -> BoardView {
+        self.grid.build_view(&self.shot_history, self.debug)
+    }
+
+    /// Board with every ship shown, for finished games and debugging
+    pub fn get_omniscient_view(&self) -> BoardView {
+        self.grid.build_view(&self.shot_history, true)
+    }
+
     pub fn is_any_ship_left(&self) -> bool{
         self.grid.is_any_ship_left()
     }
 
     pub fn is_shot_available(&self) -> bool{
-        self.remaining_shots > 0
+        self.get_remaining_shots() > 0
     }
 
+    /// Shots left in the budget, `i32::MAX` when the rules allow unlimited shots
     pub fn get_remaining_shots(&self) -> i32{
-        self.remaining_shots
+        match self.rules.shot_budget {
+            Some(budget) => budget - self.shots_fired,
+            None => i32::MAX,
+        }
+    }
+
+    /// Shots that used up the budget so far
+    pub fn get_shots_fired(&self) -> i32{
+        self.shots_fired
+    }
+
+    /// The win condition of the rules has been met
+    pub fn is_won(&self) -> bool{
+        match self.rules.win_condition {
+            WinCondition::SinkAllShips => !self.is_any_ship_left(),
+            WinCondition::ReachScore => self.get_points() >= self.rules.target_score,
+        }
+    }
+
+    /// The game is won, out of shots, out of ships to sink or out of time
+    pub fn is_over(&self) -> bool{
+        self.is_won() || !self.is_shot_available() || !self.is_any_ship_left() || self.time_up
+    }
+
+    /// Apply the time limits of the rules: every shot not taken in time is forfeited as a miss and
+    /// the game ends once the total time is used up. Front-ends of timed games call this regularly.
+    /// Returns the number of shots forfeited.
+    pub fn tick(&mut self) -> i32 {
+        if self.is_over() {
+            return 0;
+        }
+
+        let now = self.clock.now_millis();
+        let deadline = self.rules.total_time_limit.map(|limit| self.started_at + limit);
+        // shots can not time out after the game itself has
+        let cutoff = deadline.map_or(now, |deadline| now.min(deadline));
+        let mut forfeited = 0;
+        if let Some(limit) = self.rules.shot_time_limit {
+            let limit = limit.max(1);
+            while !self.is_over() && cutoff.saturating_sub(self.turn_started) >= limit {
+                self.forfeit_shot(self.turn_started + limit);
+                forfeited += 1;
+            }
+        }
+        if let Some(deadline) = deadline {
+            if now >= deadline && !self.is_over() {
+                self.run_out_of_time(deadline);
+            }
+        }
+        forfeited
+    }
+
+    /// The total time of the game ran out
+    pub fn is_time_up(&self) -> bool {
+        self.time_up
+    }
+
+    /// Shots forfeited because their time ran out
+    pub fn get_timeouts(&self) -> i32 {
+        self.timeouts
+    }
+
+    /// Milliseconds played, up to the end of the game once it is over
+    pub fn get_time_used(&self) -> u64 {
+        let used = self.stopped_at.unwrap_or_else(|| self.clock.now_millis()).saturating_sub(self.started_at);
+        self.rules.total_time_limit.map_or(used, |limit| used.min(limit))
+    }
+
+    /// Milliseconds left to take the current shot, `None` without a shot time limit
+    pub fn get_shot_time_left(&self) -> Option<u64> {
+        let now = self.clock.now_millis();
+        self.rules.shot_time_limit.map(|limit| (self.turn_started + limit).saturating_sub(now))
+    }
+
+    /// Milliseconds left for the whole game, `None` without a total time limit
+    pub fn get_total_time_left(&self) -> Option<u64> {
+        self.rules.total_time_limit.map(|limit| limit.saturating_sub(self.get_time_used()))
     }
 
     pub fn get_destroyed_ships(&self) -> Vec<Ship>{
         self.grid.get_destroyed_ships()
     }
 
+    /// Ships still afloat
+    pub fn get_remaining_ships(&self) -> Vec<Ship>{
+        self.grid.get_remaining_ships()
+    }
+
+    /// Fire a single shot and end the turn. Once the game is over the shot is refused with `ShotStatus::Over`,
+    /// this includes a shot taken after its time ran out, it was already forfeited.
+    /// Salvo games only take whole volleys through `fire_salvo`, single shots are refused with `ShotStatus::Salvo`.
     pub fn shoot_ship(&mut self, proj_loc: GridPoint) -> Shot {
-        for grid in self.shot_history.iter() {
-            if grid.x == proj_loc.x && grid.y == proj_loc.y {
-                return Shot{ status: ShotStatus::Repeat, ship_type: None, point: None };
+        if self.rules.salvo {
+            return Shot{ status: ShotStatus::Salvo, ship_type: None, point: None, distance: None };
+        }
+        self.shoot_single(proj_loc)
+    }
+
+    /// Single shot of a recorded game, replays keep the shots of a salvo one by one
+    pub(crate) fn shoot_single(&mut self, proj_loc: GridPoint) -> Shot {
+        self.tick();
+        if self.is_over() {
+            return Shot{ status: ShotStatus::Over, ship_type: None, point: None, distance: None };
+        }
+        let shots_fired = self.shots_fired;
+        let shot = self.fire_at(proj_loc);
+        if self.shots_fired > shots_fired {
+            self.end_turn();
+        }
+        shot
+    }
+
+    /// Score under the scoring rule of the session
+    pub fn get_points (&self) -> i32 {
+        self.rules.score(self.points, self.shots_fired)
+    }
+
+    /// Suggest the square most likely to hold a ship, based on the shot history and
+    /// the ships still afloat. Every call counts as a hint used and deducts the hint cost.
+    /// Returns `None` once the game is over.
+    pub fn suggest_shot(&mut self) -> Option<Hint> {
+        if !self.is_shot_available() || !self.is_any_ship_left() {
+            return None;
+        }
+
+        let shapes: Vec<Vec<Vec<(i32, i32)>>> = self.grid.get_remaining_ships().iter().map(|ship| ship.get_rotations()).collect();
+        // no ship can be on the terrain either
+        let mut blocked = self.shot_history.clone();
+        blocked.extend(self.grid.get_terrain_squares());
+        let density = DensityMap::build_with_shapes(self.grid.get_width(), self.grid.get_height(), &shapes, &blocked);
+        let (point, confidence) = density.best_point()?;
+
+        self.hints_used += 1;
+        self.points -= self.rules.hint_cost;
+        self.replay.events.push(ReplayEvent::Hint { point, timestamp: self.clock.now_millis() });
+        Some(Hint { point, confidence })
+    }
+
+    /// Points deducted every time a hint is requested, 0 by default
+    pub fn set_hint_cost(&mut self, hint_cost: i32) {
+        self.rules.hint_cost = hint_cost;
+        self.replay.rules.hint_cost = hint_cost;
+    }
+
+    pub fn get_hint_cost(&self) -> i32 {
+        self.rules.hint_cost
+    }
+
+    pub fn get_hints_used(&self) -> i32 {
+        self.hints_used
+    }
+
+    /// A session is assisted once any hint has been used
+    pub fn is_assisted(&self) -> bool {
+        self.hints_used > 0
+    }
+
+    /// Allow shots to be taken back with `undo_last_shot`, for practice and teaching games
+    pub fn enable_practice_mode(&mut self) {
+        self.practice = true;
+        self.replay.practice = true;
+    }
+
+    pub fn is_practice_mode(&self) -> bool {
+        self.practice
+    }
+
+    /// Take back the last shot in practice mode, restoring the shots, points and any ship it sank.
+    /// Returns the square that was un-fired, `None` outside practice mode or before the first shot.
+    pub fn undo_last_shot(&mut self) -> Option<GridPoint> {
+        if !self.practice {
+            return None;
+        }
+
+        // the replay keeps undone shots, each undo cancels the latest shot not cancelled yet.
+        // Special weapons and fleet movements can not be taken back, so nothing before one can be either.
+        let mut undone = 0;
+        let mut last_shot = None;
+        for event in self.replay.events.iter().rev() {
+            match event {
+                ReplayEvent::Undo { .. } => undone += 1,
+                ReplayEvent::Shot(_) if undone > 0 => undone -= 1,
+                ReplayEvent::Shot(record) => { last_shot = Some(*record); break; },
+                ReplayEvent::Weapon(_) | ReplayEvent::Move { .. } | ReplayEvent::Timeout { .. } | ReplayEvent::TimeUp { .. } => return None,
+                ReplayEvent::Hint { .. } => (),
+            }
+        }
+        let record = last_shot?;
+
+        if record.status != ShotStatus::Repeat {
+            self.shot_history.pop();
+        }
+        self.shots_fired -= 1;
+        self.points -= record.points;
+        if let Some(ship_type) = record.ship_type {
+            self.grid.restore_ship(ship_type);
+        }
+
+        if !self.is_over() {
+            self.stopped_at = None;
+        }
+        self.undo_used = true;
+        self.replay.events.push(ReplayEvent::Undo { point: record.point, timestamp: self.clock.now_millis() });
+        Some(record.point)
+    }
+
+    /// Games where a shot was taken back are kept off the high score table
+    pub fn is_undo_used(&self) -> bool {
+        self.undo_used
+    }
+
+    /// Replay of the session so far as JSON, see `Replay::to_json`
+    pub fn export_replay(&self) -> String {
+        self.replay.to_json()
+    }
+}
+
+impl Session {
+    /// Start a session with a custom fleet, each ship type at most once, shuffled from `seed`
+    /// Return: `PlacementError` if the fleet does not fit on a 10 by 10 grid
+    pub fn start_with_fleet(player_name: String, fleet: &[ShipType], seed: u64, rules: GameRules) -> Result<Session, PlacementError> {
+        Self::start_on_grid(player_name, Grid::build_with_fleet(fleet), seed, rules)
+    }
+
+    /// Start a session with ships of any shape, see `Ship::with_shape`, each ship type at most once, shuffled from `seed`
+    /// Return: `PlacementError` if the fleet does not fit on a 10 by 10 grid
+    pub fn start_with_ships(player_name: String, ships: Vec<Ship>, seed: u64, rules: GameRules) -> Result<Session, PlacementError> {
+        let mut grid = Grid::build();
+        grid.set_ships(ships);
+        Self::start_on_grid(player_name, grid, seed, rules)
+    }
+
+    /// Start a session on the terrain of `template` with a fleet of `fleet`, shuffled from `seed`
+    /// Return: `PlacementError` if the fleet does not fit on the board
+    pub fn start_on_board(player_name: String, template: &BoardTemplate, fleet: &[ShipType], seed: u64, rules: GameRules) -> Result<Session, PlacementError> {
+        template.check_fleet(fleet)?;
+        let mut grid = Grid::build_from_template(template);
+        grid.set_fleet(fleet);
+        Self::start_on_grid(player_name, grid, seed, rules)
+    }
+
+    /// Take the time from `clock` rather than the system clock, the game clocks restart from its time
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+        self.started_at = self.clock.now_millis();
+        self.turn_started = self.started_at;
+        self.stopped_at = None;
+    }
+
+    /// Summary of the game so far
+    pub fn get_summary(&self) -> GameSummary {
+        GameSummary {
+            player_name: self.player_name.clone(),
+            points: self.get_points(),
+            shots_fired: self.shots_fired,
+            ships_sunk: self.get_destroyed_ships().len() as i32,
+            won: self.is_won(),
+            timeouts: self.timeouts,
+            time_used: self.get_time_used(),
+        }
+    }
+
+    fn start_on_grid(player_name: String, mut grid: Grid, seed: u64, rules: GameRules) -> Result<Session, PlacementError> {
+        grid.shuffle_ship_location_seeded(seed)?;
+
+        let mut session = Self::build_with_rules(player_name, grid, rules);
+        session.replay.seed = Some(seed);
+        Ok(session)
+    }
+
+    /// Replay of the session so far
+    pub fn get_replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    pub(crate) fn set_replay(&mut self, replay: Replay) {
+        self.replay = replay;
+    }
+
+    /// Fire a volley with one shot per ship still afloat on the grid (capped by the shots left).
+    /// All shots are resolved before the result is returned.
+    pub fn fire_salvo(&mut self, points: Vec<GridPoint>) -> Result<SalvoResult, SalvoError> {
+        let survivors = self.grid.get_remaining_ships().len();
+        self.fire_volley(points, survivors)
+    }
+
+    /// Volley with `survivors` shots allowed, checked against the rules and the shots left
+    fn fire_volley(&mut self, points: Vec<GridPoint>, survivors: usize) -> Result<SalvoResult, SalvoError> {
+        if !self.rules.salvo {
+            return Err(SalvoError::NotSalvoGame);
+        }
+        self.tick();
+        if self.is_over() {
+            return Err(SalvoError::GameOver);
+        }
+
+        let expected = survivors.min(self.get_remaining_shots() as usize);
+        if points.len() != expected {
+            return Err(SalvoError::WrongVolleySize { expected, fired: points.len() });
+        }
+        for (n, point) in points.iter().enumerate() {
+            if points[..n].contains(point) {
+                return Err(SalvoError::DuplicatePoint(*point));
+            }
+            if !self.grid.is_on_grid(*point) {
+                return Err(SalvoError::OffGrid(*point));
+            }
+            if self.grid.get_cell_kind(*point) == Some(CellKind::Island) {
+                return Err(SalvoError::BlockedPoint(*point));
+            }
+        }
+
+        let shots: Vec<Shot> = points.into_iter().map(|point| self.fire_at(point)).collect();
+        self.end_turn();
+        let hits = shots.iter().filter(|shot| shot.status == ShotStatus::Hit).count() as i32;
+        let points = shots.iter().filter_map(|shot| shot.point).sum();
+        Ok(SalvoResult { shots, hits, points })
+    }
+
+    /// Sonar scan of the 3x3 area around `center`, uses one shot and one sonar charge
+    pub fn scan_sonar(&mut self, center: GridPoint) -> Result<WeaponOutcome, WeaponError> {
+        self.check_weapon(Weapon::Sonar, &[center])?;
+
+        let contact = (-1..=1)
+            .flat_map(|dy| (-1..=1).map(move |dx| GridPoint { x: center.x + dx, y: center.y + dy }))
+            .any(|point| self.grid.is_on_grid(point) && self.grid.get_ship(point).is_some());
+        let sonar = if contact { SonarStatus::Contact } else { SonarStatus::Clear };
+
+        Ok(self.use_weapon(Weapon::Sonar, center, None, Some(sonar), Vec::new()))
+    }
+
+    /// Torpedo from `start` along its row or column in `direction`, sinking the first ship in its way.
+    /// Uses one shot and one torpedo charge.
+    pub fn fire_torpedo(&mut self, start: GridPoint, direction: Direction) -> Result<WeaponOutcome, WeaponError> {
+        self.check_weapon(Weapon::Torpedo, &[start])?;
+
+        let mut path = Vec::new();
+        let mut point = start;
+        // an island stops the torpedo before it gets there
+        while self.grid.is_on_grid(point) && self.grid.get_cell_kind(point) != Some(CellKind::Island) {
+            path.push(point);
+            if self.grid.get_ship(point).is_some() {
+                break;
+            }
+            point = direction.step(point);
+        }
+
+        let squares = self.strike(path);
+        Ok(self.use_weapon(Weapon::Torpedo, start, Some(direction), None, squares))
+    }
+
+    /// Bomb covering the 2x2 area from `top_left`, uses one shot and one bomb charge
+    pub fn drop_bomb(&mut self, top_left: GridPoint) -> Result<WeaponOutcome, WeaponError> {
+        let area: Vec<GridPoint> = [(0, 0), (1, 0), (0, 1), (1, 1)].iter()
+            .map(|(dx, dy)| GridPoint { x: top_left.x + dx, y: top_left.y + dy })
+            .collect();
+        self.check_weapon(Weapon::Bomb, &area)?;
+
+        let squares = self.strike(area);
+        Ok(self.use_weapon(Weapon::Bomb, top_left, None, None, squares))
+    }
+
+    fn check_weapon(&mut self, weapon: Weapon, targets: &[GridPoint]) -> Result<(), WeaponError> {
+        self.tick();
+        if self.is_over() {
+            return Err(WeaponError::GameOver);
+        }
+        if let Some(point) = targets.iter().find(|point| !self.grid.is_on_grid(**point)) {
+            return Err(WeaponError::OffGrid(*point));
+        }
+        if self.weapons.get_charges(weapon) <= 0 {
+            return Err(WeaponError::NoCharges(weapon));
+        }
+        Ok(())
+    }
+
+    /// Fire at every square not fired at before, except islands, without using up the budget
+    fn strike(&mut self, squares: Vec<GridPoint>) -> Vec<(GridPoint, Shot)> {
+        let mut struck = Vec::new();
+        for point in squares {
+            if self.shot_history.contains(&point) || self.grid.get_cell_kind(point) == Some(CellKind::Island) {
+                continue;
+            }
+            self.shot_history.push(point);
+            let shot = match self.grid.hit_ship(point) {
+                shot @ Shot{ status: ShotStatus::Hit, .. } => shot,
+                _ => Shot{ status: ShotStatus::Miss, ship_type: None, point: None, distance: None }
+            };
+            struck.push((point, shot));
+        }
+        struck
+    }
+
+    fn use_weapon(&mut self, weapon: Weapon, target: GridPoint, direction: Option<Direction>, sonar: Option<SonarStatus>, squares: Vec<(GridPoint, Shot)>) -> WeaponOutcome {
+        let mut points: i32 = squares.iter().filter_map(|(_, shot)| shot.point).sum();
+        if points > 0 && !self.is_any_ship_left() {
+            points += self.rules.fleet_bonus;
+        }
+
+        self.weapons.take(weapon);
+        self.shots_fired += 1;
+        self.points += points;
+        self.replay.events.push(ReplayEvent::Weapon(WeaponRecord { weapon, target, direction, points, timestamp: self.clock.now_millis() }));
+        self.end_turn();
+        WeaponOutcome { weapon, sonar, squares, points }
+    }
+
+    /// Resolve a single shot without ending the turn
+    fn fire_at(&mut self, proj_loc: GridPoint) -> Shot {
+        if !self.grid.is_on_grid(proj_loc) {
+            return Shot{ status: ShotStatus::OffBoard, ship_type: None, point: None, distance: None };
+        }
+        if self.grid.get_cell_kind(proj_loc) == Some(CellKind::Island) {
+            return Shot{ status: ShotStatus::Blocked, ship_type: None, point: None, distance: None };
+        }
+        if self.shot_history.contains(&proj_loc) {
+            if self.rules.repeat_shot == RepeatShot::Wasted {
+                self.record_shot(proj_loc, ShotStatus::Repeat, None, -self.rules.miss_penalty, None);
             }
+            return Shot{ status: ShotStatus::Repeat, ship_type: None, point: None, distance: None };
         }
-        self.remaining_shots -= 1;
-        self.shot_history.push(proj_loc.clone());
+        self.shot_history.push(proj_loc);
 
-        match self.grid.hit_ship(proj_loc.clone()) {
-            Shot{ status: ShotStatus::Hit, ship_type, point} => {
-                self.points += point.unwrap();
-                return Shot{ status: ShotStatus::Hit, ship_type, point};
+        match self.grid.hit_ship(proj_loc) {
+            shot @ Shot{ status: ShotStatus::Hit, .. } => {
+                let bonus = if self.is_any_ship_left() { 0 } else { self.rules.fleet_bonus };
+                self.record_shot(proj_loc, ShotStatus::Hit, shot.ship_type, shot.point.unwrap() + bonus, None);
+                shot
             },
-            _ => Shot{ status: ShotStatus::Miss, ship_type: None, point: None }
+            _ => {
+                let distance = self.rules.miss_distance.and_then(|metric| self.grid.distance_to_nearest_ship(proj_loc, metric));
+                self.record_shot(proj_loc, ShotStatus::Miss, None, -self.rules.miss_penalty, distance);
+                Shot{ status: ShotStatus::Miss, ship_type: None, point: None, distance }
+            }
         }
     }
 
-    pub fn get_points (&self) -> i32 {
-        self.points
+    fn end_turn(&mut self) {
+        let now = self.clock.now_millis();
+        self.end_turn_at(now);
+    }
+
+    /// The clock for the next shot starts at `timestamp`, and after every turn of a moving-fleet game
+    /// the surviving ships get a movement phase
+    fn end_turn_at(&mut self, timestamp: u64) {
+        self.turn_started = timestamp;
+        if self.is_over() {
+            self.stopped_at.get_or_insert(timestamp);
+            return;
+        }
+        if !self.rules.moving_fleet {
+            return;
+        }
+
+        let mut rng = StdRng::seed_from_u64(self.movement_seed.wrapping_add(self.movement_phases));
+        self.movement_phases += 1;
+        let placements: Vec<ShipPlacement> = self.grid.move_ships(&mut rng, &self.shot_history);
+        if !placements.is_empty() {
+            self.replay.events.push(ReplayEvent::Move { placements, timestamp });
+        }
+    }
+
+    /// The time for the current shot ran out at `timestamp`, the shot counts as a miss
+    pub(crate) fn forfeit_shot(&mut self, timestamp: u64) {
+        self.shots_fired += 1;
+        self.points -= self.rules.miss_penalty;
+        self.timeouts += 1;
+        self.replay.events.push(ReplayEvent::Timeout { timestamp });
+        self.end_turn_at(timestamp);
+    }
+
+    /// The time for the whole game ran out at `timestamp`
+    pub(crate) fn run_out_of_time(&mut self, timestamp: u64) {
+        self.time_up = true;
+        self.stopped_at.get_or_insert(timestamp);
+        self.replay.events.push(ReplayEvent::TimeUp { timestamp });
+    }
+
+    pub(crate) fn set_movement_seed(&mut self, movement_seed: u64) {
+        self.movement_seed = movement_seed;
+        self.replay.movement_seed = movement_seed;
+    }
+
+    fn record_shot(&mut self, point: GridPoint, status: ShotStatus, ship_type: Option<ShipType>, points: i32, distance: Option<i32>) {
+        self.shots_fired += 1;
+        self.points += points;
+        self.replay.events.push(ReplayEvent::Shot(ShotRecord { point, status, ship_type, points, distance, timestamp: self.clock.now_millis() }));
+    }
+}
+
+///Two players firing at each other's fleet in turns
+///
+///Each player has a session holding the opponent's grid, so `get_session(0)` is
+///the first player's shots at the second player's fleet.
+#[derive(Debug,Clone)]
+pub struct HeadToHead {
+    sessions: [Session; 2],
+    turn: usize
+}
+
+impl HeadToHead {
+    /// Start a game with a shuffled fleet for each player, the first player fires first
+    pub fn start(first_player: String, second_player: String, rules: GameRules) -> HeadToHead {
+        HeadToHead { sessions: [Session::start_with_rules(first_player, rules), Session::start_with_rules(second_player, rules)], turn: 0 }
+    }
+
+    /// Index (0 or 1) of the player whose turn it is
+    pub fn get_turn(&self) -> usize {
+        self.turn
+    }
+
+    /// Shots of `player` at the opponent's fleet
+    pub fn get_session(&self, player: usize) -> &Session {
+        &self.sessions[player]
+    }
+
+    /// Index of the player who sank the whole opposing fleet first
+    pub fn get_winner(&self) -> Option<usize> {
+        (0..2).find(|&player| !self.sessions[player].is_any_ship_left())
+    }
+
+    /// A player has won, or neither player can fire any more
+    pub fn is_over(&self) -> bool {
+        self.get_winner().is_some() || self.sessions.iter().all(Session::is_over)
+    }
+
+    /// Single shot for the player whose turn it is, repeats and shots at islands or off the board do not end the turn.
+    /// Shots are refused with `ShotStatus::Over` once the game is over.
+    pub fn shoot_ship(&mut self, proj_loc: GridPoint) -> Shot {
+        if self.is_over() {
+            return Shot{ status: ShotStatus::Over, ship_type: None, point: None, distance: None };
+        }
+
+        let shot = self.sessions[self.turn].shoot_ship(proj_loc);
+        match shot.status {
+            ShotStatus::Repeat | ShotStatus::Blocked | ShotStatus::OffBoard | ShotStatus::Salvo => (),
+            // a player out of shots or time passes the turn to the one who can still fire
+            _ => self.turn = 1 - self.turn,
+        }
+        shot
+    }
+
+    /// Salvo for the player whose turn it is, one shot per ship the player still has afloat
+    pub fn fire_salvo(&mut self, points: Vec<GridPoint>) -> Result<SalvoResult, SalvoError> {
+        if self.get_winner().is_some() {
+            return Err(SalvoError::GameOver);
+        }
+
+        let own_fleet = self.sessions[1 - self.turn].grid.get_remaining_ships().len();
+        let result = self.sessions[self.turn].fire_volley(points, own_fleet)?;
+        self.turn = 1 - self.turn;
+        Ok(result)
     }
 }
 
@@ -135,12 +862,32 @@ impl Session {
 #[derive(Debug,Clone)]
 pub struct Play {
     session: Session,
+    /// Save slot of the play once it has been saved
+    save_id: Option<String>,
 }
 
 #[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl Play {
     pub fn init(player_name: String) -> Play {
-       Play { session: Session::start(player_name)}
+       Play { session: Session::start(player_name), save_id: None }
+    }
+
+    pub fn init_with_rules(player_name: String, rules: GameRules) -> Play {
+       Play { session: Session::start_with_rules(player_name, rules), save_id: None }
+    }
+
+    /// Play a session started elsewhere, a game of the server for example
+    pub fn init_with_session(session: Session) -> Play {
+       Play { session, save_id: None }
+    }
+
+    /// Id of the save slot the play was last saved to, `None` before it is first saved
+    pub fn get_save_id(&self) -> Option<String> {
+        self.save_id.clone()
+    }
+
+    pub(crate) fn set_save_id(&mut self, save_id: String) {
+        self.save_id = Some(save_id);
     }
 
     #[cfg(not(feature = "wasm-bindgen"))]
@@ -169,6 +916,10 @@ impl Play {
 mod tests {
 
     use crate::GamePlay;
+    use crate::inventory::ship::Orientation;
+    use crate::replay::ShipPlacement;
+    use crate::rules::DistanceMetric;
+    use crate::view::CellState;
 
     use super::*;
 
@@ -182,6 +933,318 @@ mod tests {
         println!("{:?}", game_session.get_destroyed_ships());
     }
 
+    #[test]
+    fn test_suggest_shot() {
+        let mut game_session = Session::start(String::from("Adetayo"));
+        game_session.set_hint_cost(3);
+
+        let hint = game_session.suggest_shot().unwrap();
+        assert!(hint.confidence > 0.0 && hint.confidence <= 1.0);
+        assert_eq!(1, game_session.get_hints_used());
+        assert_eq!(-3, game_session.get_points());
+        assert!(game_session.is_assisted());
+
+        game_session.shoot_ship(hint.point);
+        assert_ne!(hint.point, game_session.suggest_shot().unwrap().point);
+    }
+
+    #[test]
+    fn test_suggest_shot_after_game_over() {
+        let mut game_session = Session::start(String::from("Adetayo"));
+        for x in 1..=10 {
+            game_session.shoot_ship(GridPoint { x, y: 1 });
+        }
+
+        assert!(game_session.suggest_shot().is_none());
+        assert!(!game_session.is_assisted());
+    }
+
+    #[test]
+    fn test_undo_last_shot() {
+        let grid = Grid::build_from_fleet(&[ShipPlacement { ship_type: ShipType::Submarine, origin: GridPoint { x: 4, y: 4 }, orientation: Orientation::Horizontal }]).unwrap();
+        let mut game_session = Session::build_from_allocation(String::from("Adetayo"), grid);
+        let target = GridPoint { x: 5, y: 4 };
+        game_session.shoot_ship(GridPoint { x: 10, y: 10 });
+        assert_eq!(None, game_session.undo_last_shot());
+
+        game_session.enable_practice_mode();
+        game_session.shoot_ship(target);
+        assert_eq!(1, game_session.get_destroyed_ships().len());
+
+        assert_eq!(Some(target), game_session.undo_last_shot());
+        assert_eq!(0, game_session.get_points());
+        assert_eq!(9, game_session.get_remaining_shots());
+        assert!(game_session.get_destroyed_ships().is_empty());
+        assert_eq!(ShotStatus::Hit, game_session.shoot_ship(target).status);
+        assert!(game_session.is_undo_used());
+    }
+
+    #[test]
+    fn test_rules_unlimited_with_penalties() {
+        let grid = Grid::build_from_fleet(&[ShipPlacement { ship_type: ShipType::PatrolBoat, origin: GridPoint { x: 4, y: 4 }, orientation: Orientation::Horizontal }]).unwrap();
+        let rules = GameRules { fleet_bonus: 5, miss_penalty: 1, repeat_shot: RepeatShot::Wasted, ..GameRules::unlimited() };
+        let mut game_session = Session::build_with_rules(String::from("Adetayo"), grid, rules);
+
+        for x in 1..=12 {
+            game_session.shoot_ship(GridPoint { x: (x - 1) % 10 + 1, y: 1 });
+        }
+        assert_eq!(12, game_session.get_shots_fired());
+        assert!(game_session.is_shot_available());
+
+        // 10 misses and 2 wasted repeats cost 12, the patrol boat and fleet bonus give 15
+        game_session.shoot_ship(GridPoint { x: 4, y: 4 });
+        assert!(game_session.is_won());
+        assert_eq!(3 * 10 / 13, game_session.get_points());
+    }
+
+    #[test]
+    fn test_rules_reach_score() {
+        let grid = Grid::build_from_fleet(&[ShipPlacement { ship_type: ShipType::PatrolBoat, origin: GridPoint { x: 4, y: 4 }, orientation: Orientation::Horizontal }]).unwrap();
+        let rules = GameRules { win_condition: WinCondition::ReachScore, target_score: 10, ..GameRules::classic() };
+        let mut game_session = Session::build_with_rules(String::from("Adetayo"), grid, rules);
+
+        game_session.shoot_ship(GridPoint { x: 1, y: 1 });
+        assert_eq!(ShotStatus::Repeat, game_session.shoot_ship(GridPoint { x: 1, y: 1 }).status);
+        assert_eq!(9, game_session.get_remaining_shots());
+        assert!(!game_session.is_over());
+        game_session.shoot_ship(GridPoint { x: 4, y: 4 });
+        assert!(game_session.is_won());
+        assert!(game_session.is_over());
+    }
+
+    #[test]
+    fn test_shots_off_board() {
+        let grid = Grid::build_from_fleet(&[ShipPlacement { ship_type: ShipType::PatrolBoat, origin: GridPoint { x: 1, y: 2 }, orientation: Orientation::Horizontal }]).unwrap();
+        let mut game_session = Session::build_with_rules(String::from("Adetayo"), grid, GameRules::classic());
+        assert_eq!(ShotStatus::OffBoard, game_session.shoot_ship(GridPoint { x: 11, y: 1 }).status);
+        assert_eq!(ShotStatus::OffBoard, game_session.shoot_ship(GridPoint { x: 1, y: 11 }).status);
+        assert_eq!((0, 0), (game_session.get_shots_fired(), game_session.get_points()));
+        assert!(game_session.is_any_ship_left());
+        assert_eq!(None, game_session.get_board_view().get_cell(GridPoint { x: 1, y: 11 }));
+    }
+
+    #[test]
+    fn test_shots_refused_once_over() {
+        let grid = Grid::build_from_fleet(&[ShipPlacement { ship_type: ShipType::PatrolBoat, origin: GridPoint { x: 4, y: 4 }, orientation: Orientation::Horizontal }]).unwrap();
+        let mut game_session = Session::build_with_rules(String::from("Adetayo"), grid.clone(), GameRules::classic());
+        for x in 1..=10 {
+            game_session.shoot_ship(GridPoint { x, y: 1 });
+        }
+        assert!(game_session.is_over());
+        assert_eq!(ShotStatus::Over, game_session.shoot_ship(GridPoint { x: 4, y: 4 }).status);
+        assert_eq!((0, 10, 0), (game_session.get_remaining_shots(), game_session.get_shots_fired(), game_session.get_points()));
+
+        let mut game = HeadToHead::start(String::from("Adetayo"), String::from("Tolu"), GameRules::classic());
+        game.sessions = [
+            Session::build_with_rules(String::from("Adetayo"), grid.clone(), GameRules::classic()),
+            Session::build_with_rules(String::from("Tolu"), grid, GameRules::classic()),
+        ];
+        assert_eq!(ShotStatus::Hit, game.shoot_ship(GridPoint { x: 4, y: 4 }).status);
+        assert!(game.is_over());
+        assert_eq!(ShotStatus::Over, game.shoot_ship(GridPoint { x: 4, y: 4 }).status);
+        assert_eq!((1, 0), (game.get_turn(), game.get_session(1).get_shots_fired()));
+    }
+
+    fn salvo_fleet() -> Grid {
+        Grid::build_from_fleet(&[
+            ShipPlacement { ship_type: ShipType::PatrolBoat, origin: GridPoint { x: 1, y: 1 }, orientation: Orientation::Horizontal },
+            ShipPlacement { ship_type: ShipType::Destroyer, origin: GridPoint { x: 5, y: 5 }, orientation: Orientation::Vertical },
+        ]).unwrap()
+    }
+
+    #[test]
+    fn test_fire_salvo() {
+        let mut game_session = Session::build_with_rules(String::from("Adetayo"), salvo_fleet(), GameRules::salvo());
+
+        assert_eq!(Err(SalvoError::WrongVolleySize { expected: 2, fired: 1 }), game_session.fire_salvo(vec![GridPoint { x: 1, y: 1 }]));
+        assert_eq!(Err(SalvoError::DuplicatePoint(GridPoint { x: 1, y: 1 })), game_session.fire_salvo(vec![GridPoint { x: 1, y: 1 }, GridPoint { x: 1, y: 1 }]));
+        assert_eq!(Err(SalvoError::OffGrid(GridPoint { x: 11, y: 1 })), game_session.fire_salvo(vec![GridPoint { x: 1, y: 1 }, GridPoint { x: 11, y: 1 }]));
+        assert_eq!(0, game_session.get_shots_fired());
+
+        let result = game_session.fire_salvo(vec![GridPoint { x: 1, y: 1 }, GridPoint { x: 9, y: 9 }]).unwrap();
+        assert_eq!(1, result.hits);
+        assert_eq!(10, result.points);
+        assert_eq!(ShotStatus::Miss, result.shots[1].status);
+
+        // only the destroyer is left, so the next volley is a single shot
+        let result = game_session.fire_salvo(vec![GridPoint { x: 5, y: 6 }]).unwrap();
+        assert_eq!(1, result.hits);
+        assert_eq!(Err(SalvoError::GameOver), game_session.fire_salvo(vec![]));
+    }
+
+    #[test]
+    fn test_fire_salvo_needs_salvo_rules() {
+        let mut game_session = Session::build_from_allocation(String::from("Adetayo"), salvo_fleet());
+        assert_eq!(Err(SalvoError::NotSalvoGame), game_session.fire_salvo(vec![GridPoint { x: 1, y: 1 }, GridPoint { x: 2, y: 2 }]));
+    }
+
+    #[test]
+    fn test_single_shots_refused_in_salvo_games() {
+        let mut game_session = Session::build_with_rules(String::from("Adetayo"), salvo_fleet(), GameRules::salvo());
+        assert_eq!(ShotStatus::Salvo, game_session.shoot_ship(GridPoint { x: 1, y: 1 }).status);
+        assert_eq!(0, game_session.get_shots_fired());
+        assert_eq!(2, game_session.get_remaining_ships().len());
+
+        let mut game = HeadToHead::start(String::from("Adetayo"), String::from("Tolu"), GameRules::salvo());
+        assert_eq!(ShotStatus::Salvo, game.shoot_ship(GridPoint { x: 1, y: 1 }).status);
+        assert_eq!(0, game.get_turn());
+    }
+
+    #[test]
+    fn test_head_to_head_salvo() {
+        let mut game = HeadToHead::start(String::from("Adetayo"), String::from("Tolu"), GameRules::salvo());
+        game.sessions = [
+            Session::build_with_rules(String::from("Adetayo"), salvo_fleet(), GameRules::salvo()),
+            Session::build_with_rules(String::from("Tolu"), salvo_fleet(), GameRules::salvo()),
+        ];
+
+        game.fire_salvo(vec![GridPoint { x: 1, y: 1 }, GridPoint { x: 2, y: 2 }]).unwrap();
+        assert_eq!(1, game.get_turn());
+
+        // the second player lost the patrol boat, so fires a single shot
+        assert_eq!(Err(SalvoError::WrongVolleySize { expected: 1, fired: 2 }), game.fire_salvo(vec![GridPoint { x: 3, y: 3 }, GridPoint { x: 4, y: 4 }]));
+        game.fire_salvo(vec![GridPoint { x: 3, y: 3 }]).unwrap();
+
+        game.fire_salvo(vec![GridPoint { x: 5, y: 5 }, GridPoint { x: 6, y: 6 }]).unwrap();
+        assert_eq!(Some(0), game.get_winner());
+        assert_eq!(Err(SalvoError::GameOver), game.fire_salvo(vec![GridPoint { x: 7, y: 7 }]));
+    }
+
+    #[test]
+    fn test_custom_fleet() {
+        let mut game_session = Session::start_with_fleet(String::from("Adetayo"), &[ShipType::Cruiser, ShipType::Flagship], 11, GameRules::unlimited()).unwrap();
+        assert_eq!(2, game_session.get_replay().fleet.len());
+
+        let squares: Vec<GridPoint> = (1..=10).flat_map(|y| (1..=10).map(move |x| GridPoint { x, y })).collect();
+        let covered = squares.iter().filter(|square| game_session.get_omniscient_view().get_cell(**square) == Some(CellState::Ship)).count();
+        assert_eq!(9, covered);
+
+        for square in squares {
+            game_session.shoot_ship(square);
+        }
+        assert!(game_session.is_won());
+        assert_eq!(6, game_session.get_destroyed_ships().iter().map(|ship| ship.get_point()).sum::<i32>());
+    }
+
+    #[test]
+    fn test_fleet_too_large_for_board() {
+        let pond = BoardTemplate { width: 4, height: 4, ..BoardTemplate::open_sea() };
+        let started = Session::start_on_board(String::from("Adetayo"), &pond, &[ShipType::AircraftCarrier], 4, GameRules::default());
+        assert_eq!(Some(PlacementError::ShipTooLarge(ShipType::AircraftCarrier)), started.err());
+        assert!(Session::start_on_board(String::from("Adetayo"), &pond, &[ShipType::Battleship], 4, GameRules::default()).is_ok());
+    }
+
+    #[test]
+    fn test_custom_ship_shapes() {
+        let barge = Ship::with_shape(ShipType::Destroyer, vec![(0, 0), (1, 0), (0, 1), (1, 1)], 3, "Barge").unwrap();
+        let mut game_session = Session::start_with_ships(String::from("Adetayo"), vec![barge, Ship::build(ShipType::PatrolBoat)], 4, GameRules::default()).unwrap();
+        let squares = game_session.get_remaining_ships().into_iter().find(|ship| ship.get_name() == "Barge").unwrap().get_squares();
+        assert_eq!(4, squares.len());
+        assert_eq!(ShotStatus::Hit, game_session.shoot_ship(squares[3]).status);
+        assert_eq!(vec![String::from("Barge")], game_session.get_destroyed_ships().iter().map(|ship| ship.get_name().to_string()).collect::<Vec<String>>());
+
+        let replay = Replay::from_json(&game_session.get_replay().to_json()).unwrap();
+        assert_eq!(1, replay.designs.len());
+        assert_eq!(game_session.get_points(), replay.verify_score(game_session.get_points()).unwrap().get_points());
+    }
+
+    #[test]
+    fn test_shots_at_terrain() {
+        let template = BoardTemplate::builtin("archipelago").unwrap();
+        let mut game_session = Session::start_on_board(String::from("Adetayo"), &template, &[ShipType::Submarine], 4, GameRules::default()).unwrap();
+
+        assert_eq!(ShotStatus::Blocked, game_session.shoot_ship(GridPoint { x: 3, y: 3 }).status);
+        assert_eq!(0, game_session.get_shots_fired());
+        assert_eq!(ShotStatus::Miss, game_session.shoot_ship(GridPoint { x: 4, y: 3 }).status);
+        assert_eq!(1, game_session.get_shots_fired());
+
+        let hint = game_session.suggest_shot().unwrap();
+        assert_eq!(CellKind::Water, template.get_cell_kind(hint.point));
+
+        let replayed = crate::replay::Replayer::new(game_session.get_replay().clone()).unwrap().final_session();
+        assert_eq!(game_session.get_omniscient_view(), replayed.get_omniscient_view());
+        assert_eq!(Some(CellState::Island), replayed.get_board_view().get_cell(GridPoint { x: 8, y: 2 }));
+    }
+
+    #[test]
+    fn test_miss_distance() {
+        let grid = Grid::build_from_fleet(&[ShipPlacement { ship_type: ShipType::Submarine, origin: GridPoint { x: 4, y: 4 }, orientation: Orientation::Vertical }]).unwrap();
+        let mut game_session = Session::build_with_rules(String::from("Adetayo"), grid.clone(), GameRules::warmer_colder());
+        assert_eq!(Some(6), game_session.shoot_ship(GridPoint { x: 1, y: 1 }).distance);
+        assert_eq!(None, game_session.shoot_ship(GridPoint { x: 4, y: 5 }).distance);
+        assert_eq!(Some(6), game_session.get_replay().get_shots()[0].distance);
+
+        let mut rules = GameRules::warmer_colder();
+        rules.miss_distance = Some(DistanceMetric::Chebyshev);
+        let mut game_session = Session::build_with_rules(String::from("Adetayo"), grid.clone(), rules);
+        assert_eq!(Some(2), game_session.shoot_ship(GridPoint { x: 2, y: 8 }).distance);
+
+        let mut game_session = Session::build_from_allocation(String::from("Adetayo"), grid);
+        assert_eq!(None, game_session.shoot_ship(GridPoint { x: 1, y: 1 }).distance);
+    }
+
+    #[test]
+    fn test_blitz_clock() {
+        let clock = ManualClock::new(1_000);
+        let mut game_session = Session::start_with_rules(String::from("Adetayo"), GameRules::blitz());
+        game_session.set_clock(Arc::new(clock.clone()));
+        assert_eq!(Some(5_000), game_session.get_shot_time_left());
+
+        clock.advance(4_000);
+        assert_eq!(0, game_session.tick());
+        game_session.shoot_ship(GridPoint { x: 1, y: 1 });
+        assert_eq!(Some(5_000), game_session.get_shot_time_left());
+
+        // two shots run out of time, both count as misses
+        clock.advance(12_000);
+        assert_eq!(2, game_session.tick());
+        assert_eq!(3, game_session.get_shots_fired());
+        assert_eq!(2, game_session.get_timeouts());
+        assert_eq!(Some(3_000), game_session.get_shot_time_left());
+        assert_eq!(None, game_session.undo_last_shot());
+
+        // forfeits stop once the whole game is out of time
+        clock.advance(60_000);
+        assert_eq!(7, game_session.tick());
+        assert!(game_session.is_over() && !game_session.is_time_up());
+
+        let mut game_session = Session::start_with_rules(String::from("Adetayo"), GameRules { shot_time_limit: None, ..GameRules::blitz() });
+        game_session.set_clock(Arc::new(clock.clone()));
+        clock.advance(61_000);
+        assert_eq!(ShotStatus::Over, game_session.shoot_ship(GridPoint { x: 1, y: 1 }).status);
+        assert!(game_session.is_time_up() && game_session.is_over());
+        assert_eq!(0, game_session.get_shots_fired());
+
+        let summary = game_session.get_summary();
+        assert_eq!(60_000, summary.time_used);
+        assert!(!summary.won);
+
+        // the timeouts are replayed from the recorded events
+        let replayer = crate::replay::Replayer::new(game_session.get_replay().clone()).unwrap();
+        assert!(replayer.final_session().is_time_up());
+    }
+
+    #[test]
+    fn test_special_weapons() {
+        let mut game_session = Session::build_with_rules(String::from("Adetayo"), salvo_fleet(), GameRules::tactical());
+
+        assert_eq!(SonarStatus::Contact, game_session.scan_sonar(GridPoint { x: 2, y: 2 }).unwrap().sonar.unwrap());
+        assert_eq!(Err(WeaponError::NoCharges(Weapon::Sonar)), game_session.scan_sonar(GridPoint { x: 8, y: 8 }));
+
+        // the torpedo runs down column 5 and stops at the destroyer
+        let outcome = game_session.fire_torpedo(GridPoint { x: 5, y: 1 }, Direction::Down).unwrap();
+        assert_eq!(5, outcome.squares.len());
+        assert_eq!(Some(ShipType::Destroyer), outcome.squares[4].1.ship_type);
+        assert_eq!(8, outcome.points);
+
+        assert_eq!(Err(WeaponError::OffGrid(GridPoint { x: 11, y: 10 })), game_session.drop_bomb(GridPoint { x: 10, y: 10 }));
+        let outcome = game_session.drop_bomb(GridPoint { x: 1, y: 1 }).unwrap();
+        assert_eq!(4, outcome.squares.len());
+        assert_eq!(18, game_session.get_points());
+        assert_eq!(3, game_session.get_shots_fired());
+        assert!(game_session.is_won());
+    }
+
     #[test]
     fn test_play() {
         let mut game = GamePlay::initialize();
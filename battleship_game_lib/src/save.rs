@@ -0,0 +1,340 @@
+use std::fmt;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::Sha256;
+use crate::replay::{Replay, ReplayError, Replayer};
+use crate::runtime::Play;
+
+
+/// Version written by `SaveFile::to_json`, bumped whenever the format changes
+///
+/// 1. the replay of the play only
+/// 2. the save slot id and when the game was created and last played
+/// 3. an HMAC of the save, see `SaveFile::sign`
+/// 4. the replay in replay format 2, see `REPLAY_FORMAT_VERSION`
+pub const SAVE_FORMAT_VERSION: u32 = 4;
+
+/// First version with a signature, older saves were never signed
+const SIGNED_SINCE: u32 = 3;
+
+/// Length in bytes of the keys made by `generate_key`
+pub const SAVE_KEY_LEN: usize = 32;
+
+/// Upgrades from one version to the next, `MIGRATIONS[n]` turns version `n + 1` into version `n + 2`
+const MIGRATIONS: [fn(Value) -> Result<Value, SaveError>; (SAVE_FORMAT_VERSION - 1) as usize] = [
+    migrate_v1_to_v2,
+    migrate_v2_to_v3,
+    migrate_v3_to_v4,
+];
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Random key to sign saves with, it stays on the device and is never written into a save
+pub fn generate_key() -> Vec<u8> {
+    (0..SAVE_KEY_LEN).map(|_| rand::random()).collect()
+}
+
+/// A saved play as written to disk. The play is kept as its replay and rebuilt when the save is loaded,
+/// so changes to how a game is held in memory do not break old saves.
+///
+/// ```
+/// use battleship_game_lib::runtime::{GridPoint, Play};
+/// use battleship_game_lib::save::SaveFile;
+///
+/// let mut play = Play::init(String::from("Adetayo"));
+/// play.get_session_as_mut().shoot_ship(GridPoint::new(3, 4));
+///
+/// let save = SaveFile::build(String::from("save-1"), 1_000, 2_000, &play);
+/// let loaded = SaveFile::from_json(&save.to_json()).unwrap().into_play().unwrap();
+/// assert_eq!(1, loaded.get_session_as_ref().get_shots_fired());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SaveFile {
+    pub format_version: u32,
+    /// Save slot the play was saved to, `None` for saves from before slots
+    pub id: Option<String>,
+    /// Milliseconds since the unix epoch when the game was first saved
+    pub created_at: u64,
+    /// Milliseconds since the unix epoch when the game was last saved
+    pub last_played: u64,
+    pub replay: Replay,
+    /// Hex encoded HMAC-SHA256 of the save without this field, `None` until it is signed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mac: Option<String>,
+}
+
+impl SaveFile {
+    pub fn build(id: String, created_at: u64, last_played: u64, play: &Play) -> SaveFile {
+        let replay = play.get_session_as_ref().get_replay().clone();
+        SaveFile { format_version: SAVE_FORMAT_VERSION, id: Some(id), created_at, last_played, replay, mac: None }
+    }
+
+    /// Sign the save with an HMAC of its JSON under `key`, so any change to the file can be detected
+    pub fn sign(&mut self, key: &[u8]) {
+        let unsigned = SaveFile { mac: None, ..self.clone() };
+        self.mac = Some(sign_json(&unsigned.to_json(), key));
+    }
+
+    /// Check the save was signed with `key` and has not been changed since
+    pub fn verify(&self, key: &[u8]) -> Result<(), SaveError> {
+        let mac = self.mac.as_ref().ok_or(SaveError::Unsigned)?;
+        let mac = hex::decode(mac).map_err(|_| SaveError::Tampered)?;
+        self.digest(key).verify_slice(&mac).map_err(|_| SaveError::Tampered)
+    }
+
+    fn digest(&self, key: &[u8]) -> HmacSha256 {
+        let unsigned = SaveFile { mac: None, ..self.clone() };
+        let mut digest = HmacSha256::new_from_slice(key).expect("HMAC takes keys of any length");
+        digest.update(unsigned.to_json().as_bytes());
+        digest
+    }
+
+    /// Export the save as pretty printed JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("save is always serialisable")
+    }
+
+    /// Read a save written by this or any older version, upgrading it to the current format
+    pub fn from_json(json: &str) -> Result<SaveFile, SaveError> {
+        let mut value: Value = serde_json::from_str(json).map_err(|err| SaveError::Parse(err.to_string()))?;
+        let version = value.get("format_version").and_then(Value::as_u64).ok_or(SaveError::MissingVersion)? as u32;
+        if version == 0 {
+            return Err(SaveError::MissingVersion);
+        }
+        if version > SAVE_FORMAT_VERSION {
+            return Err(SaveError::UnsupportedVersion(version));
+        }
+
+        for migrate in MIGRATIONS[(version - 1) as usize..].iter() {
+            value = migrate(value)?;
+        }
+        let replay = value.get_mut("replay").map(Value::take).ok_or_else(|| SaveError::Parse(String::from("save has no replay")))?;
+        let replay = Replay::from_value(replay).map_err(SaveError::Replay)?;
+        value["replay"] = serde_json::to_value(replay).expect("replay is always serialisable");
+        serde_json::from_value(value).map_err(|err| SaveError::Parse(err.to_string()))
+    }
+
+    /// Read a save written by this or any older version with `key`, checking the signature against the JSON
+    /// as it was written before upgrading it, then signing the upgraded save again.
+    /// Saves from before signatures can not be checked and are signed as they are.
+    pub fn load(json: &str, key: &[u8]) -> Result<SaveFile, SaveError> {
+        let value: Value = serde_json::from_str(json).map_err(|err| SaveError::Parse(err.to_string()))?;
+        let version = value.get("format_version").and_then(Value::as_u64).ok_or(SaveError::MissingVersion)? as u32;
+        if version >= SIGNED_SINCE {
+            let mac = value.get("mac").and_then(Value::as_str).ok_or(SaveError::Unsigned)?;
+            if !verify_written_json(json, mac, key) {
+                return Err(SaveError::Tampered);
+            }
+        }
+
+        let mut save = Self::from_json(json)?;
+        save.sign(key);
+        Ok(save)
+    }
+
+    /// Rebuild the play from its replay
+    pub fn into_play(self) -> Result<Play, SaveError> {
+        let session = Replayer::new(self.replay).map_err(SaveError::Replay)?.final_session();
+        let mut play = Play::init_with_session(session);
+        if let Some(id) = self.id {
+            play.set_save_id(id);
+        }
+        Ok(play)
+    }
+}
+
+/// Hex encoded HMAC-SHA256 of `json` under `key`
+pub(crate) fn sign_json(json: &str, key: &[u8]) -> String {
+    let mut digest = HmacSha256::new_from_slice(key).expect("HMAC takes keys of any length");
+    digest.update(json.as_bytes());
+    hex::encode(digest.finalize().into_bytes())
+}
+
+/// Check `mac` was made by `sign_json` with `key` from `json` as it was before the signature was written into it.
+/// The pretty printed JSON of a signed file has the signature as its last field, taking it out leaves the JSON that was signed.
+pub(crate) fn verify_written_json(json: &str, mac: &str, key: &[u8]) -> bool {
+    let Some(signed_part) = json.trim_end().strip_suffix(&format!(",\n  \"mac\": {}\n}}", Value::from(mac))) else {
+        return false;
+    };
+    let Ok(mac) = hex::decode(mac) else {
+        return false;
+    };
+    let mut digest = HmacSha256::new_from_slice(key).expect("HMAC takes keys of any length");
+    digest.update(signed_part.as_bytes());
+    digest.update(b"\n}");
+    digest.verify_slice(&mac).is_ok()
+}
+
+/// Version 1 had no save slots, the times are taken from the first and the last event of the replay
+fn migrate_v1_to_v2(mut value: Value) -> Result<Value, SaveError> {
+    let timestamps: Vec<u64> = value.pointer("/replay/events")
+        .and_then(Value::as_array)
+        .ok_or_else(|| SaveError::Parse(String::from("version 1 save has no replay events")))?
+        .iter()
+        .filter_map(event_timestamp)
+        .collect();
+
+    let object = value.as_object_mut().ok_or_else(|| SaveError::Parse(String::from("save is not an object")))?;
+    object.insert(String::from("format_version"), Value::from(2));
+    object.insert(String::from("id"), Value::Null);
+    object.insert(String::from("created_at"), Value::from(timestamps.first().copied().unwrap_or(0)));
+    object.insert(String::from("last_played"), Value::from(timestamps.last().copied().unwrap_or(0)));
+    Ok(value)
+}
+
+/// Version 3 added the signature, older saves are left unsigned
+fn migrate_v2_to_v3(mut value: Value) -> Result<Value, SaveError> {
+    let object = value.as_object_mut().ok_or_else(|| SaveError::Parse(String::from("save is not an object")))?;
+    object.insert(String::from("format_version"), Value::from(3));
+    Ok(value)
+}
+
+/// Version 4 upgraded the replay inside the save. The signature of a version 3 save covers its old replay,
+/// so it can not match the upgraded one and is dropped, `SaveFile::load` checks it before the upgrade.
+fn migrate_v3_to_v4(mut value: Value) -> Result<Value, SaveError> {
+    let object = value.as_object_mut().ok_or_else(|| SaveError::Parse(String::from("save is not an object")))?;
+    object.remove("mac");
+    object.insert(String::from("format_version"), Value::from(4));
+    Ok(value)
+}
+
+/// Timestamp of a replay event, every kind of event has one
+fn event_timestamp(event: &Value) -> Option<u64> {
+    event.get("timestamp")?.as_u64()
+}
+
+/// Reasons a save can not be loaded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SaveError {
+    /// The JSON could not be read
+    Parse(String),
+    /// The save does not say which version wrote it
+    MissingVersion,
+    /// The save was written by a newer version of the game
+    UnsupportedVersion(u32),
+    /// The play can not be rebuilt from the replay of the save
+    Replay(ReplayError),
+    /// The save has no signature
+    Unsigned,
+    /// The signature does not match, the save was changed or signed with another key
+    Tampered,
+    /// There is no save with this id
+    NoSuchSave(String),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Parse(err) => write!(f, "could not read save: {}", err),
+            SaveError::MissingVersion => write!(f, "save has no format version"),
+            SaveError::UnsupportedVersion(version) => write!(f, "save format version {} is newer than {}, update the game to load it", version, SAVE_FORMAT_VERSION),
+            SaveError::Replay(err) => write!(f, "could not rebuild saved game: {}", err),
+            SaveError::Unsigned => write!(f, "save is not signed"),
+            SaveError::Tampered => write!(f, "save was changed after it was written"),
+            SaveError::NoSuchSave(id) => write!(f, "there is no save {}", id),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::runtime::GridPoint;
+    use crate::view::CellState;
+
+    use super::*;
+
+    const V1: &str = include_str!("../fixtures/saves/v1.json");
+    const V2: &str = include_str!("../fixtures/saves/v2.json");
+    const V3: &str = include_str!("../fixtures/saves/v3.json");
+    const V4: &str = include_str!("../fixtures/saves/v4.json");
+    /// Key the version 3 and 4 fixtures were signed with
+    const FIXTURE_KEY: &[u8] = b"fixture key";
+
+    #[test]
+    fn load_version_1_fixture() {
+        let save = SaveFile::from_json(V1).unwrap();
+        assert_eq!((SAVE_FORMAT_VERSION, None), (save.format_version, save.id.clone()));
+        assert_eq!((1700000001000, 1700000003000), (save.created_at, save.last_played));
+        assert_eq!(Err(SaveError::Unsigned), save.verify(FIXTURE_KEY));
+
+        let play = save.into_play().unwrap();
+        let session = play.get_session_as_ref();
+        assert_eq!("Adetayo", session.get_player_name());
+        assert_eq!((3, 12, 1), (session.get_shots_fired(), session.get_points(), session.get_hints_used()));
+        assert_eq!(None, play.get_save_id());
+    }
+
+    #[test]
+    fn load_version_2_fixture() {
+        let save = SaveFile::from_json(V2).unwrap();
+        assert_eq!((SAVE_FORMAT_VERSION, None), (save.format_version, save.mac.clone()));
+
+        let play = save.into_play().unwrap();
+        assert_eq!(Some(String::from("save-3")), play.get_save_id());
+        let session = play.get_session_as_ref();
+        assert_eq!((2, 14), (session.get_shots_fired(), session.get_points()));
+        assert_eq!(Some(CellState::Hit), session.get_board_view().get_cell(GridPoint::new(8, 3)));
+    }
+
+    #[test]
+    fn load_version_3_fixture() {
+        let mut save = SaveFile::from_json(V3).unwrap();
+        // the signature covered the version 1 replay, which has been upgraded since
+        assert_eq!(Err(SaveError::Unsigned), save.verify(FIXTURE_KEY));
+        save.sign(FIXTURE_KEY);
+        assert_eq!(V4.trim_end(), save.to_json());
+    }
+
+    #[test]
+    fn load_signed_older_saves() {
+        // checked as it was written, then signed again in the current format
+        assert_eq!(V4.trim_end(), SaveFile::load(V3, FIXTURE_KEY).unwrap().to_json());
+        assert_eq!(Err(SaveError::Tampered), SaveFile::load(V3, b"another key"));
+        let edited = V3.replacen("\"last_played\": 1700000200000", "\"last_played\": 1700000200001", 1);
+        assert_eq!(Err(SaveError::Tampered), SaveFile::load(&edited, FIXTURE_KEY));
+
+        // saves from before signatures have nothing to check
+        assert_eq!(Ok(()), SaveFile::load(V2, FIXTURE_KEY).unwrap().verify(FIXTURE_KEY));
+        assert_eq!(Ok(()), SaveFile::load(V4, FIXTURE_KEY).unwrap().verify(FIXTURE_KEY));
+        let unsigned = SaveFile::from_json(V4).map(|save| SaveFile { mac: None, ..save }).unwrap().to_json();
+        assert_eq!(Err(SaveError::Unsigned), SaveFile::load(&unsigned, FIXTURE_KEY));
+    }
+
+    #[test]
+    fn load_version_4_fixture() {
+        let save = SaveFile::from_json(V4).unwrap();
+        // the current version is written back exactly as it was read
+        assert_eq!(V4.trim_end(), save.to_json());
+        assert_eq!(Ok(()), save.verify(FIXTURE_KEY));
+        assert_eq!(Err(SaveError::Tampered), save.verify(b"another key"));
+        assert_eq!(Some(String::from("save-4")), save.into_play().unwrap().get_save_id());
+    }
+
+    #[test]
+    fn detect_tampering() {
+        // the submarine is moved out of the way of the shot that sank it
+        let moved = V4.replacen("\"x\": 7,\n          \"y\": 8", "\"x\": 1,\n          \"y\": 8", 1);
+        assert_ne!(V4, moved);
+        assert_eq!(Err(SaveError::Tampered), SaveFile::from_json(&moved).unwrap().verify(FIXTURE_KEY));
+
+        let mut save = SaveFile::from_json(V4).unwrap();
+        save.created_at += 1;
+        assert_eq!(Err(SaveError::Tampered), save.verify(FIXTURE_KEY));
+        save.sign(FIXTURE_KEY);
+        assert_eq!(Ok(()), save.verify(FIXTURE_KEY));
+    }
+
+    #[test]
+    fn reject_unknown_versions() {
+        let newer = V4.replacen("\"format_version\": 4", "\"format_version\": 5", 1);
+        assert_eq!(Err(SaveError::UnsupportedVersion(5)), SaveFile::from_json(&newer));
+        let newer_replay = V4.replacen("\"format_version\": 2", "\"format_version\": 3", 1);
+        assert_eq!(Err(SaveError::Replay(ReplayError::UnsupportedVersion(3))), SaveFile::from_json(&newer_replay));
+        assert_eq!(Err(SaveError::MissingVersion), SaveFile::from_json(r#"{ "replay": {} }"#));
+        assert!(matches!(SaveFile::from_json("{"), Err(SaveError::Parse(_))));
+    }
+}
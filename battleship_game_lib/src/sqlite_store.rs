@@ -0,0 +1,404 @@
+use std::fmt;
+use std::path::Path;
+use rusqlite::{params, Connection, OptionalExtension};
+use crate::achievements::{Achievement, AchievementRecord};
+use crate::runtime::{self, Play, Session};
+use crate::save::{SaveError, SaveFile};
+use crate::stats::PlayerStats;
+use crate::storage::{SaveInfo, SaveStatus, Score};
+
+
+/// Schema changes in order, `MIGRATIONS[n]` brings the database from version `n` to `n + 1`.
+/// The version reached is kept in `PRAGMA user_version`.
+const MIGRATIONS: [&str; 3] = [
+    "CREATE TABLE players (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL UNIQUE,
+        created_at INTEGER NOT NULL
+    );
+    CREATE TABLE games (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        player_id INTEGER NOT NULL REFERENCES players (id),
+        created_at INTEGER NOT NULL,
+        last_played INTEGER NOT NULL,
+        status TEXT NOT NULL,
+        points INTEGER NOT NULL,
+        save TEXT NOT NULL
+    );
+    CREATE INDEX games_by_player ON games (player_id, last_played DESC);
+    CREATE TABLE shots (
+        game_id INTEGER NOT NULL REFERENCES games (id) ON DELETE CASCADE,
+        number INTEGER NOT NULL,
+        x INTEGER NOT NULL,
+        y INTEGER NOT NULL,
+        status TEXT NOT NULL,
+        ship_type TEXT,
+        points INTEGER NOT NULL,
+        timestamp INTEGER NOT NULL,
+        PRIMARY KEY (game_id, number)
+    );
+    CREATE TABLE scores (
+        id INTEGER PRIMARY KEY,
+        player_id INTEGER NOT NULL REFERENCES players (id),
+        game_id INTEGER UNIQUE REFERENCES games (id) ON DELETE SET NULL,
+        points INTEGER NOT NULL,
+        assisted INTEGER NOT NULL,
+        recorded_at INTEGER NOT NULL
+    );
+    CREATE INDEX scores_leaderboard ON scores (assisted, points DESC);",
+    "ALTER TABLE players ADD COLUMN stats TEXT;
+    ALTER TABLE players ADD COLUMN achievements TEXT;",
+    "CREATE INDEX scores_by_points ON scores (points DESC);",
+];
+
+/// Leaderboard without assisted games, read in order from `scores_leaderboard`
+const UNASSISTED_SCORES: &str = "SELECT players.name, scores.points, scores.assisted FROM scores JOIN players ON players.id = scores.player_id
+    WHERE scores.assisted = 0 ORDER BY scores.points DESC LIMIT ?1";
+/// Leaderboard of every game, read in order from `scores_by_points`
+const ALL_SCORES: &str = "SELECT players.name, scores.points, scores.assisted FROM scores JOIN players ON players.id = scores.player_id
+    ORDER BY scores.points DESC LIMIT ?1";
+
+/// Game storage in a SQLite database, for servers that keep games of many players across restarts.
+/// Saves are signed like the saves of `Store`, see `SaveFile::sign`.
+pub struct SqliteStore {
+    connection: Connection,
+    save_key: Vec<u8>,
+}
+
+impl SqliteStore {
+    /// Open or create the database at `path` and bring its schema up to date
+    pub fn open<P: AsRef<Path>>(path: P, save_key: Vec<u8>) -> Result<SqliteStore, StoreError> {
+        let connection = Connection::open(path)?;
+        connection.pragma_update(None, "foreign_keys", true)?;
+        let mut store = SqliteStore { connection, save_key };
+        store.migrate()?;
+        Ok(store)
+    }
+
+    /// Version of the schema, the number of migrations applied
+    pub fn get_schema_version(&self) -> Result<u32, StoreError> {
+        Ok(self.connection.pragma_query_value(None, "user_version", |row| row.get(0))?)
+    }
+
+    fn migrate(&mut self) -> Result<(), StoreError> {
+        let version = self.get_schema_version()? as usize;
+        if version > MIGRATIONS.len() {
+            return Err(StoreError::UnsupportedVersion(version as u32));
+        }
+        for (n, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            let transaction = self.connection.transaction()?;
+            transaction.execute_batch(migration)?;
+            transaction.pragma_update(None, "user_version", n as u32 + 1)?;
+            transaction.commit()?;
+        }
+        Ok(())
+    }
+
+    /// Save `play` to its save slot, or to a new one if it has never been saved, with its shots
+    /// and its score unless a shot was taken back
+    /// Return: id of the save slot
+    pub fn save_play(&mut self, play: &Play) -> Result<String, StoreError> {
+        self.write_play(play, true)
+    }
+
+    /// Save `play` part way through, like `save_play` but without putting its score on the table,
+    /// for games saved after every move
+    /// Return: id of the save slot
+    pub fn save_progress(&mut self, play: &Play) -> Result<String, StoreError> {
+        self.write_play(play, false)
+    }
+
+    fn write_play(&mut self, play: &Play, with_score: bool) -> Result<String, StoreError> {
+        let session = play.get_session_as_ref();
+        let now = runtime::now_millis();
+        let status = if session.is_over() { SaveStatus::Finished } else { SaveStatus::InProgress };
+        let transaction = self.connection.transaction()?;
+        let player_id = player_id(&transaction, &session.get_player_name(), now)?;
+
+        let existing: Option<(i64, u64)> = match play.get_save_id().as_deref().and_then(parse_save_id) {
+            Some(game_id) => transaction.query_row("SELECT id, created_at FROM games WHERE id = ?1", [game_id], |row| Ok((row.get(0)?, row.get(1)?))).optional()?,
+            None => None,
+        };
+        let (game_id, created_at) = match existing {
+            Some(game) => game,
+            None => {
+                transaction.execute(
+                    "INSERT INTO games (player_id, created_at, last_played, status, points, save) VALUES (?1, ?2, ?2, ?3, 0, '')",
+                    params![player_id, now, format!("{:?}", status)],
+                )?;
+                (transaction.last_insert_rowid(), now)
+            },
+        };
+
+        let id = format_save_id(game_id);
+        let mut save = SaveFile::build(id.clone(), created_at, now, play);
+        save.sign(&self.save_key);
+        transaction.execute(
+            "UPDATE games SET last_played = ?2, status = ?3, points = ?4, save = ?5 WHERE id = ?1",
+            params![game_id, now, format!("{:?}", status), session.get_points(), save.to_json()],
+        )?;
+
+        transaction.execute("DELETE FROM shots WHERE game_id = ?1", [game_id])?;
+        for (number, shot) in session.get_replay().get_shots().iter().enumerate() {
+            transaction.execute(
+                "INSERT INTO shots (game_id, number, x, y, status, ship_type, points, timestamp) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![game_id, number, shot.point.x, shot.point.y, format!("{:?}", shot.status), shot.ship_type.map(|ship_type| format!("{:?}", ship_type)), shot.points, shot.timestamp],
+            )?;
+        }
+
+        if with_score && !session.is_undo_used() {
+            transaction.execute(
+                "INSERT INTO scores (player_id, game_id, points, assisted, recorded_at) VALUES (?1, ?2, ?3, ?4, ?5)
+                 ON CONFLICT (game_id) DO UPDATE SET points = excluded.points, assisted = excluded.assisted, recorded_at = excluded.recorded_at",
+                params![player_id, game_id, session.get_points(), session.is_assisted(), now],
+            )?;
+        }
+        transaction.commit()?;
+        Ok(id)
+    }
+
+    /// Load a saved game, checking it has not been changed since it was saved
+    pub fn get_play(&self, id: &str) -> Result<Play, StoreError> {
+        let no_save = || StoreError::Save(SaveError::NoSuchSave(id.to_string()));
+        let game_id = parse_save_id(id).ok_or_else(no_save)?;
+        let json: String = self.connection.query_row("SELECT save FROM games WHERE id = ?1", [game_id], |row| row.get(0)).optional()?.ok_or_else(no_save)?;
+
+        Ok(SaveFile::load(&json, &self.save_key)?.into_play()?)
+    }
+
+    /// Delete a saved game and its shots, `false` if there is none with this id
+    pub fn delete_play(&mut self, id: &str) -> Result<bool, StoreError> {
+        let deleted = match parse_save_id(id) {
+            Some(game_id) => self.connection.execute("DELETE FROM games WHERE id = ?1", [game_id])?,
+            None => 0,
+        };
+        Ok(deleted > 0)
+    }
+
+    /// Saved games of the player, last played first
+    pub fn list_saves(&self, player_name: &str) -> Result<Vec<SaveInfo>, StoreError> {
+        let mut statement = self.connection.prepare(
+            "SELECT games.id, games.created_at, games.last_played, games.status, games.points FROM games
+             JOIN players ON players.id = games.player_id WHERE players.name = ?1 ORDER BY games.last_played DESC, games.id DESC",
+        )?;
+        let saves = statement.query_map([player_name], |row| {
+            let status: String = row.get(3)?;
+            Ok(SaveInfo {
+                id: format_save_id(row.get(0)?),
+                player_name: player_name.to_string(),
+                created_at: row.get(1)?,
+                last_played: row.get(2)?,
+                status: if status == "Finished" { SaveStatus::Finished } else { SaveStatus::InProgress },
+                points: row.get(4)?,
+            })
+        })?;
+        Ok(saves.collect::<Result<Vec<SaveInfo>, rusqlite::Error>>()?)
+    }
+
+    /// Shots stored for a saved game, shots taken back included
+    pub fn get_shot_count(&self, id: &str) -> Result<usize, StoreError> {
+        let game_id = parse_save_id(id).unwrap_or(-1);
+        let count: i64 = self.connection.query_row("SELECT COUNT(*) FROM shots WHERE game_id = ?1", [game_id], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+
+    /// Add a score that does not belong to a saved game, a verified submission for example
+    pub fn add_score(&mut self, score: &Score) -> Result<(), StoreError> {
+        let now = runtime::now_millis();
+        let transaction = self.connection.transaction()?;
+        let player_id = player_id(&transaction, &score.name, now)?;
+        transaction.execute(
+            "INSERT INTO scores (player_id, points, assisted, recorded_at) VALUES (?1, ?2, ?3, ?4)",
+            params![player_id, score.point, score.assisted, now],
+        )?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Best `limit` scores, best first. Assisted games are left out unless `include_assisted` is set
+    pub fn get_high_scores(&self, include_assisted: bool, limit: usize) -> Result<Vec<Score>, StoreError> {
+        let mut statement = self.connection.prepare(if include_assisted { ALL_SCORES } else { UNASSISTED_SCORES })?;
+        let scores = statement.query_map(params![limit as i64], |row| {
+            Ok(Score { name: row.get(0)?, point: row.get(1)?, assisted: row.get(2)? })
+        })?;
+        Ok(scores.collect::<Result<Vec<Score>, rusqlite::Error>>()?)
+    }
+
+    /// Statistics of the player, empty if the player has not finished a game yet
+    pub fn get_stats(&self, player_name: &str) -> Result<PlayerStats, StoreError> {
+        read_json(&self.connection, "SELECT stats FROM players WHERE name = ?1", player_name)
+    }
+
+    /// Achievements of the player, none unlocked if the player has not finished a game yet
+    pub fn get_achievements(&self, player_name: &str) -> Result<AchievementRecord, StoreError> {
+        read_json(&self.connection, "SELECT achievements FROM players WHERE name = ?1", player_name)
+    }
+
+    /// Count the finished `session` towards the statistics and achievements of its player
+    /// Return: achievements unlocked by the game
+    pub fn record_game(&mut self, session: &Session) -> Result<Vec<Achievement>, StoreError> {
+        let now = runtime::now_millis();
+        let player_name = session.get_player_name();
+        let transaction = self.connection.transaction()?;
+        let player_id = player_id(&transaction, &player_name, now)?;
+
+        let mut stats: PlayerStats = read_json(&transaction, "SELECT stats FROM players WHERE name = ?1", &player_name)?;
+        let mut achievements: AchievementRecord = read_json(&transaction, "SELECT achievements FROM players WHERE name = ?1", &player_name)?;
+        stats.record_game(session);
+        let unlocked = achievements.record_game(session, now);
+
+        transaction.execute(
+            "UPDATE players SET stats = ?2, achievements = ?3 WHERE id = ?1",
+            params![player_id, serde_json::to_string(&stats).expect("stats are always serialisable"), serde_json::to_string(&achievements).expect("achievements are always serialisable")],
+        )?;
+        transaction.commit()?;
+        Ok(unlocked)
+    }
+}
+
+/// Id of the player called `name`, adding the player if there is none yet
+fn player_id(connection: &Connection, name: &str, now: u64) -> Result<i64, StoreError> {
+    connection.execute("INSERT OR IGNORE INTO players (name, created_at) VALUES (?1, ?2)", params![name, now])?;
+    Ok(connection.query_row("SELECT id FROM players WHERE name = ?1", [name], |row| row.get(0))?)
+}
+
+/// JSON column of a player read into `T`, the default when the player or the column is missing
+fn read_json<T: serde::de::DeserializeOwned + Default>(connection: &Connection, query: &str, player_name: &str) -> Result<T, StoreError> {
+    let json: Option<String> = connection.query_row(query, [player_name], |row| row.get(0)).optional()?.flatten();
+    match json {
+        Some(json) => serde_json::from_str(&json).map_err(|err| StoreError::Corrupt(err.to_string())),
+        None => Ok(T::default()),
+    }
+}
+
+fn format_save_id(game_id: i64) -> String {
+    format!("save-{}", game_id)
+}
+
+fn parse_save_id(id: &str) -> Option<i64> {
+    id.strip_prefix("save-")?.parse().ok()
+}
+
+/// Reasons the database can not be used
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StoreError {
+    /// SQLite reported an error
+    Sqlite(String),
+    /// The database was written by a newer version of the game
+    UnsupportedVersion(u32),
+    /// A column holds something that can not be read
+    Corrupt(String),
+    /// A saved game can not be loaded
+    Save(SaveError),
+}
+
+impl From<rusqlite::Error> for StoreError {
+    fn from(err: rusqlite::Error) -> Self {
+        StoreError::Sqlite(err.to_string())
+    }
+}
+
+impl From<SaveError> for StoreError {
+    fn from(err: SaveError) -> Self {
+        StoreError::Save(err)
+    }
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StoreError::Sqlite(err) => write!(f, "database error: {}", err),
+            StoreError::UnsupportedVersion(version) => write!(f, "database schema version {} is newer than {}", version, MIGRATIONS.len()),
+            StoreError::Corrupt(err) => write!(f, "database holds data that can not be read: {}", err),
+            StoreError::Save(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::runtime::GridPoint;
+
+    use super::*;
+
+    fn open(file: &tempfile::NamedTempFile) -> SqliteStore {
+        SqliteStore::open(file.path(), b"test key".to_vec()).unwrap()
+    }
+
+    #[test]
+    fn migrations_run_once() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        assert_eq!(MIGRATIONS.len() as u32, open(&file).get_schema_version().unwrap());
+        // opening again finds the schema up to date
+        assert_eq!(MIGRATIONS.len() as u32, open(&file).get_schema_version().unwrap());
+
+        let connection = Connection::open(file.path()).unwrap();
+        connection.pragma_update(None, "user_version", 99).unwrap();
+        assert_eq!(Err(StoreError::UnsupportedVersion(99)), SqliteStore::open(file.path(), Vec::new()).map(|_| ()));
+    }
+
+    #[test]
+    fn saves_survive_reopening() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let mut play = Play::init(String::from("Adetayo"));
+        play.get_session_as_mut().shoot_ship(GridPoint { x: 2, y: 2 });
+        let id = open(&file).save_play(&play).unwrap();
+
+        let mut store = open(&file);
+        let mut play = store.get_play(&id).unwrap();
+        play.get_session_as_mut().shoot_ship(GridPoint { x: 3, y: 3 });
+        assert_eq!(id, store.save_play(&play).unwrap());
+        let other = store.save_progress(&Play::init(String::from("Adetayo"))).unwrap();
+        assert_eq!(1, store.get_high_scores(true, 10).unwrap().len());
+
+        let saves = store.list_saves("Adetayo").unwrap();
+        assert_eq!(2, saves.len());
+        assert_eq!(2, store.get_shot_count(&id).unwrap());
+        assert_eq!(2, store.get_play(&id).unwrap().get_session_as_ref().get_shots_fired());
+        assert!(store.list_saves("Tolu").unwrap().is_empty());
+
+        assert!(store.delete_play(&other).unwrap());
+        assert!(!store.delete_play(&other).unwrap());
+        assert_eq!(Err(StoreError::Save(SaveError::NoSuchSave(other.clone()))), store.get_play(&other).map(|_| ()));
+
+        // a save edited in the database is refused
+        let connection = Connection::open(file.path()).unwrap();
+        connection.execute("UPDATE games SET save = replace(save, '\"Adetayo\"', '\"Tolu\"')", []).unwrap();
+        assert_eq!(Err(StoreError::Save(SaveError::Tampered)), store.get_play(&id).map(|_| ()));
+    }
+
+    #[test]
+    fn leaderboard_and_profiles() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let mut store = open(&file);
+        store.add_score(&Score { name: String::from("Adetayo"), point: 10, assisted: false }).unwrap();
+        store.add_score(&Score { name: String::from("Tolu"), point: 30, assisted: true }).unwrap();
+        store.add_score(&Score { name: String::from("Kemi"), point: 20, assisted: false }).unwrap();
+
+        let names: Vec<String> = store.get_high_scores(false, 10).unwrap().into_iter().map(|score| score.name).collect();
+        assert_eq!(vec![String::from("Kemi"), String::from("Adetayo")], names);
+        assert_eq!(String::from("Tolu"), store.get_high_scores(true, 1).unwrap()[0].name);
+
+        // both leaderboards are read in order from an index instead of sorting every score
+        for (sql, index) in [(UNASSISTED_SCORES, "scores_leaderboard"), (ALL_SCORES, "scores_by_points")] {
+            let plan: Vec<String> = store.connection.prepare(&format!("EXPLAIN QUERY PLAN {}", sql)).unwrap()
+                .query_map(params![10], |row| row.get(3)).unwrap()
+                .collect::<Result<_, _>>().unwrap();
+            assert!(plan.iter().any(|step| step.contains(index)), "{:?}", plan);
+            assert!(!plan.iter().any(|step| step.contains("TEMP B-TREE")), "{:?}", plan);
+        }
+
+        let mut session = Session::start(String::from("Kemi"));
+        session.shoot_ship(GridPoint { x: 5, y: 5 });
+        store.record_game(&session).unwrap();
+        drop(store);
+
+        let store = open(&file);
+        assert_eq!(1, store.get_stats("Kemi").unwrap().get_games_played());
+        assert_eq!(1, store.get_achievements("Kemi").unwrap().get_games_played());
+        assert_eq!(0, store.get_stats("Tolu").unwrap().get_games_played());
+    }
+}
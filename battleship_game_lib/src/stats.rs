@@ -0,0 +1,211 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use serde::{Deserialize, Serialize};
+use crate::inventory::ship::ShipType;
+use crate::replay::{ReplayEvent, ShotRecord};
+use crate::runtime::{GridPoint, Session, ShotStatus};
+
+
+/// Shots it took to sink one ship type, summed over every game it was sunk in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SinkCount {
+    pub ship_type: ShipType,
+    /// Games the ship was sunk by a shot
+    pub sinks: u32,
+    /// Shots of those games up to and including the one that sank the ship
+    pub shots: u32,
+}
+
+/// Square a game was opened with and how often
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OpeningCount {
+    pub point: GridPoint,
+    pub games: u32,
+}
+
+/// Profile of a player built up from every game they finished
+///
+/// ```
+/// use battleship_game_lib::runtime::{GridPoint, Session};
+/// use battleship_game_lib::stats::PlayerStats;
+///
+/// let mut session = Session::start(String::from("Adetayo"));
+/// session.shoot_ship(GridPoint::new(5, 5));
+///
+/// let mut stats = PlayerStats::default();
+/// stats.record_game(&session);
+/// assert_eq!(1, stats.get_games_played());
+/// assert_eq!(Some(GridPoint::new(5, 5)), stats.get_favourite_opening());
+/// ```
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlayerStats {
+    games_played: u32,
+    wins: u32,
+    total_points: i64,
+    /// Shots fired at single squares, weapons and timeouts left out
+    shots: u32,
+    hits: u32,
+    longest_streak: u32,
+    sinks: Vec<SinkCount>,
+    openings: Vec<OpeningCount>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl PlayerStats {
+    pub fn get_games_played(&self) -> u32 {
+        self.games_played
+    }
+
+    pub fn get_wins(&self) -> u32 {
+        self.wins
+    }
+
+    /// Points per game, 0 before the first game
+    pub fn get_average_points(&self) -> f64 {
+        self.total_points as f64 / f64::from(self.games_played.max(1))
+    }
+
+    /// Share (0.0 - 1.0) of the shots that hit a ship, 0 before the first shot
+    pub fn get_accuracy(&self) -> f64 {
+        f64::from(self.hits) / f64::from(self.shots.max(1))
+    }
+
+    /// Most hits in a row within a game
+    pub fn get_longest_streak(&self) -> u32 {
+        self.longest_streak
+    }
+
+    /// Shots a game takes on average to sink `ship_type`, `None` if a shot never sank it
+    pub fn get_average_shots_to_sink(&self, ship_type: ShipType) -> Option<f64> {
+        self.sinks.iter()
+            .find(|count| count.ship_type == ship_type)
+            .map(|count| f64::from(count.shots) / f64::from(count.sinks))
+    }
+
+    /// Square the player opens with most often
+    pub fn get_favourite_opening(&self) -> Option<GridPoint> {
+        self.get_favourite_openings(1).first().map(|count| count.point)
+    }
+}
+
+impl PlayerStats {
+    /// Add a finished game to the profile. Shots taken back in practice mode are left out.
+    pub fn record_game(&mut self, session: &Session) {
+        self.games_played += 1;
+        if session.is_won() {
+            self.wins += 1;
+        }
+        self.total_points += i64::from(session.get_points());
+
+        // shots still standing after undos, with the number of shots used up when each was fired
+        let mut shots: Vec<(u32, ShotRecord)> = Vec::new();
+        let mut shots_used = 0;
+        for event in session.get_replay().events.iter() {
+            match event {
+                ReplayEvent::Shot(record) => {
+                    shots_used += 1;
+                    shots.push((shots_used, *record));
+                },
+                ReplayEvent::Undo { .. } => {
+                    shots_used -= 1;
+                    shots.pop();
+                },
+                ReplayEvent::Weapon(_) | ReplayEvent::Timeout { .. } => shots_used += 1,
+                ReplayEvent::Hint { .. } | ReplayEvent::Move { .. } | ReplayEvent::TimeUp { .. } => (),
+            }
+        }
+
+        let mut streak = 0;
+        for (shots_used, record) in shots.iter() {
+            self.shots += 1;
+            if record.status != ShotStatus::Hit {
+                streak = 0;
+                continue;
+            }
+            self.hits += 1;
+            streak += 1;
+            self.longest_streak = self.longest_streak.max(streak);
+            if let Some(ship_type) = record.ship_type {
+                self.add_sink(ship_type, *shots_used);
+            }
+        }
+
+        if let Some((_, record)) = shots.first() {
+            self.add_opening(record.point);
+        }
+    }
+
+    /// Squares the player opens with, most often first, at most `count` of them
+    pub fn get_favourite_openings(&self, count: usize) -> Vec<OpeningCount> {
+        let mut openings = self.openings.clone();
+        openings.sort_by_key(|opening| std::cmp::Reverse(opening.games));
+        openings.truncate(count);
+        openings
+    }
+
+    /// Shots to sink of every ship type sunk by a shot so far
+    pub fn get_sink_counts(&self) -> &[SinkCount] {
+        &self.sinks
+    }
+
+    fn add_sink(&mut self, ship_type: ShipType, shots: u32) {
+        match self.sinks.iter_mut().find(|count| count.ship_type == ship_type) {
+            Some(count) => {
+                count.sinks += 1;
+                count.shots += shots;
+            },
+            None => self.sinks.push(SinkCount { ship_type, sinks: 1, shots }),
+        }
+    }
+
+    fn add_opening(&mut self, point: GridPoint) {
+        match self.openings.iter_mut().find(|opening| opening.point == point) {
+            Some(opening) => opening.games += 1,
+            None => self.openings.push(OpeningCount { point, games: 1 }),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::inventory::grid::Grid;
+    use crate::inventory::ship::Orientation;
+    use crate::replay::ShipPlacement;
+    use crate::rules::GameRules;
+
+    use super::*;
+
+    #[test]
+    fn stats_over_games() {
+        let fleet = [
+            ShipPlacement { ship_type: ShipType::Destroyer, origin: GridPoint { x: 1, y: 1 }, orientation: Orientation::Horizontal },
+            ShipPlacement { ship_type: ShipType::PatrolBoat, origin: GridPoint { x: 1, y: 3 }, orientation: Orientation::Horizontal },
+        ];
+        let grid = Grid::build_from_fleet(&fleet).unwrap();
+
+        let mut stats = PlayerStats::default();
+        let mut session = Session::build_with_rules(String::from("Adetayo"), grid.clone(), GameRules::classic());
+        for point in [GridPoint { x: 9, y: 9 }, GridPoint { x: 1, y: 1 }, GridPoint { x: 1, y: 3 }] {
+            session.shoot_ship(point);
+        }
+        stats.record_game(&session);
+
+        let mut session = Session::build_with_rules(String::from("Adetayo"), grid, GameRules::classic());
+        session.enable_practice_mode();
+        for point in [GridPoint { x: 9, y: 9 }, GridPoint { x: 8, y: 8 }, GridPoint { x: 1, y: 3 }] {
+            session.shoot_ship(point);
+        }
+        session.undo_last_shot();
+        stats.record_game(&session);
+
+        assert_eq!((2, 1), (stats.get_games_played(), stats.get_wins()));
+        assert_eq!(2, stats.get_longest_streak());
+        assert_eq!(0.4, stats.get_accuracy());
+        assert_eq!(Some(2.0), stats.get_average_shots_to_sink(ShipType::Destroyer));
+        assert_eq!(None, stats.get_average_shots_to_sink(ShipType::Submarine));
+        assert_eq!(vec![OpeningCount { point: GridPoint { x: 9, y: 9 }, games: 2 }], stats.get_favourite_openings(3));
+    }
+}
@@ -1,41 +1,184 @@
 
 
 use std::collections::HashMap;
-use crate::runtime::Play;
+use serde::{Deserialize, Serialize};
+use crate::achievements::{Achievement, AchievementRecord};
+use crate::bundle::{Bundle, BundleError, ImportReport, MergeStrategy, PlayerRecord, BUNDLE_FORMAT_VERSION};
+use crate::campaign::{Campaign, CampaignProgress};
+use crate::replay::{Replay, ReplayError};
+use crate::runtime::{self, Play, Session};
+use crate::save::{self, SaveError, SaveFile};
+use crate::stats::PlayerStats;
 
 
 
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Score {
     pub name: String,
-    pub point: i32
+    pub point: i32,
+    /// Hints were used during the game
+    pub assisted: bool
 }
 
 
+/// Whether a saved game can still be played
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SaveStatus {
+    InProgress,
+    Finished,
+}
+
+/// What a player sees of a saved game in the list of saves
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SaveInfo {
+    pub id: String,
+    pub player_name: String,
+    /// Milliseconds since the unix epoch when the game was first saved
+    pub created_at: u64,
+    /// Milliseconds since the unix epoch when the game was last saved
+    pub last_played: u64,
+    pub status: SaveStatus,
+    pub points: i32,
+}
+
+/// A saved game is kept as its signed save file, exactly as it would be written to disk
+#[derive(Debug, Clone)]
+struct SavedGame {
+    info: SaveInfo,
+    json: String,
+}
+
+#[derive(Clone)]
 pub struct Store {
-    plays: HashMap<String, Play>,
+    /// Saved games keyed by save id
+    plays: HashMap<String, SavedGame>,
+    /// Number of the next save id
+    next_save: u64,
     scores: Vec<Score>,
+    /// Campaign progress keyed by player name and campaign name
+    campaigns: HashMap<(String, String), CampaignProgress>,
+    /// Achievements keyed by player name
+    achievements: HashMap<String, AchievementRecord>,
+    /// Statistics keyed by player name
+    stats: HashMap<String, PlayerStats>,
+    /// Key the saves are signed with
+    save_key: Vec<u8>,
+}
+
+/// Empty store, see `Store::build`
+impl Default for Store {
+    fn default() -> Self {
+        Store::build()
+    }
 }
 
 impl Store{
+    /// Empty store signing its saves with a new random key
     pub fn build() -> Store {
-        Store { plays: HashMap::new(), scores: Vec::new() }
+        Self::build_with_key(save::generate_key())
+    }
+
+    /// Empty store signing its saves with `save_key`, the key kept on the device from an earlier run
+    pub fn build_with_key(save_key: Vec<u8>) -> Store {
+        Store { plays: HashMap::new(), next_save: 1, scores: Vec::new(), campaigns: HashMap::new(), achievements: HashMap::new(), stats: HashMap::new(), save_key }
+    }
+
+    /// Key the saves are signed with, to be kept on the device and never shared
+    pub fn get_save_key(&self) -> &[u8] {
+        &self.save_key
+    }
+
+    pub fn build_with(plays: Vec<Play>, scores: Vec<Score>) -> Store{
+        let mut store = Store { scores, ..Store::build() };
+        for play in plays {
+            store.insert_play(play);
+        }
+        store
+    }
+
+    /// Save `play` to its save slot, or to a new one if it has never been saved
+    /// Return: id of the save slot
+    pub fn save_play(&mut self, play: Play) -> String {
+        let session = play.get_session_as_ref();
+        if !session.is_undo_used() {
+            self.add_score(Score { name: session.get_player_name(), point: session.get_points(), assisted: session.is_assisted() });
+        }
+        self.insert_play(play)
     }
 
-    pub fn build_with(plays: HashMap<String, Play>, scores: Vec<Score>) -> Store{
-        Store { plays, scores }
+    /// Save `play` part way through, like `save_play` but without putting its score on the table,
+    /// for games saved after every move that would otherwise score again on each save
+    /// Return: id of the save slot
+    pub fn save_progress(&mut self, play: Play) -> String {
+        self.insert_play(play)
     }
 
-    pub fn save_play(&mut self, play: Play){
-        self.add_score(Score { name: play.get_session_as_ref().get_player_name(), point: play.get_session_as_ref().get_points() });
-        self.plays.insert(play.get_session_as_ref().get_player_name(), play);
+    /// Remove a saved game and hand it back, a save that fails its check stays where it is
+    pub fn pop_play(&mut self, id: &str) -> Result<Play, SaveError> {
+        let play = self.get_play(id)?;
+        self.plays.remove(id);
+        Ok(play)
     }
 
-    pub fn pop_play(&mut self, player_name: &String) -> Option<Play> {
-        self.plays.remove(player_name)
+    /// Keep new saves from taking the id of a save kept elsewhere that could not be loaded
+    pub fn reserve_save_id(&mut self, id: &str) {
+        if let Some(n) = id.strip_prefix("save-").and_then(|n| n.parse::<u64>().ok()) {
+            self.next_save = self.next_save.max(n + 1);
+        }
     }
 
-    pub fn get_play(&self, player_name: &String) -> Option<Play>{
-        self.plays.get(player_name).cloned()
+    /// Delete a saved game without loading it, `false` if there is none with this id
+    pub fn delete_play(&mut self, id: &str) -> bool {
+        self.plays.remove(id).is_some()
+    }
+
+    /// Load a saved game, checking it has not been changed since it was saved
+    pub fn get_play(&self, id: &str) -> Result<Play, SaveError> {
+        let saved = self.plays.get(id).ok_or_else(|| SaveError::NoSuchSave(id.to_string()))?;
+        SaveFile::load(&saved.json, &self.save_key)?.into_play()
+    }
+
+    pub fn get_save(&self, id: &str) -> Option<SaveInfo> {
+        self.plays.get(id).map(|saved| saved.info.clone())
+    }
+
+    /// Signed, versioned JSON of a saved game for writing to disk, see `SaveFile`
+    pub fn export_play(&self, id: &str) -> Option<String> {
+        self.plays.get(id).map(|saved| saved.json.clone())
+    }
+
+    /// Load a save written by `export_play` of a store with the same key, keeping its times.
+    /// A save whose slot is taken gets a new one.
+    /// Return: id of the save slot
+    pub fn import_play(&mut self, json: &str) -> Result<String, SaveError> {
+        let save = SaveFile::load(json, &self.save_key)?;
+        let (created_at, last_played) = (save.created_at, save.last_played);
+        let id = match &save.id {
+            Some(id) if !self.plays.contains_key(id) => id.clone(),
+            _ => self.new_save_id(),
+        };
+
+        let play = save.into_play()?;
+        self.write_play(id.clone(), created_at, last_played, &play);
+        Ok(id)
+    }
+
+    /// Put the score of a game played elsewhere on the high score table, once replaying the
+    /// fleet shuffled from its seed and its events scores the `claimed` points
+    pub fn submit_score(&mut self, replay: &Replay, claimed: i32) -> Result<Score, ReplayError> {
+        let score = Self::verify_score(replay, claimed)?;
+        self.add_score(score.clone());
+        Ok(score)
+    }
+
+    /// Saved games of the player, last played first
+    pub fn list_saves(&self, player_name: &str) -> Vec<SaveInfo> {
+        let mut saves: Vec<SaveInfo> = self.plays.values()
+            .filter(|saved| saved.info.player_name == player_name)
+            .map(|saved| saved.info.clone())
+            .collect();
+        saves.sort_by(|a, b| b.last_played.cmp(&a.last_played).then_with(|| b.id.cmp(&a.id)));
+        saves
     }
 
     pub fn add_score(&mut self, score: Score){
@@ -46,8 +189,266 @@ impl Store{
         self.scores.retain(|x| x.point != score.point && x.name != score.name);
     }
 
+    /// High score table, best first. Assisted games are left out unless `include_assisted` is set
+    pub fn get_high_scores(&self, include_assisted: bool) -> Vec<Score> {
+        let mut scores: Vec<Score> = self.scores.iter().filter(|score| include_assisted || !score.assisted).cloned().collect();
+        scores.sort_by_key(|score| std::cmp::Reverse(score.point));
+        scores
+    }
+
+    /// Players with at least one saved game
     pub fn get_player_names(&self) -> Vec<String> {
-        self.plays.keys().cloned().collect()
+        let mut names: Vec<String> = self.plays.values().map(|saved| saved.info.player_name.clone()).collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Progress of the player through `campaign`, nothing beaten if the player has not played it
+    pub fn get_campaign_progress(&self, player_name: &str, campaign: &Campaign) -> CampaignProgress {
+        self.campaigns.get(&(player_name.to_string(), campaign.name.clone()))
+            .cloned()
+            .unwrap_or_else(|| CampaignProgress::build(campaign))
+    }
+
+    pub fn save_campaign_progress(&mut self, player_name: &str, progress: CampaignProgress) {
+        self.campaigns.insert((player_name.to_string(), progress.campaign.clone()), progress);
+    }
+
+    /// Achievements of the player, none unlocked if the player has not finished a game yet
+    pub fn get_achievements(&self, player_name: &str) -> AchievementRecord {
+        self.achievements.get(player_name).cloned().unwrap_or_default()
+    }
+
+    /// Statistics of the player, empty if the player has not finished a game yet
+    pub fn get_stats(&self, player_name: &str) -> PlayerStats {
+        self.stats.get(player_name).cloned().unwrap_or_default()
+    }
+
+    /// Count the finished `session` towards the statistics and achievements of its player
+    /// Return: achievements unlocked by the game
+    pub fn record_game(&mut self, session: &Session) -> Vec<Achievement> {
+        self.stats.entry(session.get_player_name()).or_default().record_game(session);
+        self.achievements.entry(session.get_player_name()).or_default().record_game(session, runtime::now_millis())
+    }
+
+    /// Every saved game, score and player record of the store, see `Bundle`
+    pub fn export_bundle(&self) -> Bundle {
+        let mut saves: Vec<SaveFile> = self.plays.values()
+            .map(|saved| SaveFile { mac: None, ..SaveFile::from_json(&saved.json).expect("saves are written by this store") })
+            .collect();
+        saves.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
+
+        let mut names: Vec<String> = self.stats.keys().chain(self.achievements.keys()).chain(self.campaigns.keys().map(|(name, _)| name)).cloned().collect();
+        names.sort();
+        names.dedup();
+        let players = names.iter().map(|name| self.get_player_record(name)).collect();
+
+        Bundle { format_version: BUNDLE_FORMAT_VERSION, exported_at: runtime::now_millis(), saves, scores: self.scores.clone(), players, mac: None }
+    }
+
+    /// High scores and player records without the saves, signed with the key of the store for keeping next to its saves
+    pub fn export_records(&self) -> String {
+        let mut records = Bundle { saves: Vec::new(), ..self.export_bundle() };
+        records.sign(&self.save_key);
+        records.to_json()
+    }
+
+    /// Merge records written by `export_records` of a store with the same key, replacing player records that differ.
+    /// Their high scores were put on the table by this store, so they are taken as they are.
+    pub fn import_records(&mut self, json: &str) -> Result<ImportReport, BundleError> {
+        let records = Bundle::load_signed(json, &self.save_key)?;
+        self.merge_bundle(records, MergeStrategy::Overwrite, false, true)
+    }
+
+    /// Merge a bundle into the store, settling conflicts with `strategy`.
+    /// A bundle with a save that can not be rebuilt is refused as a whole.
+    /// With `dry_run` the store is left as it is and the report says what the import would change.
+    pub fn import_bundle(&mut self, bundle: Bundle, strategy: MergeStrategy, dry_run: bool) -> Result<ImportReport, BundleError> {
+        self.merge_bundle(bundle, strategy, dry_run, false)
+    }
+
+    /// Merge a bundle into the store, taking its scores as they are if `trusted` rather than checking them against replays
+    fn merge_bundle(&mut self, bundle: Bundle, strategy: MergeStrategy, dry_run: bool, trusted: bool) -> Result<ImportReport, BundleError> {
+        // the import works on a copy, so an error half way leaves the store untouched
+        let mut store = self.clone();
+        let mut report = ImportReport { dry_run, ..ImportReport::default() };
+
+        // players first, so the saves and scores of a renamed player follow it
+        let mut renamed: HashMap<String, String> = HashMap::new();
+        for record in bundle.players {
+            let name = record.name.clone();
+            if !store.has_player_record(&name) {
+                store.put_player_record(record);
+                report.players_added.push(name);
+            } else if store.get_player_record(&name) == record {
+                continue;
+            } else {
+                match strategy {
+                    MergeStrategy::KeepExisting => report.players_skipped.push(name),
+                    MergeStrategy::Overwrite => {
+                        store.put_player_record(record);
+                        report.players_replaced.push(name);
+                    },
+                    MergeStrategy::KeepBoth => {
+                        let new_name = store.free_player_name(&name);
+                        store.put_player_record(PlayerRecord { name: new_name.clone(), ..record });
+                        renamed.insert(name.clone(), new_name.clone());
+                        report.players_renamed.push((name, new_name));
+                    },
+                }
+            }
+        }
+
+        // replays the scores of the bundle are checked against, each backs at most one score
+        let mut replays: Vec<Replay> = store.plays.values()
+            .map(|saved| SaveFile::from_json(&saved.json).expect("saves are written by this store").replay)
+            .collect();
+        for (n, mut save) in bundle.saves.into_iter().enumerate() {
+            if let Some(new_name) = renamed.get(&save.replay.player_name) {
+                save.replay.player_name = new_name.clone();
+            }
+            replays.push(save.replay.clone());
+            let (created_at, last_played) = (save.created_at, save.last_played);
+            let existing = save.id.as_ref().and_then(|id| store.plays.get(id)).map(|saved| SaveFile::from_json(&saved.json).expect("saves are written by this store"));
+            let play = save.clone().into_play().map_err(|error| BundleError::Save { save: n, error })?;
+
+            let id = match (save.id, existing) {
+                (Some(id), Some(existing)) => {
+                    if (existing.created_at, existing.last_played, &existing.replay) == (created_at, last_played, &save.replay) {
+                        report.saves_unchanged.push(id);
+                        continue;
+                    }
+                    match strategy {
+                        MergeStrategy::KeepExisting => {
+                            report.saves_skipped.push(id);
+                            continue;
+                        },
+                        MergeStrategy::Overwrite => {
+                            report.saves_replaced.push(id.clone());
+                            id
+                        },
+                        MergeStrategy::KeepBoth => {
+                            let new_id = store.new_save_id();
+                            report.saves_renumbered.push((id, new_id.clone()));
+                            new_id
+                        },
+                    }
+                },
+                (Some(id), None) => {
+                    report.saves_added.push(id.clone());
+                    id
+                },
+                (None, _) => {
+                    let id = store.new_save_id();
+                    report.saves_added.push(id.clone());
+                    id
+                },
+            };
+            store.write_play(id, created_at, last_played, &play);
+        }
+
+        // a score already in the store is taken to be the same one, each can only match once.
+        // Any other score of a bundle from elsewhere only goes on the table if the replay of a save scores it.
+        let mut unmatched = store.scores.clone();
+        for mut score in bundle.scores {
+            if let Some(new_name) = renamed.get(&score.name) {
+                score.name = new_name.clone();
+            }
+            match unmatched.iter().position(|existing| *existing == score) {
+                Some(n) => {
+                    unmatched.swap_remove(n);
+                },
+                None if trusted => {
+                    store.scores.push(score);
+                    report.scores_added += 1;
+                },
+                None => {
+                    let backed = replays.iter().position(|replay| Self::verify_score(replay, score.point).as_ref() == Ok(&score));
+                    match backed {
+                        Some(n) => {
+                            replays.swap_remove(n);
+                            store.scores.push(score);
+                            report.scores_added += 1;
+                        },
+                        None => report.scores_unverified += 1,
+                    }
+                },
+            }
+        }
+
+        if !dry_run {
+            *self = store;
+        }
+        Ok(report)
+    }
+
+    /// Score `replay` earns if it replays to the `claimed` points without taking shots back
+    fn verify_score(replay: &Replay, claimed: i32) -> Result<Score, ReplayError> {
+        let session = replay.verify_score(claimed)?;
+        if session.is_undo_used() {
+            return Err(ReplayError::UndoUsed);
+        }
+        Ok(Score { name: session.get_player_name(), point: claimed, assisted: session.is_assisted() })
+    }
+
+    fn has_player_record(&self, name: &str) -> bool {
+        self.stats.contains_key(name) || self.achievements.contains_key(name) || self.campaigns.keys().any(|(player, _)| player == name)
+    }
+
+    fn get_player_record(&self, name: &str) -> PlayerRecord {
+        let mut campaigns: Vec<CampaignProgress> = self.campaigns.iter()
+            .filter(|((player, _), _)| player == name)
+            .map(|(_, progress)| progress.clone())
+            .collect();
+        campaigns.sort_by(|a, b| a.campaign.cmp(&b.campaign));
+        PlayerRecord { name: name.to_string(), stats: self.get_stats(name), achievements: self.get_achievements(name), campaigns }
+    }
+
+    fn put_player_record(&mut self, record: PlayerRecord) {
+        self.campaigns.retain(|(player, _), _| *player != record.name);
+        for progress in record.campaigns {
+            self.save_campaign_progress(&record.name, progress);
+        }
+        self.stats.insert(record.name.clone(), record.stats);
+        self.achievements.insert(record.name, record.achievements);
+    }
+
+    /// `name` with the lowest number after it that no player, save or score uses yet
+    fn free_player_name(&self, name: &str) -> String {
+        let taken = |candidate: &str| self.has_player_record(candidate)
+            || self.plays.values().any(|saved| saved.info.player_name == candidate)
+            || self.scores.iter().any(|score| score.name == candidate);
+        (2..).map(|n| format!("{} ({})", name, n)).find(|candidate| !taken(candidate)).expect("there is always a free name")
+    }
+
+    fn new_save_id(&mut self) -> String {
+        loop {
+            let id = format!("save-{}", self.next_save);
+            self.next_save += 1;
+            // imported saves keep their ids, which may be ahead of the count
+            if !self.plays.contains_key(&id) {
+                return id;
+            }
+        }
+    }
+
+    fn insert_play(&mut self, play: Play) -> String {
+        let now = runtime::now_millis();
+        let id = play.get_save_id().unwrap_or_else(|| self.new_save_id());
+        let created_at = self.plays.get(&id).map_or(now, |saved| saved.info.created_at);
+        self.write_play(id.clone(), created_at, now, &play);
+        id
+    }
+
+    fn write_play(&mut self, id: String, created_at: u64, last_played: u64, play: &Play) {
+        let mut save = SaveFile::build(id.clone(), created_at, last_played, play);
+        save.sign(&self.save_key);
+
+        let session = play.get_session_as_ref();
+        let status = if session.is_over() { SaveStatus::Finished } else { SaveStatus::InProgress };
+        let info = SaveInfo { id: id.clone(), player_name: session.get_player_name(), created_at, last_played, status, points: session.get_points() };
+        self.plays.insert(id, SavedGame { info, json: save.to_json() });
     }
 
 }
@@ -55,7 +456,7 @@ impl Store{
 #[cfg(test)]
 mod tests {
 
-    use crate::runtime::GridPoint;
+    use crate::runtime::{GridPoint, ShotStatus};
 
     use super::*;
 
@@ -79,9 +480,9 @@ mod tests {
         println!("{:?}", game_session.get_destroyed_ships());
         
         println!("---------------------------------------");
-        store.save_play(play);
+        let id = store.save_play(play);
 
-        play = store.pop_play(&String::from("Adetayo")).unwrap();
+        play = store.pop_play(&id).unwrap();
         game_session = play.get_session_as_mut();
 
         println!("---------------------------------------");
@@ -91,4 +492,216 @@ mod tests {
 
 
     }
+
+    #[test]
+    fn test_undo_kept_off_high_scores() {
+        let mut store = Store::build();
+        let mut play = Play::init(String::from("Adetayo"));
+        let session = play.get_session_as_mut();
+        session.enable_practice_mode();
+        session.shoot_ship(GridPoint { x: 2 , y:  2});
+        session.undo_last_shot();
+
+        let id = store.save_play(play);
+        assert!(store.get_high_scores(true).is_empty());
+        assert!(store.get_play(&id).is_ok());
+    }
+
+    #[test]
+    fn test_save_slots() {
+        let mut store = Store::build();
+        let first = store.save_play(Play::init(String::from("Adetayo")));
+        let mut play = Play::init(String::from("Adetayo"));
+        play.get_session_as_mut().shoot_ship(GridPoint { x: 2 , y:  2});
+        let second = store.save_play(play);
+        assert_ne!(first, second);
+
+        // a saved play goes back to its own slot
+        let mut play = store.get_play(&second).unwrap();
+        for n in 1..=10 {
+            play.get_session_as_mut().shoot_ship(GridPoint { x: n , y:  10});
+        }
+        assert_eq!(second, store.save_play(play));
+
+        let saves = store.list_saves("Adetayo");
+        assert_eq!(2, saves.len());
+        let save = store.get_save(&second).unwrap();
+        assert_eq!(SaveStatus::Finished, save.status);
+        assert!(save.created_at <= save.last_played);
+        assert_eq!(SaveStatus::InProgress, store.get_save(&first).unwrap().status);
+        assert!(store.list_saves("Tolu").is_empty());
+    }
+
+    #[test]
+    fn test_export_and_import() {
+        let mut store = Store::build();
+        let mut play = Play::init(String::from("Adetayo"));
+        play.get_session_as_mut().shoot_ship(GridPoint { x: 2 , y:  2});
+        let id = store.save_play(play);
+        let json = store.export_play(&id).unwrap();
+
+        // the slot is taken, so the same save comes back in a new one
+        let copy = store.import_play(&json).unwrap();
+        assert_ne!(id, copy);
+        let (original, imported) = (store.get_save(&id).unwrap(), store.get_save(&copy).unwrap());
+        assert_eq!((original.created_at, original.points), (imported.created_at, imported.points));
+        assert_eq!(1, store.get_play(&copy).unwrap().get_session_as_ref().get_shots_fired());
+
+        let mut fresh = Store::build_with_key(store.get_save_key().to_vec());
+        assert_eq!(id, fresh.import_play(&json).unwrap());
+        assert!(matches!(fresh.import_play("{}"), Err(SaveError::MissingVersion)));
+
+        // saves only load with the key they were signed with, and only as they were written
+        assert_eq!(Err(SaveError::Tampered), Store::build().import_play(&json));
+        let edited = json.replacen("\"player_name\": \"Adetayo\"", "\"player_name\": \"Tolu\"", 1);
+        assert_ne!(json, edited);
+        assert_eq!(Err(SaveError::Tampered), Store::build_with_key(store.get_save_key().to_vec()).import_play(&edited));
+        assert_eq!(Err(SaveError::NoSuchSave(String::from("save-9"))), store.get_play("save-9").map(|_| ()));
+
+        let mut other = Store::build();
+        other.reserve_save_id("save-4");
+        assert_eq!("save-5", other.save_play(Play::init(String::from("Tolu"))));
+    }
+
+    #[test]
+    fn test_import_older_signed_save() {
+        let v3 = include_str!("../fixtures/saves/v3.json");
+        let mut store = Store::build_with_key(b"fixture key".to_vec());
+        let id = store.import_play(v3).unwrap();
+        assert_eq!("save-4", id);
+        assert_eq!("Tolu", store.get_play(&id).unwrap().get_session_as_ref().get_player_name());
+        assert_eq!(Err(SaveError::Tampered), Store::build().import_play(v3));
+    }
+
+    #[test]
+    fn test_import_bundle_strategies() {
+        let name = String::from("Adetayo");
+        let mut home = Store::build();
+        let mut play = Play::init(name.clone());
+        play.get_session_as_mut().shoot_ship(GridPoint { x: 2 , y:  2});
+        let shared = home.save_play(play);
+        home.record_game(&Session::start(name.clone()));
+        let bundle = home.export_bundle();
+
+        // the other machine played on from the same save and finished another game
+        let mut away = Store::build();
+        away.import_bundle(bundle.clone(), MergeStrategy::KeepExisting, false).unwrap();
+        let mut play = away.get_play(&shared).unwrap();
+        play.get_session_as_mut().shoot_ship(GridPoint { x: 3 , y:  3});
+        away.save_play(play);
+        away.record_game(&Session::start(name.clone()));
+        let changed = away.export_bundle();
+
+        // importing a bundle into the store it came from changes nothing
+        let report = home.import_bundle(bundle.clone(), MergeStrategy::Overwrite, false).unwrap();
+        assert!(report.is_empty());
+        assert_eq!(vec![shared.clone()], report.saves_unchanged);
+
+        let report = home.import_bundle(changed.clone(), MergeStrategy::KeepExisting, true).unwrap();
+        assert_eq!((vec![shared.clone()], vec![name.clone()], 1), (report.saves_skipped.clone(), report.players_skipped.clone(), report.scores_added));
+        assert!(report.dry_run && report.to_string().starts_with("Would skip save"));
+        assert_eq!(1, home.get_play(&shared).unwrap().get_session_as_ref().get_shots_fired());
+
+        let mut overwritten = home.clone();
+        let report = overwritten.import_bundle(changed.clone(), MergeStrategy::Overwrite, false).unwrap();
+        assert_eq!((vec![shared.clone()], vec![name.clone()]), (report.saves_replaced, report.players_replaced));
+        assert_eq!(2, overwritten.get_play(&shared).unwrap().get_session_as_ref().get_shots_fired());
+        assert_eq!(2, overwritten.get_stats(&name).get_games_played());
+        assert_eq!(2, overwritten.get_high_scores(true).len());
+
+        let report = home.import_bundle(changed, MergeStrategy::KeepBoth, false).unwrap();
+        let renamed = String::from("Adetayo (2)");
+        assert_eq!(vec![(name.clone(), renamed.clone())], report.players_renamed);
+        let (_, copy) = report.saves_renumbered[0].clone();
+        assert_eq!(renamed, home.get_play(&copy).unwrap().get_session_as_ref().get_player_name());
+        assert_eq!((1, 2), (home.get_stats(&name).get_games_played(), home.get_stats(&renamed).get_games_played()));
+        assert_eq!(vec![name.clone(), renamed], home.get_player_names());
+
+        // a bundle with a save that can not be rebuilt is refused as a whole
+        let mut broken = bundle;
+        let ship = broken.saves[0].replay.fleet[0];
+        broken.saves[0].replay.fleet.push(ship);
+        broken.saves[0].id = Some(String::from("save-9"));
+        let mut fresh = Store::build();
+        assert!(matches!(fresh.import_bundle(broken, MergeStrategy::Overwrite, false), Err(BundleError::Save { save: 0, .. })));
+        assert!(fresh.list_saves(&name).is_empty() && fresh.get_high_scores(true).is_empty());
+    }
+
+    #[test]
+    fn test_bundle_scores_are_replayed() {
+        let mut home = Store::build();
+        let mut play = Play::init(String::from("Adetayo"));
+        let squares: Vec<GridPoint> = (1..=10).flat_map(|y| (1..=10).map(move |x| GridPoint { x, y })).collect();
+        let session = play.get_session_as_mut();
+        for square in squares {
+            if session.shoot_ship(square).status == ShotStatus::Hit {
+                break;
+            }
+        }
+        let points = play.get_session_as_ref().get_points();
+        home.save_play(play);
+
+        let mut bundle = home.export_bundle();
+        bundle.scores.push(Score { name: String::from("Adetayo"), point: 9000, assisted: false });
+        bundle.scores.push(Score { name: String::from("Tolu"), point: points, assisted: false });
+        let mut away = Store::build();
+        let report = away.import_bundle(bundle.clone(), MergeStrategy::KeepExisting, false).unwrap();
+        assert_eq!((1, 2), (report.scores_added, report.scores_unverified));
+        assert_eq!(vec![Score { name: String::from("Adetayo"), point: points, assisted: false }], away.get_high_scores(true));
+
+        // without the save nothing backs the score
+        bundle.saves.clear();
+        let report = Store::build().import_bundle(bundle, MergeStrategy::KeepExisting, false).unwrap();
+        assert_eq!((0, 3), (report.scores_added, report.scores_unverified));
+        assert!(report.to_string().contains("Left out 3 high scores"));
+    }
+
+    #[test]
+    fn test_own_records_are_trusted() {
+        let mut home = Store::build();
+        home.add_score(Score { name: String::from("Adetayo"), point: 40, assisted: false });
+        let records = home.export_records();
+
+        // no save backs the score, the signature of the store does
+        let mut fresh = Store::build_with_key(home.get_save_key().to_vec());
+        assert_eq!(1, fresh.import_records(&records).unwrap().scores_added);
+        assert_eq!(home.get_high_scores(true), fresh.get_high_scores(true));
+
+        let edited = records.replacen("\"point\": 40", "\"point\": 9000", 1);
+        assert_ne!(records, edited);
+        assert_eq!(Err(BundleError::Tampered), Store::build_with_key(home.get_save_key().to_vec()).import_records(&edited));
+        assert_eq!(Err(BundleError::Tampered), Store::build().import_records(&records));
+        assert_eq!(Err(BundleError::Unsigned), fresh.import_records(&home.export_bundle().to_json()));
+    }
+
+    #[test]
+    fn test_submitted_scores_are_replayed() {
+        let mut session = Session::start_with_seed(String::from("Adetayo"), 7, Default::default());
+        for point in [GridPoint { x: 10 , y: 10 }, GridPoint { x: 5 , y: 1 }, GridPoint { x: 7 , y: 1 }] {
+            session.shoot_ship(point);
+        }
+        let replay = session.get_replay().clone();
+        let mut store = Store::build();
+
+        assert_eq!(Err(ReplayError::ScoreMismatch { claimed: 50, replayed: 12 }), store.submit_score(&replay, 50).map(|_| ()));
+        let mut moved = replay.clone();
+        moved.fleet[0].origin = GridPoint { x: 10 , y: 10 };
+        assert_eq!(Err(ReplayError::FleetMismatch), store.submit_score(&moved, 14).map(|_| ()));
+        assert!(store.get_high_scores(true).is_empty());
+
+        assert_eq!(12, store.submit_score(&replay, 12).unwrap().point);
+        assert_eq!(1, store.get_high_scores(false).len());
+    }
+
+    #[test]
+    fn test_high_scores_without_assisted() {
+        let mut store = Store::build();
+        store.add_score(Score { name: String::from("Adetayo"), point: 10, assisted: false });
+        store.add_score(Score { name: String::from("Tolu"), point: 30, assisted: true });
+        store.add_score(Score { name: String::from("Kemi"), point: 20, assisted: false });
+
+        let names: Vec<String> = store.get_high_scores(false).into_iter().map(|score| score.name).collect();
+        assert_eq!(vec![String::from("Kemi"), String::from("Adetayo")], names);
+        assert_eq!(3, store.get_high_scores(true).len());
+    }
 }
\ No newline at end of file
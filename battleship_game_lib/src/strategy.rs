@@ -0,0 +1,148 @@
+use crate::runtime::GridPoint;
+
+
+/// Placement counting over a grid
+///
+/// For every ship still afloat, every placement in each of its rotations that avoids
+/// the squares already fired at is counted. Squares covered by many placements are
+/// the most likely to hold a ship. Only the shapes of the surviving ships and the shot
+/// history are used, never the real ship locations, so the same map can drive a
+/// computer opponent without letting it cheat.
+///
+/// ```
+/// use battleship_game_lib::{runtime::GridPoint, strategy::DensityMap};
+///
+/// let density = DensityMap::build(10, 10, &[5, 4], &[GridPoint::new(1, 1)]);
+/// let (point, confidence) = density.best_point().unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct DensityMap {
+    width: i32,
+    height: i32,
+    counts: Vec<u32>,
+    probabilities: Vec<f64>,
+}
+
+impl DensityMap {
+    /// Count every legal placement of straight ships of `ship_sizes` on a `width` by `height` grid,
+    /// skipping placements that touch a square in `fired`
+    pub fn build(width: i32, height: i32, ship_sizes: &[i32], fired: &[GridPoint]) -> DensityMap {
+        let shapes: Vec<Vec<Vec<(i32, i32)>>> = ship_sizes.iter().map(|&size| {
+            let horizontal: Vec<(i32, i32)> = (0..size).map(|n| (n, 0)).collect();
+            let vertical: Vec<(i32, i32)> = (0..size).map(|n| (0, n)).collect();
+            // a single square has only one placement
+            if size == 1 { vec![horizontal] } else { vec![horizontal, vertical] }
+        }).collect();
+        Self::build_with_shapes(width, height, &shapes, fired)
+    }
+
+    /// Count every legal placement of ships of any shape, one entry in `ship_shapes` per ship
+    /// holding the offsets of each of its distinct rotations (see `Ship::get_rotations`)
+    pub fn build_with_shapes(width: i32, height: i32, ship_shapes: &[Vec<Vec<(i32, i32)>>], fired: &[GridPoint]) -> DensityMap {
+        let cells = (width * height) as usize;
+        let mut counts = vec![0; cells];
+        let mut miss_chance = vec![1.0; cells];
+
+        for rotations in ship_shapes {
+            let mut ship_counts = vec![0u32; cells];
+            let mut total = 0u32;
+
+            for y in 1..=height {
+                for x in 1..=width {
+                    for offsets in rotations {
+                        let squares: Vec<GridPoint> = offsets.iter().map(|(dx, dy)| GridPoint { x: x + dx, y: y + dy }).collect();
+                        let fits = squares.iter().all(|p| p.x <= width && p.y <= height && !fired.contains(p));
+                        if !fits {
+                            continue;
+                        }
+                        total += 1;
+                        for p in squares {
+                            ship_counts[Self::index(width, p)] += 1;
+                        }
+                    }
+                }
+            }
+
+            if total == 0 {
+                continue;
+            }
+            for (n, count) in ship_counts.iter().enumerate() {
+                counts[n] += count;
+                miss_chance[n] *= 1.0 - f64::from(*count) / f64::from(total);
+            }
+        }
+
+        let probabilities = miss_chance.iter().map(|chance| 1.0 - chance).collect();
+        DensityMap { width, height, counts, probabilities }
+    }
+
+    /// Number of placements covering the square
+    pub fn get_count(&self, point: GridPoint) -> u32 {
+        self.counts[Self::index(self.width, point)]
+    }
+
+    /// Estimated probability (0.0 - 1.0) that the square holds a ship
+    pub fn get_probability(&self, point: GridPoint) -> f64 {
+        self.probabilities[Self::index(self.width, point)]
+    }
+
+    /// Square with the most placements and its probability, `None` when nothing fits anywhere.
+    /// Ties go to the square closest to the top left corner.
+    pub fn best_point(&self) -> Option<(GridPoint, f64)> {
+        let mut best: Option<(GridPoint, u32)> = None;
+        for y in 1..=self.height {
+            for x in 1..=self.width {
+                let point = GridPoint { x, y };
+                let count = self.get_count(point);
+                if count > 0 && best.is_none_or(|(_, top)| count > top) {
+                    best = Some((point, count));
+                }
+            }
+        }
+
+        best.map(|(point, _)| (point, self.get_probability(point)))
+    }
+
+    fn index(width: i32, point: GridPoint) -> usize {
+        ((point.y - 1) * width + (point.x - 1)) as usize
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn centre_is_densest_on_empty_grid() {
+        let density = DensityMap::build(10, 10, &[5, 4, 3, 2, 1], &[]);
+        assert!(density.get_count(GridPoint { x: 5, y: 5 }) > density.get_count(GridPoint { x: 1, y: 1 }));
+    }
+
+    #[test]
+    fn fired_squares_are_never_suggested() {
+        let fired: Vec<GridPoint> = (1..=10).flat_map(|y| (1..=9).map(move |x| GridPoint { x, y })).collect();
+        let density = DensityMap::build(10, 10, &[1], &fired);
+        let (point, confidence) = density.best_point().unwrap();
+        assert_eq!(10, point.x);
+        assert!((confidence - 0.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn shapes_match_straight_sizes() {
+        let straight = DensityMap::build(10, 10, &[3], &[]);
+        let shaped = DensityMap::build_with_shapes(10, 10, &[vec![vec![(0, 0), (1, 0), (2, 0)], vec![(0, 0), (0, 1), (0, 2)]]], &[]);
+        assert_eq!(straight.get_count(GridPoint { x: 4, y: 7 }), shaped.get_count(GridPoint { x: 4, y: 7 }));
+
+        // a plus can never cover a corner
+        let plus = DensityMap::build_with_shapes(10, 10, &[vec![vec![(1, 0), (0, 1), (1, 1), (2, 1), (1, 2)]]], &[]);
+        assert_eq!(0, plus.get_count(GridPoint { x: 1, y: 1 }));
+        assert_eq!(5, plus.get_count(GridPoint { x: 5, y: 5 }));
+    }
+
+    #[test]
+    fn no_placement_left() {
+        let density = DensityMap::build(10, 10, &[], &[]);
+        assert!(density.best_point().is_none());
+    }
+}
@@ -0,0 +1,191 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use crate::inventory::grid::{Grid, PlacementError};
+use crate::inventory::ship::ShipType;
+use crate::runtime::GridPoint;
+
+
+/// Smallest number of rows or columns a board may have
+pub const MIN_BOARD_SIZE: i32 = 4;
+/// Largest number of rows or columns a board may have
+pub const MAX_BOARD_SIZE: i32 = 26;
+/// Largest share of the squares, in percent, a template may block so a fleet still has room
+pub const MAX_BLOCKED_PERCENT: usize = 30;
+
+/////////////////////////////////////////////////////////////////////////
+/// What a square of the board is made of
+/////////////////////////////////////////////////////////////////////////
+///
+///Water, Island, Reef
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CellKind {
+    /// Open water, ships can be placed here
+    #[default]
+    Water,
+    /// No ship can be placed here and shots at it are refused
+    Island,
+    /// No ship can be placed here and shots at it are wasted as misses
+    Reef,
+}
+
+/// Board size and layout listing the blocked squares, every other square is water
+///
+/// ```
+/// use battleship_game_lib::runtime::GridPoint;
+/// use battleship_game_lib::terrain::{BoardTemplate, CellKind};
+///
+/// let template = BoardTemplate::from_json(r#"{ "name": "rock", "islands": [{ "x": 5, "y": 5 }] }"#).unwrap();
+/// assert_eq!(CellKind::Island, template.get_cell_kind(GridPoint::new(5, 5)));
+/// assert!(BoardTemplate::builtin("archipelago").is_some());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BoardTemplate {
+    pub name: String,
+    /// Number of columns
+    #[serde(default = "default_board_size")]
+    pub width: i32,
+    /// Number of rows
+    #[serde(default = "default_board_size")]
+    pub height: i32,
+    #[serde(default)]
+    pub islands: Vec<GridPoint>,
+    #[serde(default)]
+    pub reefs: Vec<GridPoint>,
+}
+
+impl BoardTemplate {
+    /// Classic 10 by 10 board without any terrain
+    pub fn open_sea() -> BoardTemplate {
+        BoardTemplate { name: String::from("open_sea"), width: default_board_size(), height: default_board_size(), islands: Vec::new(), reefs: Vec::new() }
+    }
+
+    /// Names of the templates shipped with the library
+    pub fn builtin_names() -> Vec<&'static str> {
+        vec!["open_sea", "archipelago", "reef_belt", "strait"]
+    }
+
+    /// Template shipped with the library, `None` if there is none called `name`
+    pub fn builtin(name: &str) -> Option<BoardTemplate> {
+        let json = match name {
+            "open_sea" => return Some(BoardTemplate::open_sea()),
+            "archipelago" => include_str!("../templates/archipelago.json"),
+            "reef_belt" => include_str!("../templates/reef_belt.json"),
+            "strait" => include_str!("../templates/strait.json"),
+            _ => return None,
+        };
+        Some(BoardTemplate::from_json(json).expect("built-in templates are valid"))
+    }
+
+    /// Read a template from JSON, checking the size and that every blocked square is on the board and listed once
+    pub fn from_json(json: &str) -> Result<BoardTemplate, TemplateError> {
+        let template: BoardTemplate = serde_json::from_str(json).map_err(|err| TemplateError::Parse(err.to_string()))?;
+        template.validate()?;
+        Ok(template)
+    }
+
+    /// Export the template as pretty printed JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("template is always serialisable")
+    }
+
+    /// Kind of the square at `point`
+    pub fn get_cell_kind(&self, point: GridPoint) -> CellKind {
+        if self.islands.contains(&point) {
+            CellKind::Island
+        } else if self.reefs.contains(&point) {
+            CellKind::Reef
+        } else {
+            CellKind::Water
+        }
+    }
+
+    /// Check every ship of `fleet` fits on the board and the fleet is no larger than its open water
+    pub fn check_fleet(&self, fleet: &[ShipType]) -> Result<(), PlacementError> {
+        let mut grid = Grid::build_from_template(self);
+        grid.set_fleet(fleet);
+        grid.check_fleet_fits()
+    }
+
+    /// Check the size and the blocked squares, templates read with `from_json` are always valid
+    pub fn validate(&self) -> Result<(), TemplateError> {
+        let sizes = MIN_BOARD_SIZE..=MAX_BOARD_SIZE;
+        if !sizes.contains(&self.width) || !sizes.contains(&self.height) {
+            return Err(TemplateError::BadSize { width: self.width, height: self.height });
+        }
+        let blocked: Vec<GridPoint> = self.islands.iter().chain(self.reefs.iter()).copied().collect();
+        for (n, point) in blocked.iter().enumerate() {
+            if !(1..=self.width).contains(&point.x) || !(1..=self.height).contains(&point.y) {
+                return Err(TemplateError::OffGrid(*point));
+            }
+            if blocked[..n].contains(point) {
+                return Err(TemplateError::Duplicate(*point));
+            }
+        }
+        if blocked.len() * 100 > (self.width * self.height) as usize * MAX_BLOCKED_PERCENT {
+            return Err(TemplateError::TooManyBlocked(blocked.len()));
+        }
+        Ok(())
+    }
+}
+
+fn default_board_size() -> i32 {
+    10
+}
+
+/// Reasons a board template can not be used
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateError {
+    /// The JSON could not be read
+    Parse(String),
+    /// The board is smaller than `MIN_BOARD_SIZE` or larger than `MAX_BOARD_SIZE`
+    BadSize { width: i32, height: i32 },
+    /// A blocked square is not on the grid
+    OffGrid(GridPoint),
+    /// A square is listed more than once
+    Duplicate(GridPoint),
+    /// More than `MAX_BLOCKED_PERCENT` of the squares are blocked
+    TooManyBlocked(usize),
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TemplateError::Parse(err) => write!(f, "could not read board template: {}", err),
+            TemplateError::BadSize { width, height } => write!(f, "a {}x{} board is not between {} and {} squares a side", width, height, MIN_BOARD_SIZE, MAX_BOARD_SIZE),
+            TemplateError::OffGrid(point) => write!(f, "{} is not on the grid", point),
+            TemplateError::Duplicate(point) => write!(f, "{} is listed more than once", point),
+            TemplateError::TooManyBlocked(count) => write!(f, "{} squares are blocked, at most {}% of the board may be", count, MAX_BLOCKED_PERCENT),
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_templates_load() {
+        for name in BoardTemplate::builtin_names() {
+            let template = BoardTemplate::builtin(name).unwrap();
+            assert_eq!(name, template.name);
+            assert_eq!(Ok(template.clone()), BoardTemplate::from_json(&template.to_json()));
+        }
+        assert!(BoardTemplate::builtin("atlantis").is_none());
+    }
+
+    #[test]
+    fn reject_invalid_templates() {
+        assert_eq!(Err(TemplateError::OffGrid(GridPoint { x: 11, y: 1 })), BoardTemplate::from_json(r#"{ "name": "edge", "islands": [{ "x": 11, "y": 1 }] }"#));
+        assert_eq!(Err(TemplateError::Duplicate(GridPoint { x: 2, y: 2 })), BoardTemplate::from_json(r#"{ "name": "twice", "islands": [{ "x": 2, "y": 2 }], "reefs": [{ "x": 2, "y": 2 }] }"#));
+        assert!(matches!(BoardTemplate::from_json("[]"), Err(TemplateError::Parse(_))));
+        assert_eq!(Err(TemplateError::BadSize { width: 30, height: 10 }), BoardTemplate::from_json(r#"{ "name": "wide", "width": 30 }"#));
+        assert_eq!(Err(TemplateError::OffGrid(GridPoint { x: 6, y: 6 })), BoardTemplate::from_json(r#"{ "name": "small", "width": 5, "height": 5, "reefs": [{ "x": 6, "y": 6 }] }"#));
+    }
+}
@@ -0,0 +1,120 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use serde::{Deserialize, Serialize};
+use crate::runtime::GridPoint;
+
+
+/////////////////////////////////////////////////////////////////////////
+/// State of a single square as a front-end should draw it
+/////////////////////////////////////////////////////////////////////////
+///
+///Unknown, Miss, Hit, Sunk, Ship, Island, Reef
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CellState {
+    /// Not fired at and nothing known about it
+    Unknown,
+    /// Fired at, no ship
+    Miss,
+    /// Fired at, on a ship that has been sunk
+    Hit,
+    /// Part of a sunk ship that was not fired at directly
+    Sunk,
+    /// Part of a ship still afloat, only shown in debug mode or in the omniscient view
+    Ship,
+    /// Island, shots are refused
+    Island,
+    /// Reef not fired at, shots are wasted
+    Reef,
+}
+
+/// Board layout for renderers, one `CellState` per square in row-major order
+///
+/// ```
+/// use battleship_game_lib::runtime::{GridPoint, Session};
+/// use battleship_game_lib::view::CellState;
+///
+/// let mut session = Session::start(String::from("Adetayo"));
+/// session.shoot_ship(GridPoint::new(1, 1));
+/// let view = session.get_board_view();
+/// assert_ne!(Some(CellState::Unknown), view.get_cell(GridPoint::new(1, 1)));
+/// assert_eq!(None, view.get_cell(GridPoint::new(11, 1)));
+/// ```
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BoardView {
+    width: i32,
+    height: i32,
+    cells: Vec<CellState>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl BoardView {
+    /// Number of columns
+    pub fn get_width(&self) -> i32 {
+        self.width
+    }
+
+    /// Number of rows
+    pub fn get_height(&self) -> i32 {
+        self.height
+    }
+
+    /// State of the square at `point`, `None` for a point outside the board
+    pub fn get_cell(&self, point: GridPoint) -> Option<CellState> {
+        self.index(point).map(|index| self.cells[index])
+    }
+
+    /// All squares in row-major order, starting at (1,1)
+    pub fn get_cells(&self) -> Vec<CellState> {
+        self.cells.clone()
+    }
+}
+
+impl BoardView {
+    /// Blank view where every square is `CellState::Unknown`
+    pub fn build(width: i32, height: i32) -> BoardView {
+        BoardView { width, height, cells: vec![CellState::Unknown; (width * height) as usize] }
+    }
+
+    /// Set the state of the square at `point`, a point outside the board is ignored
+    pub fn set_cell(&mut self, point: GridPoint, state: CellState) {
+        if let Some(index) = self.index(point) {
+            self.cells[index] = state;
+        }
+    }
+
+    /// Squares as a slice in row-major order
+    pub fn as_slice(&self) -> &[CellState] {
+        &self.cells
+    }
+
+    /// Squares as rows, `rows()[y - 1][x - 1]` is the square at (x, y)
+    pub fn rows(&self) -> Vec<Vec<CellState>> {
+        self.cells.chunks(self.width as usize).map(|row| row.to_vec()).collect()
+    }
+
+    fn index(&self, point: GridPoint) -> Option<usize> {
+        if !(1..=self.width).contains(&point.x) || !(1..=self.height).contains(&point.y) {
+            return None;
+        }
+        Some(((point.y - 1) * self.width + (point.x - 1)) as usize)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_follow_row_major_order() {
+        let mut view = BoardView::build(10, 10);
+        view.set_cell(GridPoint { x: 3, y: 2 }, CellState::Miss);
+
+        assert_eq!(CellState::Miss, view.rows()[1][2]);
+        assert_eq!(CellState::Miss, view.as_slice()[12]);
+        assert_eq!(10, view.rows().len());
+    }
+}
@@ -0,0 +1,173 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use crate::runtime::{GridPoint, Shot};
+
+
+/////////////////////////////////////////////////////////////////////////
+/// Special weapons
+/////////////////////////////////////////////////////////////////////////
+///
+///Sonar, Torpedo, Bomb
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Weapon {
+    /// Reports whether any ship is within a 3x3 area, without revealing where
+    Sonar,
+    /// Runs along a row or column and hits the first ship in its way
+    Torpedo,
+    /// Covers a 2x2 area
+    Bomb,
+}
+
+/////////////////////////////////////////////////////////////////////////
+/// Direction a torpedo runs in
+/////////////////////////////////////////////////////////////////////////
+///
+///Right, Down, Left, Up
+///
+///Replays recorded before torpedo runs had their own directions name them after ship orientations, those names are still read
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Direction {
+    /// Along the row, towards the last column
+    #[serde(alias = "Horizontal")]
+    Right,
+    /// Down the column, towards the last row
+    #[serde(alias = "Vertical")]
+    Down,
+    /// Along the row, towards column A
+    #[serde(alias = "HorizontalReversed")]
+    Left,
+    /// Up the column, towards row 1
+    #[serde(alias = "VerticalReversed")]
+    Up,
+}
+
+impl Direction {
+    /// The square next to `point` in this direction
+    pub fn step(&self, point: GridPoint) -> GridPoint {
+        match self {
+            Direction::Right => GridPoint { x: point.x + 1, y: point.y },
+            Direction::Down => GridPoint { x: point.x, y: point.y + 1 },
+            Direction::Left => GridPoint { x: point.x - 1, y: point.y },
+            Direction::Up => GridPoint { x: point.x, y: point.y - 1 },
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////
+/// Result of a sonar scan
+/////////////////////////////////////////////////////////////////////////
+///
+///Contact, Clear
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SonarStatus {
+    /// At least one ship is in the scanned area
+    Contact,
+    /// No ship is in the scanned area
+    Clear,
+}
+
+/// Charges left for each special weapon, every use costs one charge and one shot
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct WeaponInventory {
+    pub sonar: i32,
+    pub torpedo: i32,
+    pub bomb: i32,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl WeaponInventory {
+    pub fn new(sonar: i32, torpedo: i32, bomb: i32) -> WeaponInventory {
+        WeaponInventory { sonar, torpedo, bomb }
+    }
+
+    /// Charges left for `weapon`
+    pub fn get_charges(&self, weapon: Weapon) -> i32 {
+        match weapon {
+            Weapon::Sonar => self.sonar,
+            Weapon::Torpedo => self.torpedo,
+            Weapon::Bomb => self.bomb,
+        }
+    }
+}
+
+impl WeaponInventory {
+    /// Use up one charge of `weapon`, `false` if there is none left
+    pub(crate) fn take(&mut self, weapon: Weapon) -> bool {
+        let charges = match weapon {
+            Weapon::Sonar => &mut self.sonar,
+            Weapon::Torpedo => &mut self.torpedo,
+            Weapon::Bomb => &mut self.bomb,
+        };
+        if *charges <= 0 {
+            return false;
+        }
+        *charges -= 1;
+        true
+    }
+}
+
+/// What a special weapon did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WeaponOutcome {
+    pub weapon: Weapon,
+    /// Only set for `Weapon::Sonar`
+    pub sonar: Option<SonarStatus>,
+    /// Squares the weapon struck and what each produced, empty for `Weapon::Sonar`
+    pub squares: Vec<(GridPoint, Shot)>,
+    /// Points scored
+    pub points: i32,
+}
+
+/// Reasons a special weapon can not be used
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WeaponError {
+    /// No charges of the weapon are left
+    NoCharges(Weapon),
+    /// The target is not on the grid
+    OffGrid(GridPoint),
+    /// The game is already over
+    GameOver,
+}
+
+impl fmt::Display for WeaponError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WeaponError::NoCharges(weapon) => write!(f, "no {:?} charges left", weapon),
+            WeaponError::OffGrid(point) => write!(f, "{} is not on the grid", point),
+            WeaponError::GameOver => write!(f, "the game is over"),
+        }
+    }
+}
+
+impl std::error::Error for WeaponError {}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn take_charges() {
+        let mut weapons = WeaponInventory::new(1, 0, 2);
+        assert!(weapons.take(Weapon::Sonar));
+        assert!(!weapons.take(Weapon::Sonar));
+        assert!(!weapons.take(Weapon::Torpedo));
+        assert!(weapons.take(Weapon::Bomb));
+        assert_eq!(1, weapons.get_charges(Weapon::Bomb));
+    }
+
+    #[test]
+    fn directions_read_recorded_names() {
+        assert_eq!("\"Left\"", serde_json::to_string(&Direction::Left).unwrap());
+        assert_eq!(Direction::Up, serde_json::from_str("\"VerticalReversed\"").unwrap());
+        assert_eq!(Direction::Up, serde_json::from_str("\"Up\"").unwrap());
+        assert_eq!(GridPoint { x: 3, y: 4 }, Direction::Down.step(GridPoint { x: 3, y: 3 }));
+    }
+}
@@ -0,0 +1,471 @@
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use battleship_game_lib::bundle::MergeStrategy;
+use battleship_game_lib::notation;
+use battleship_game_lib::puzzle::PuzzleSession;
+use battleship_game_lib::runtime::{GridPoint, ShotStatus};
+use battleship_game_lib::storage::SaveStatus;
+use battleship_game_lib::view::CellState;
+use serde_json::json;
+
+use crate::{data, plain};
+
+const USAGE: &str = "\
+Usage: cli_game [COMMAND] [--json]
+
+Without a command the game starts in the terminal.
+
+Commands:
+  --plain [--name NAME]          Play with prompts over plain lines of text, for pipes and SSH
+  puzzle FILE                    Solve a puzzle read from FILE with prompts over plain lines of text
+  new --name NAME                Start a game and print its id
+  shoot --game ID SQUARE         Fire at a square such as B7
+  status --game ID               Show the board and the score of a game
+  list [--name NAME]             List the saved games, of one player or of everyone
+  leaderboard [--top N] [--all]  Show the N best scores (10), --all includes games that used hints
+  delete --game ID               Delete a saved game
+  export [FILE]                  Write every saved game, high score and player record to FILE, or to stdout
+  import FILE [OPTIONS]          Merge a file written by export, - reads stdin
+      --dry-run                  Only report what would change
+      --on-conflict STRATEGY     keep (default), overwrite or both
+  help                           Print this message
+
+Every command but export and puzzle prints JSON instead of text with --json.";
+
+/// Scores shown by `leaderboard` without `--top`
+const DEFAULT_TOP: usize = 10;
+
+/// What the command line asks for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+  /// Play in the terminal
+  Play,
+  /// Play with prompts over stdin and stdout
+  Plain { name: Option<String> },
+  /// Solve the puzzle in a file with prompts over stdin and stdout
+  Puzzle { file: PathBuf },
+  Help,
+  New { name: String },
+  Shoot { game: String, square: GridPoint },
+  Status { game: String },
+  /// Saved games of one player, or of everyone without a name
+  List { name: Option<String> },
+  Leaderboard { top: usize, include_assisted: bool },
+  Delete { game: String },
+  /// Export to a file, or to stdout without one
+  Export { file: Option<PathBuf> },
+  /// Import from a file, or from stdin for `-`
+  Import { file: PathBuf, strategy: MergeStrategy, dry_run: bool },
+}
+
+/// A command and whether its output is JSON
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invocation {
+  pub command: Command,
+  pub json: bool,
+}
+
+/// Options and positional arguments of a command, each option taken once it is read
+struct Arguments {
+  options: Vec<(String, Option<String>)>,
+  positional: Vec<String>,
+}
+
+impl Arguments {
+  /// Split `args` into options and positional arguments, `valued` options take the argument after them
+  fn split<I: Iterator<Item = String>>(mut args: I, valued: &[&str], flags: &[&str]) -> Result<Arguments> {
+    let (mut options, mut positional) = (Vec::new(), Vec::new());
+    while let Some(arg) = args.next() {
+      if valued.contains(&arg.as_str()) {
+        let value = args.next().ok_or_else(|| anyhow!("{} needs a value", arg))?;
+        options.push((arg, Some(value)));
+      } else if flags.contains(&arg.as_str()) {
+        options.push((arg, None));
+      } else if arg.starts_with("--") {
+        bail!("unknown option {}\n\n{}", arg, USAGE);
+      } else {
+        positional.push(arg);
+      }
+    }
+    Ok(Arguments { options, positional })
+  }
+
+  fn flag(&mut self, name: &str) -> bool {
+    let before = self.options.len();
+    self.options.retain(|(option, _)| option != name);
+    self.options.len() < before
+  }
+
+  fn value(&mut self, name: &str) -> Option<String> {
+    let n = self.options.iter().position(|(option, _)| option == name)?;
+    self.options.remove(n).1
+  }
+
+  fn required(&mut self, name: &str) -> Result<String> {
+    self.value(name).ok_or_else(|| anyhow!("{} is required\n\n{}", name, USAGE))
+  }
+
+  /// The only positional argument, `None` if there is none
+  fn single(&mut self) -> Result<Option<String>> {
+    if self.positional.len() > 1 {
+      bail!("unexpected argument {}\n\n{}", self.positional[1], USAGE);
+    }
+    Ok(self.positional.pop())
+  }
+
+  fn expect_end(&self) -> Result<()> {
+    match self.positional.first() {
+      None => Ok(()),
+      Some(arg) => bail!("unexpected argument {}\n\n{}", arg, USAGE),
+    }
+  }
+}
+
+/// Read the command from the arguments after the program name
+pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Invocation> {
+  let mut args = args.into_iter();
+  let command = match args.next() {
+    None => return Ok(Invocation { command: Command::Play, json: false }),
+    Some(command) => command,
+  };
+  let mut arguments = Arguments::split(args, &["--name", "--game", "--top", "--on-conflict"], &["--json", "--all", "--dry-run"])?;
+  let json = arguments.flag("--json");
+
+  let command = match command.as_str() {
+    "help" | "--help" | "-h" => Command::Help,
+    "--plain" => Command::Plain { name: arguments.value("--name") },
+    "puzzle" => {
+      let file = arguments.single()?.ok_or_else(|| anyhow!("puzzle needs a file\n\n{}", USAGE))?;
+      Command::Puzzle { file: PathBuf::from(file) }
+    },
+    "new" => Command::New { name: arguments.required("--name")? },
+    "shoot" => {
+      let game = arguments.required("--game")?;
+      let square = arguments.single()?.ok_or_else(|| anyhow!("shoot needs a square such as B7\n\n{}", USAGE))?;
+      Command::Shoot { game, square: square.parse().with_context(|| format!("can not fire at {}", square))? }
+    },
+    "status" => Command::Status { game: arguments.required("--game")? },
+    "list" => Command::List { name: arguments.value("--name") },
+    "leaderboard" => {
+      let top = match arguments.value("--top") {
+        Some(top) => top.parse().map_err(|_| anyhow!("--top needs a number, not {}", top))?,
+        None => DEFAULT_TOP,
+      };
+      Command::Leaderboard { top, include_assisted: arguments.flag("--all") }
+    },
+    "delete" => Command::Delete { game: arguments.required("--game")? },
+    "export" => Command::Export { file: arguments.single()?.map(PathBuf::from) },
+    "import" => {
+      let strategy = match arguments.value("--on-conflict") {
+        Some(strategy) => parse_strategy(&strategy)?,
+        None => MergeStrategy::KeepExisting,
+      };
+      let dry_run = arguments.flag("--dry-run");
+      let file = arguments.single()?.ok_or_else(|| anyhow!("import needs a file\n\n{}", USAGE))?;
+      Command::Import { file: PathBuf::from(file), strategy, dry_run }
+    },
+    _ => bail!("unknown command {}\n\n{}", command, USAGE),
+  };
+
+  arguments.expect_end()?;
+  if let Some((option, _)) = arguments.options.first() {
+    bail!("{} does not apply to {}\n\n{}", option, command_name(&command), USAGE);
+  }
+  Ok(Invocation { command, json })
+}
+
+fn command_name(command: &Command) -> &'static str {
+  match command {
+    Command::Play => "play",
+    Command::Plain { .. } => "--plain",
+    Command::Puzzle { .. } => "puzzle",
+    Command::Help => "help",
+    Command::New { .. } => "new",
+    Command::Shoot { .. } => "shoot",
+    Command::Status { .. } => "status",
+    Command::List { .. } => "list",
+    Command::Leaderboard { .. } => "leaderboard",
+    Command::Delete { .. } => "delete",
+    Command::Export { .. } => "export",
+    Command::Import { .. } => "import",
+  }
+}
+
+fn parse_strategy(strategy: &str) -> Result<MergeStrategy> {
+  match strategy {
+    "keep" => Ok(MergeStrategy::KeepExisting),
+    "overwrite" => Ok(MergeStrategy::Overwrite),
+    "both" => Ok(MergeStrategy::KeepBoth),
+    _ => bail!("unknown conflict strategy {}, use keep, overwrite or both", strategy),
+  }
+}
+
+/// Character a square is drawn with in `status`
+fn cell_symbol(cell: CellState) -> char {
+  match cell {
+    CellState::Unknown => '.',
+    CellState::Miss => 'o',
+    CellState::Hit => 'X',
+    CellState::Sunk => 'x',
+    CellState::Ship => 'S',
+    CellState::Island => '^',
+    CellState::Reef => '~',
+  }
+}
+
+/// Write `value` as JSON, or `text` for people
+fn print(out: &mut impl Write, json: bool, value: serde_json::Value, text: &str) -> Result<()> {
+  if json {
+    writeln!(out, "{}", serde_json::to_string_pretty(&value)?)?;
+  } else {
+    write!(out, "{}", text)?;
+  }
+  Ok(())
+}
+
+/// Run a command other than `Play` on the games kept in `dir`, writing what it has to say to `out`
+pub fn run(invocation: Invocation, dir: &Path, out: &mut impl Write) -> Result<()> {
+  let json = invocation.json;
+  match invocation.command {
+    Command::Play => unreachable!("the game is played in the terminal"),
+    Command::Help => writeln!(out, "{}", USAGE)?,
+    Command::Plain { name } => {
+      let mut game = data::load(dir)?;
+      let result = plain::run(&mut game, name.unwrap_or_else(|| String::from(plain::DEFAULT_NAME)), &mut std::io::stdin().lock(), out);
+      data::store(&game, dir)?;
+      result?;
+    },
+    Command::Puzzle { file } => {
+      let json = fs::read_to_string(&file).with_context(|| format!("could not read {}", file.display()))?;
+      let mut session = PuzzleSession::from_json(&json).with_context(|| format!("could not load {}", file.display()))?;
+      plain::solve_puzzle(&mut session, &mut std::io::stdin().lock(), out)?;
+    },
+    Command::New { name } => {
+      let mut game = data::load(dir)?;
+      game.start_new(name.clone());
+      game.save_progress();
+      let id = game.get_play().and_then(|play| play.get_save_id()).expect("the game was just saved");
+      data::store(&game, dir)?;
+      print(out, json, json!({ "game": id, "player": name }), &format!("{}\n", id))?;
+    },
+    Command::Shoot { game: id, square } => {
+      let mut game = data::load(dir)?;
+      let session = game.load(id.clone())?.get_session_as_mut();
+
+      let shot = session.shoot_ship(square);
+      match shot.status {
+        ShotStatus::Over => bail!("game {} is over", id),
+        ShotStatus::OffBoard => bail!("{:#} is off the board", square),
+        ShotStatus::Salvo => bail!("game {} is played in salvos, single shots are not allowed", id),
+        _ => (),
+      }
+      let ship = shot.ship_type.map(|ship_type| session.get_destroyed_ships().iter().find(|ship| ship.get_type() == ship_type).map_or_else(|| format!("{:?}", ship_type), |ship| ship.get_name().to_string()));
+      let (points, remaining_shots, over, won) = (session.get_points(), session.get_remaining_shots(), session.is_over(), session.is_won());
+      let unlocked = if over {
+        game.finish_game().unwrap_or_default()
+      } else {
+        game.save_progress();
+        Vec::new()
+      };
+      data::store(&game, dir)?;
+
+      let mut text = match (shot.status, &ship) {
+        (ShotStatus::Hit, Some(ship)) => format!("Hit! {} sunk\n", ship),
+        (ShotStatus::Hit, None) => String::from("Hit!\n"),
+        (ShotStatus::Miss, _) => String::from("Miss\n"),
+        (ShotStatus::Repeat, _) => format!("{:#} was already fired at\n", square),
+        (ShotStatus::Blocked, _) => format!("{:#} is an island\n", square),
+        (ShotStatus::Over | ShotStatus::OffBoard | ShotStatus::Salvo, _) => unreachable!("refused shots are reported above"),
+      };
+      text += &format!("{} points, {} shots left\n", points, remaining_shots);
+      if over {
+        text += if won { "Every ship is sunk, you won\n" } else { "Game over\n" };
+      }
+      for achievement in unlocked.iter() {
+        text += &format!("Achievement unlocked: {}\n", achievement.get_name());
+      }
+      let value = json!({
+        "game": id,
+        "square": format!("{:#}", square),
+        "status": shot.status,
+        "ship": ship,
+        "points": points,
+        "remaining_shots": remaining_shots,
+        "game_over": over,
+        "won": won,
+        "achievements": unlocked.iter().map(|achievement| achievement.get_name()).collect::<Vec<&str>>(),
+      });
+      print(out, json, value, &text)?;
+    },
+    Command::Status { game: id } => {
+      let mut game = data::load(dir)?;
+      let session = game.load(id.clone())?.get_session_as_ref();
+      let board: Vec<String> = session.get_board_view().rows().iter().map(|row| row.iter().map(|cell| cell_symbol(*cell)).collect()).collect();
+      let sunk: Vec<String> = session.get_destroyed_ships().iter().map(|ship| ship.get_name().to_string()).collect();
+
+      let columns: String = (1..=board.first().map_or(0, String::len)).map(|n| format!(" {}", notation::axis_letters(n as i32))).collect();
+      let mut text = format!("{}  {} points, {} shots fired, {} left{}\n\n   {}\n", session.get_player_name(), session.get_points(), session.get_shots_fired(), session.get_remaining_shots(), if session.is_over() { ", over" } else { "" }, columns.trim_start());
+      for (n, row) in board.iter().enumerate() {
+        let cells: Vec<String> = row.chars().map(String::from).collect();
+        text += &format!("{:>2} {}\n", n + 1, cells.join(" "));
+      }
+      if !sunk.is_empty() {
+        text += &format!("\nSunk: {}\n", sunk.join(", "));
+      }
+      let value = json!({
+        "game": id,
+        "player": session.get_player_name(),
+        "points": session.get_points(),
+        "shots_fired": session.get_shots_fired(),
+        "remaining_shots": session.get_remaining_shots(),
+        "game_over": session.is_over(),
+        "won": session.is_won(),
+        "sunk": sunk,
+        "board": board,
+      });
+      print(out, json, value, &text)?;
+    },
+    Command::List { name } => {
+      let game = data::load(dir)?;
+      let names = name.map_or_else(|| game.list_players(), |name| vec![name]);
+      let saves: Vec<_> = names.iter().flat_map(|name| game.list_saves(name)).collect();
+      let text: String = saves.iter().map(|save| format!("{}  {}  {} points{}\n", save.id, save.player_name, save.points, if save.status == SaveStatus::Finished { ", finished" } else { "" })).collect();
+      print(out, json, serde_json::to_value(&saves)?, &text)?;
+    },
+    Command::Leaderboard { top, include_assisted } => {
+      let mut scores = data::load(dir)?.get_high_scores(include_assisted);
+      scores.truncate(top);
+      let text: String = scores.iter().enumerate().map(|(n, score)| format!("{:>2}. {}  {}{}\n", n + 1, score.name, score.point, if score.assisted { " (hints)" } else { "" })).collect();
+      print(out, json, serde_json::to_value(&scores)?, &text)?;
+    },
+    Command::Delete { game: id } => {
+      let mut game = data::load(dir)?;
+      if !game.delete(id.clone()) {
+        bail!("there is no save {}", id);
+      }
+      data::store(&game, dir)?;
+      data::remove_save(dir, &id)?;
+      print(out, json, json!({ "deleted": id }), &format!("Deleted {}\n", id))?;
+    },
+    Command::Export { file } => {
+      let bytes = data::load(dir)?.export_all();
+      match file {
+        Some(file) => fs::write(&file, bytes).with_context(|| format!("could not write {}", file.display()))?,
+        None => out.write_all(&bytes)?,
+      }
+    },
+    Command::Import { file, strategy, dry_run } => {
+      let bytes = if file.as_os_str() == "-" {
+        let mut bytes = Vec::new();
+        std::io::stdin().read_to_end(&mut bytes)?;
+        bytes
+      } else {
+        fs::read(&file).with_context(|| format!("could not read {}", file.display()))?
+      };
+
+      let mut game = data::load(dir)?;
+      let report = game.import(&bytes, strategy, dry_run)?;
+      if !dry_run {
+        data::store(&game, dir)?;
+      }
+      print(out, json, serde_json::to_value(&report)?, &report.to_string())?;
+    },
+  }
+  Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parse_line(line: &str) -> Result<Invocation> {
+    parse(line.split_whitespace().map(String::from))
+  }
+
+  fn run_line(dir: &Path, line: &str) -> Result<serde_json::Value> {
+    let mut out = Vec::new();
+    run(parse_line(line)?, dir, &mut out)?;
+    Ok(serde_json::from_slice(&out)?)
+  }
+
+  #[test]
+  fn parse_commands() {
+    assert_eq!(Command::Play, parse_line("").unwrap().command);
+    assert_eq!(Command::Export { file: None }, parse_line("export").unwrap().command);
+    assert_eq!(Command::Plain { name: Some(String::from("Tolu")) }, parse_line("--plain --name Tolu").unwrap().command);
+    assert_eq!(Command::Puzzle { file: PathBuf::from("puzzle.json") }, parse_line("puzzle puzzle.json").unwrap().command);
+    assert_eq!(
+      Invocation { command: Command::Shoot { game: String::from("save-2"), square: GridPoint { x: 2, y: 7 } }, json: true },
+      parse_line("shoot b7 --json --game save-2").unwrap()
+    );
+    assert_eq!(Command::Leaderboard { top: DEFAULT_TOP, include_assisted: false }, parse_line("leaderboard").unwrap().command);
+    assert_eq!(
+      Command::Import { file: PathBuf::from("games.json"), strategy: MergeStrategy::KeepBoth, dry_run: true },
+      parse_line("import --on-conflict both games.json --dry-run").unwrap().command
+    );
+    for line in ["new", "shoot --game save-1", "shoot --game save-1 7B", "leaderboard --top ten", "list --all", "import", "puzzle", "import a.json --on-conflict merge", "export a.json b.json", "status --game", "fire"] {
+      assert!(parse_line(line).is_err(), "{}", line);
+    }
+  }
+
+  #[test]
+  fn scripted_game() {
+    let dir = tempfile::tempdir().unwrap();
+    let home = dir.path().to_path_buf();
+
+    let game = run_line(&home, "new --name Adetayo --json").unwrap()["game"].as_str().unwrap().to_string();
+    let shot = run_line(&home, &format!("shoot --game {} A1 --json", game)).unwrap();
+    assert_eq!(1, run_line(&home, &format!("status --game {} --json", game)).unwrap()["shots_fired"]);
+    assert_eq!(shot["remaining_shots"], run_line(&home, &format!("status --game {} --json", game)).unwrap()["remaining_shots"]);
+    assert_eq!("Repeat", run_line(&home, &format!("shoot --game {} a1 --json", game)).unwrap()["status"]);
+    assert!(run_line(&home, &format!("shoot --game {} K1", game)).is_err());
+
+    assert_eq!(game, run_line(&home, "list --name Adetayo --json").unwrap()[0]["id"]);
+    assert_eq!(serde_json::json!([]), run_line(&home, "leaderboard --json").unwrap());
+
+    // a score written into the records without a save that replays to it is left off the table
+    let records = home.join("records.json");
+    let mut bundle: serde_json::Value = serde_json::from_slice(&fs::read(&records).unwrap()).unwrap();
+    bundle["scores"] = serde_json::json!([{ "name": "Adetayo", "point": 9000, "assisted": false }]);
+    fs::write(&records, bundle.to_string()).unwrap();
+    assert_eq!(serde_json::json!([]), run_line(&home, "leaderboard --json").unwrap());
+
+    // a save edited on disk is left out, and new saves are not written over it
+    let save = home.join("saves").join(format!("{}.json", game));
+    let json = fs::read_to_string(&save).unwrap();
+    let edited = json.replacen("\"Adetayo\"", "\"Tolu\"", 1);
+    fs::write(&save, &edited).unwrap();
+    assert_eq!(serde_json::json!([]), run_line(&home, "list --name Adetayo --json").unwrap());
+    let other = run_line(&home, "new --name Tolu --json").unwrap()["game"].as_str().unwrap().to_string();
+    assert_ne!(game, other);
+    assert_eq!(edited, fs::read_to_string(&save).unwrap());
+
+    fs::write(&save, json).unwrap();
+    run_line(&home, &format!("delete --game {} --json", game)).unwrap();
+    assert!(run_line(&home, &format!("status --game {}", game)).is_err());
+    assert_eq!(other, run_line(&home, "list --json").unwrap()[0]["id"]);
+    assert!(!save.exists());
+  }
+
+  #[test]
+  fn deleted_games_keep_their_scores() {
+    let dir = tempfile::tempdir().unwrap();
+    let home = dir.path().to_path_buf();
+
+    let game = run_line(&home, "new --name Adetayo --json").unwrap()["game"].as_str().unwrap().to_string();
+    let ships = data::load(&home).unwrap().load(game.clone()).unwrap().get_session_as_ref().get_remaining_ships();
+    // one hit sinks a ship
+    for ship in ships {
+      run_line(&home, &format!("shoot --game {} {:#} --json", game, ship.get_squares()[0])).unwrap();
+    }
+    let leaderboard = run_line(&home, "leaderboard --json").unwrap();
+    assert_eq!("Adetayo", leaderboard[0]["name"]);
+
+    run_line(&home, &format!("delete --game {} --json", game)).unwrap();
+    assert_eq!(leaderboard, run_line(&home, "leaderboard --json").unwrap());
+  }
+}
@@ -0,0 +1,114 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use battleship_game_lib::{bundle::MergeStrategy, save, GamePlay};
+
+/// Overrides where the games are kept, mostly for scripts and tests
+const HOME_VARIABLE: &str = "BATTLESHIP_HOME";
+/// Key the saves are signed with, it never leaves the machine
+const KEY_FILE: &str = "save.key";
+/// Saved games, one signed save file each named after its id
+const SAVES_DIR: &str = "saves";
+/// High scores and player records signed with the key, in the format of `GamePlay::export_records`
+const RECORDS_FILE: &str = "records.json";
+/// Everything in one unsigned bundle, as earlier versions kept it
+const LEGACY_GAMES_FILE: &str = "games.json";
+
+/// Directory the games are kept in, `$BATTLESHIP_HOME` or `.battleship` in the home directory
+pub fn data_dir() -> PathBuf {
+  if let Some(dir) = std::env::var_os(HOME_VARIABLE) {
+    return PathBuf::from(dir);
+  }
+  let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE")).map(PathBuf::from).unwrap_or_default();
+  home.join(".battleship")
+}
+
+/// Everything saved in `dir`, or an empty store on the first run.
+/// A save or records file that can not be loaded, or fails its signature check, is left out and reported on stderr.
+pub fn load(dir: &Path) -> Result<GamePlay> {
+  fs::create_dir_all(dir.join(SAVES_DIR)).with_context(|| format!("could not create {}", dir.display()))?;
+
+  let key_file = dir.join(KEY_FILE);
+  let key = match fs::read(&key_file) {
+    Ok(key) => key,
+    Err(err) if err.kind() == ErrorKind::NotFound => {
+      let key = save::generate_key();
+      fs::write(&key_file, &key).with_context(|| format!("could not write {}", key_file.display()))?;
+      key
+    },
+    Err(err) => return Err(err).with_context(|| format!("could not read {}", key_file.display())),
+  };
+
+  let mut game = GamePlay::initialize_with_key(key);
+  let mut files: Vec<PathBuf> = fs::read_dir(dir.join(SAVES_DIR))?
+    .map(|entry| entry.map(|entry| entry.path()))
+    .collect::<std::io::Result<_>>()?;
+  files.retain(|file| file.extension().is_some_and(|extension| extension == "json"));
+  files.sort();
+  for file in files {
+    let loaded = fs::read_to_string(&file).map_err(anyhow::Error::from).and_then(|json| Ok(game.import_save(&json)?));
+    if let Err(err) = loaded {
+      eprintln!("Left out {}: {:#}", file.display(), err);
+      // new saves must not be written over the file
+      if let Some(id) = file.file_stem().and_then(|stem| stem.to_str()) {
+        game.reserve_save_id(id);
+      }
+    }
+  }
+
+  // the bundle of earlier versions is unsigned, its scores are only kept if its saves replay to them
+  if let Some(bytes) = read_if_present(&dir.join(LEGACY_GAMES_FILE))? {
+    game.import(&bytes, MergeStrategy::Overwrite, false).with_context(|| format!("could not load {}", LEGACY_GAMES_FILE))?;
+  }
+  if let Some(bytes) = read_if_present(&dir.join(RECORDS_FILE))? {
+    if let Err(err) = game.import_records(&bytes) {
+      eprintln!("Left out {}: {}", dir.join(RECORDS_FILE).display(), err);
+    }
+  }
+  Ok(game)
+}
+
+/// Contents of `file`, `None` if there is no such file
+fn read_if_present(file: &Path) -> Result<Option<Vec<u8>>> {
+  match fs::read(file) {
+    Ok(bytes) => Ok(Some(bytes)),
+    Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+    Err(err) => Err(err).with_context(|| format!("could not read {}", file.display())),
+  }
+}
+
+/// Write the store back to `dir`, each save and the records replaced in one step so a crash can not leave half of one.
+/// Files of saves that were left out on loading stay as they are.
+pub fn store(game: &GamePlay, dir: &Path) -> Result<()> {
+  let saves_dir = dir.join(SAVES_DIR);
+  for id in game.list_players().into_iter().flat_map(|name| game.list_saved(name)) {
+    let json = game.export_save(&id).expect("the save was just listed");
+    write_file(&saves_dir.join(format!("{}.json", id)), json.as_bytes())?;
+  }
+
+  write_file(&dir.join(RECORDS_FILE), &game.export_records())?;
+  // the saves and records written above replace the bundle of earlier versions
+  match fs::remove_file(dir.join(LEGACY_GAMES_FILE)) {
+    Err(err) if err.kind() != ErrorKind::NotFound => Err(err).with_context(|| format!("could not delete {}", LEGACY_GAMES_FILE)),
+    _ => Ok(()),
+  }
+}
+
+/// Delete the file of the save `id`, after it was deleted from the store
+pub fn remove_save(dir: &Path, id: &str) -> Result<()> {
+  let file = dir.join(SAVES_DIR).join(format!("{}.json", id));
+  match fs::remove_file(&file) {
+    Err(err) if err.kind() != ErrorKind::NotFound => Err(err).with_context(|| format!("could not delete {}", file.display())),
+    _ => Ok(()),
+  }
+}
+
+/// Write `file` through a partial file renamed over it
+fn write_file(file: &Path, bytes: &[u8]) -> Result<()> {
+  let partial = file.with_extension("json.partial");
+  fs::write(&partial, bytes).with_context(|| format!("could not write {}", partial.display()))?;
+  fs::rename(&partial, file).with_context(|| format!("could not write {}", file.display()))?;
+  Ok(())
+}
@@ -1,5 +1,8 @@
+mod commands;
+mod data;
+mod plain;
+
 use anyhow::Result;
-use battleship_game_lib::{runtime::GridPoint, GamePlay};
 use crossterm::{
     event::{self, Event::Key, KeyCode::Char},
     execute,
@@ -76,6 +79,11 @@ fn startup() -> Result<()> {
   }
   
   fn main() -> Result<()> {
+    let invocation = commands::parse(std::env::args().skip(1))?;
+    if invocation.command != commands::Command::Play {
+      return commands::run(invocation, &data::data_dir(), &mut std::io::stdout());
+    }
+
     // setup terminal
     startup()?;
   
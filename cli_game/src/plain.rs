@@ -0,0 +1,230 @@
+use std::io::{BufRead, Write};
+
+use anyhow::Result;
+use battleship_game_lib::notation::{self, NotationError};
+use battleship_game_lib::puzzle::{MarkingError, PuzzleSession};
+use battleship_game_lib::runtime::{GridPoint, Session, ShotStatus};
+use battleship_game_lib::GamePlay;
+
+/// Player name used without `--name`
+pub const DEFAULT_NAME: &str = "Player";
+
+/// Play one game as a prompt loop over lines of text, so it works in pipes and over SSH.
+/// The game is recorded when it ends, or kept as a save if the input runs out first.
+pub fn run(game: &mut GamePlay, name: String, input: &mut impl BufRead, out: &mut impl Write) -> Result<()> {
+  writeln!(out, "Would you like to play in debug mode? (y/n)")?;
+  let debug = match read_line(input)? {
+    Some(answer) => answer.trim().to_lowercase().starts_with('y'),
+    None => return Ok(()),
+  };
+
+  let session = game.start_new(name).get_session_as_mut();
+  session.debug = debug;
+  while !session.is_over() {
+    if session.debug {
+      write_ship_locations(session, out)?;
+    }
+    writeln!(out, "Please enter the next square to fire at:")?;
+    let line = match read_line(input)? {
+      Some(line) => line,
+      None => break,
+    };
+
+    let square = match parse_square(&line) {
+      Ok(square) => square,
+      Err(err) => {
+        writeln!(out, "{}", err)?;
+        continue;
+      },
+    };
+    let shot = session.shoot_ship(square);
+    match shot.status {
+      ShotStatus::Hit => {
+        let ship = shot.ship_type.and_then(|ship_type| session.get_destroyed_ships().into_iter().find(|ship| ship.get_type() == ship_type));
+        match ship {
+          Some(ship) => writeln!(out, "Hit! You sank the {}, worth {} points.", ship.get_name(), ship.get_point())?,
+          None => writeln!(out, "Hit!")?,
+        }
+      },
+      ShotStatus::Miss => writeln!(out, "Miss.")?,
+      ShotStatus::Repeat => writeln!(out, "You have already fired at {:#}.", square)?,
+      ShotStatus::Blocked => writeln!(out, "{:#} is an island.", square)?,
+      ShotStatus::Over => unreachable!("the loop stops once the game is over"),
+      ShotStatus::Salvo => unreachable!("new games are not played in salvos"),
+      ShotStatus::OffBoard => {
+        let view = session.get_board_view();
+        writeln!(out, "{:#} is not on the grid, it runs from A1 to {:#}", square, GridPoint::new(view.get_width(), view.get_height()))?
+      },
+    }
+  }
+
+  if !session.is_over() {
+    game.save_progress();
+    let id = game.get_play().and_then(|play| play.get_save_id()).expect("the game was just saved");
+    writeln!(out, "Game saved as {}.", id)?;
+    return Ok(());
+  }
+
+  let mut destroyed = session.get_destroyed_ships();
+  destroyed.sort_by_key(|ship| ship.get_type());
+  let names: Vec<&str> = destroyed.iter().map(|ship| ship.get_name()).collect();
+  writeln!(out, "Game over.")?;
+  writeln!(out, "Ships destroyed: {}", if names.is_empty() { String::from("none") } else { names.join(", ") })?;
+  writeln!(out, "Total points: {}", session.get_points())?;
+  game.finish_game();
+  Ok(())
+}
+
+/// Solve a puzzle as a prompt loop over lines of text, each square entered marks or clears it.
+/// Stops once the puzzle is solved or the input runs out.
+pub fn solve_puzzle(session: &mut PuzzleSession, input: &mut impl BufRead, out: &mut impl Write) -> Result<()> {
+  loop {
+    write_puzzle(session, out)?;
+    if session.is_solved() {
+      writeln!(out, "Solved, every ship is marked.")?;
+      return Ok(());
+    }
+    writeln!(out, "Please enter a square to mark or clear:")?;
+    let line = match read_line(input)? {
+      Some(line) => line,
+      None => return Ok(()),
+    };
+
+    let square = match parse_square(&line) {
+      Ok(square) => square,
+      Err(err) => {
+        writeln!(out, "{}", err)?;
+        continue;
+      },
+    };
+    if !(1..=session.get_width()).contains(&square.x) || !(1..=session.get_height()).contains(&square.y) {
+      writeln!(out, "{:#} is not on the grid, it runs from A1 to {:#}", square, GridPoint::new(session.get_width(), session.get_height()))?;
+      continue;
+    }
+    if session.is_revealed(square) {
+      writeln!(out, "{:#} is given away and can not be changed.", square)?;
+      continue;
+    }
+    session.toggle_mark(square);
+    for error in session.validate() {
+      match error {
+        MarkingError::TooManyInRow(y) => writeln!(out, "Row {} has more marks than ship squares.", y)?,
+        MarkingError::TooManyInColumn(x) => writeln!(out, "Column {} has more marks than ship squares.", notation::axis_letters(x))?,
+      }
+    }
+  }
+}
+
+/// Marks of a puzzle, with the ship squares of each row at its end and of each column below it
+fn write_puzzle(session: &PuzzleSession, out: &mut impl Write) -> Result<()> {
+  let columns: String = (1..=session.get_width()).map(|x| format!(" {}", notation::axis_letters(x))).collect();
+  writeln!(out, "  {}", columns)?;
+  for y in 1..=session.get_height() {
+    let cells: String = (1..=session.get_width()).map(|x| {
+      let point = GridPoint::new(x, y);
+      match (session.is_marked(point), session.is_revealed(point)) {
+        (true, _) => " #",
+        (false, true) => " o",
+        (false, false) => " .",
+      }
+    }).collect();
+    writeln!(out, "{:>2}{}  {}", y, cells, session.get_row_count(y))?;
+  }
+  let counts: String = (1..=session.get_width()).map(|x| format!(" {}", session.get_column_count(x))).collect();
+  writeln!(out, "  {}", counts)?;
+  Ok(())
+}
+
+/// Next line without its line ending, `None` once the input has run out
+fn read_line(input: &mut impl BufRead) -> Result<Option<String>> {
+  let mut line = String::new();
+  if input.read_line(&mut line)? == 0 {
+    return Ok(None);
+  }
+  Ok(Some(line.trim_end_matches(['\r', '\n']).to_string()))
+}
+
+/// Square such as B7, or as the (x,y) pair the debug table shows
+fn parse_square(line: &str) -> Result<GridPoint, String> {
+  let line = line.trim();
+  if line.contains(',') {
+    let pair = line.trim_start_matches('(').trim_end_matches(')');
+    let numbers: Vec<Option<i32>> = pair.split(',').map(|number| number.trim().parse().ok()).collect();
+    return match numbers[..] {
+      [Some(x), Some(y)] => Ok(GridPoint::new(x, y)),
+      _ => Err(format!("{} is not a square, enter one such as B7 or (2,7)", line)),
+    };
+  }
+  line.parse().map_err(|err: NotationError| err.to_string())
+}
+
+/// Table of the ships still afloat and their squares
+fn write_ship_locations(session: &Session, out: &mut impl Write) -> Result<()> {
+  let mut ships = session.get_remaining_ships();
+  ships.sort_by_key(|ship| ship.get_type());
+  let width = ships.iter().map(|ship| ship.get_name().len()).max().unwrap_or(0).max("Ship".len());
+
+  writeln!(out, "{:<width$}  Squares", "Ship", width = width)?;
+  for ship in ships {
+    let squares: Vec<String> = ship.get_squares().iter().map(|square| format!("({},{})", square.x, square.y)).collect();
+    writeln!(out, "{:<width$}  {}", ship.get_name(), squares.join(" "), width = width)?;
+  }
+  Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+  use battleship_game_lib::inventory::{grid::Grid, ship::{Orientation, ShipType}};
+  use battleship_game_lib::puzzle::Puzzle;
+  use battleship_game_lib::replay::ShipPlacement;
+
+  use super::*;
+
+  fn play(input: &str) -> (GamePlay, String) {
+    let mut game = GamePlay::initialize();
+    let mut out = Vec::new();
+    run(&mut game, String::from(DEFAULT_NAME), &mut input.as_bytes(), &mut out).unwrap();
+    (game, String::from_utf8(out).unwrap())
+  }
+
+  #[test]
+  fn scripted_game() {
+    let (game, out) = play("n\nZ\nA1\na1\n(2,x)\n(2,2)\nC3\nD4\nE5\nF6\nG7\nH8\nI9\nJ10\n");
+    assert!(!out.contains("Squares"));
+    assert!(out.contains("a square ends with its number"));
+    assert!(out.contains("(2,x) is not a square"));
+    assert!(out.contains("You have already fired at A1."));
+    assert!(out.contains("Ships destroyed: "));
+    assert!(out.contains("Total points: "));
+    // the game ends after ten shots, the invalid and repeated squares are not counted
+    assert_eq!(13, out.matches("Please enter the next square to fire at:").count());
+    assert_eq!(1, game.list_saved(String::from(DEFAULT_NAME)).len());
+  }
+
+  #[test]
+  fn solve_loaded_puzzle() {
+    let fleet = [
+      ShipPlacement { ship_type: ShipType::Destroyer, origin: GridPoint::new(2, 2), orientation: Orientation::Horizontal },
+      ShipPlacement { ship_type: ShipType::PatrolBoat, origin: GridPoint::new(1, 1), orientation: Orientation::Horizontal },
+    ];
+    let puzzle = Puzzle::build_from_grid(&Grid::build_from_fleet(&fleet).unwrap());
+    let mut session = PuzzleSession::from_json(&puzzle.to_json()).unwrap();
+    let mut out = Vec::new();
+    solve_puzzle(&mut session, &mut "K1\nA1\nA2\nB2\nC2\nA2\n".as_bytes(), &mut out).unwrap();
+
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("K1 is not on the grid, it runs from A1 to J10"));
+    assert!(out.contains("Row 2 has more marks than ship squares."));
+    assert!(out.trim_end().ends_with("Solved, every ship is marked."));
+  }
+
+  #[test]
+  fn debug_mode_shows_ships() {
+    let (game, out) = play("y\nK1\n");
+    assert!(out.contains("Aircraft Carrier"));
+    assert!(out.contains("K1 is not on the grid, it runs from A1 to J10"));
+    assert!(out.trim_end().ends_with("Game saved as save-1."));
+    assert!(!game.get_play().unwrap().get_session_as_ref().is_over());
+  }
+}
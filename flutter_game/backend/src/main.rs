@@ -1,3 +1,260 @@
-fn main() {
-    println!("Hello, world!");
+use std::collections::HashMap;
+#[cfg(not(feature = "sqlite"))]
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use battleship_game_lib::notation::NotationError;
+use battleship_game_lib::rules::{self, GameRules};
+use battleship_game_lib::runtime::{GridPoint, Play, Session, ShotStatus};
+#[cfg(feature = "sqlite")]
+use battleship_game_lib::save::{self, SaveError};
+#[cfg(feature = "sqlite")]
+use battleship_game_lib::sqlite_store::{SqliteStore, StoreError};
+#[cfg(not(feature = "sqlite"))]
+use battleship_game_lib::storage::Store;
+use tonic::{transport::Server, Request, Response, Status};
+
+pub mod proto {
+    tonic::include_proto!("battleship");
+}
+
+use proto::battleship_server::{Battleship, BattleshipServer};
+use proto::{DistanceMetric, Opening, PlayerStatsRequest, PlayerStatsResponse, ShootRequest, ShootResponse, ShotsToSink, StartGameRequest, StartGameResponse, TickRequest, TickResponse};
+
+/// Number of favourite opening squares reported
+const FAVOURITE_OPENINGS: usize = 3;
+
+/// Database used when `BATTLESHIP_DB` is not set
+#[cfg(feature = "sqlite")]
+const DEFAULT_DATABASE: &str = "battleship.db";
+
+/// Reasons a move can not be made
+enum GameError {
+    /// There is no game with this id
+    NoSuchGame,
+    /// The game could not be loaded from or saved to the database
+    #[cfg(feature = "sqlite")]
+    Store(StoreError),
+}
+
+impl From<GameError> for Status {
+    fn from(err: GameError) -> Status {
+        match err {
+            GameError::NoSuchGame => Status::not_found("no such game"),
+            #[cfg(feature = "sqlite")]
+            GameError::Store(err) => Status::internal(err.to_string()),
+        }
+    }
+}
+
+/// Games in progress, keyed by game id, and the statistics of finished games.
+/// With the `sqlite` feature every move is saved to the database, a game id is the id of its save,
+/// and games are only kept in memory until they are over.
+pub struct BattleshipService {
+    games: Mutex<HashMap<String, Play>>,
+    #[cfg(not(feature = "sqlite"))]
+    next_id: AtomicU64,
+    #[cfg(not(feature = "sqlite"))]
+    store: Mutex<Store>,
+    #[cfg(feature = "sqlite")]
+    store: Mutex<SqliteStore>,
+}
+
+#[cfg(not(feature = "sqlite"))]
+impl BattleshipService {
+    fn new(store: Store) -> BattleshipService {
+        BattleshipService { games: Mutex::default(), next_id: AtomicU64::default(), store: Mutex::new(store) }
+    }
+
+    /// Keep a new game
+    /// Return: its game id
+    fn add_game(&self, session: Session) -> Result<String, GameError> {
+        let game_id = self.next_id.fetch_add(1, Ordering::Relaxed).to_string();
+        self.games.lock().unwrap().insert(game_id.clone(), Play::init_with_session(session));
+        Ok(game_id)
+    }
+
+    /// Make a move in the game `game_id` with `action`, counting the game towards the statistics of its player once it ends
+    fn play<T>(&self, game_id: &str, action: impl FnOnce(&mut Session) -> T) -> Result<T, GameError> {
+        let mut games = self.games.lock().unwrap();
+        let session = games.get_mut(game_id).ok_or(GameError::NoSuchGame)?.get_session_as_mut();
+        let was_over = session.is_over();
+        let result = action(session);
+        if session.is_over() && !was_over {
+            self.store.lock().unwrap().record_game(session);
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl BattleshipService {
+    fn new(store: SqliteStore) -> BattleshipService {
+        BattleshipService { games: Mutex::default(), store: Mutex::new(store) }
+    }
+
+    /// Save a new game to the database
+    /// Return: its game id, the id of the save
+    fn add_game(&self, session: Session) -> Result<String, GameError> {
+        let mut store = self.store.lock().unwrap();
+        let game_id = store.save_progress(&Play::init_with_session(session)).map_err(GameError::Store)?;
+        // loaded back so the play knows its save
+        let play = store.get_play(&game_id).map_err(GameError::Store)?;
+        self.games.lock().unwrap().insert(game_id.clone(), play);
+        Ok(game_id)
+    }
+
+    /// Make a move in the game `game_id` with `action` and save it. A game that is not in memory, after a restart
+    /// for example, is loaded from the database. A game that has just ended is saved with its score, counted towards
+    /// the statistics of its player and dropped from memory.
+    fn play<T>(&self, game_id: &str, action: impl FnOnce(&mut Session) -> T) -> Result<T, GameError> {
+        let mut games = self.games.lock().unwrap();
+        let mut store = self.store.lock().unwrap();
+        let mut play = match games.remove(game_id) {
+            Some(play) => play,
+            None => store.get_play(game_id).map_err(|err| match err {
+                StoreError::Save(SaveError::NoSuchSave(_)) => GameError::NoSuchGame,
+                err => GameError::Store(err),
+            })?,
+        };
+
+        let session = play.get_session_as_mut();
+        let was_over = session.is_over();
+        let result = action(session);
+        let session = play.get_session_as_ref();
+        let saved = match (was_over, session.is_over()) {
+            (false, true) => store.save_play(&play).and_then(|_| store.record_game(session)).map(|_| ()),
+            (false, false) => store.save_progress(&play).map(|_| ()),
+            (true, _) => Ok(()),
+        };
+        match saved {
+            Ok(()) if session.is_over() => (),
+            Ok(()) => {
+                games.insert(game_id.to_string(), play);
+            },
+            // kept in memory so the move is not lost
+            Err(err) => {
+                eprintln!("Could not save game {} of {}: {}", game_id, session.get_player_name(), err);
+                games.insert(game_id.to_string(), play);
+            },
+        }
+        Ok(result)
+    }
+}
+
+#[tonic::async_trait]
+impl Battleship for BattleshipService {
+    async fn start_game(&self, request: Request<StartGameRequest>) -> Result<Response<StartGameResponse>, Status> {
+        let request = request.into_inner();
+        let mut rules = GameRules::classic();
+        rules.miss_distance = match request.miss_distance() {
+            DistanceMetric::None => None,
+            DistanceMetric::Manhattan => Some(rules::DistanceMetric::Manhattan),
+            DistanceMetric::Chebyshev => Some(rules::DistanceMetric::Chebyshev),
+        };
+        rules.shot_time_limit = request.shot_time_limit;
+        rules.total_time_limit = request.total_time_limit;
+
+        let session = Session::start_with_rules(request.player_name, rules);
+        let remaining_shots = session.get_remaining_shots();
+        let game_id = self.add_game(session)?;
+
+        Ok(Response::new(StartGameResponse { game_id, remaining_shots }))
+    }
+
+    async fn shoot(&self, request: Request<ShootRequest>) -> Result<Response<ShootResponse>, Status> {
+        let request = request.into_inner();
+        let square = if request.square.is_empty() {
+            GridPoint::new(request.x, request.y)
+        } else {
+            request.square.parse().map_err(|err: NotationError| Status::invalid_argument(err.to_string()))?
+        };
+
+        let (forfeited, shot, remaining_shots, score, game_over) = self.play(&request.game_id, |session| {
+            // shots that timed out since the last request are forfeited first
+            let forfeited = session.tick();
+            let shot = session.shoot_ship(square);
+            (forfeited, shot, session.get_remaining_shots(), session.get_points(), session.is_over())
+        })?;
+        let status = match shot.status {
+            ShotStatus::Hit => proto::ShotStatus::Hit,
+            ShotStatus::Miss => proto::ShotStatus::Miss,
+            ShotStatus::Repeat => proto::ShotStatus::Repeat,
+            ShotStatus::Blocked => proto::ShotStatus::Blocked,
+            ShotStatus::Over => return Err(Status::failed_precondition("the game is over")),
+            ShotStatus::OffBoard => return Err(Status::invalid_argument("the square is not on the grid")),
+            ShotStatus::Salvo => return Err(Status::failed_precondition("the game is played in salvos")),
+        };
+
+        Ok(Response::new(ShootResponse {
+            status: status.into(),
+            ship_type: shot.ship_type.map(|ship_type| format!("{:?}", ship_type)),
+            points: shot.point,
+            distance: shot.distance,
+            remaining_shots,
+            score,
+            game_over,
+            forfeited,
+        }))
+    }
+
+    async fn tick(&self, request: Request<TickRequest>) -> Result<Response<TickResponse>, Status> {
+        let request = request.into_inner();
+        let response = self.play(&request.game_id, |session| TickResponse {
+            forfeited: session.tick(),
+            remaining_shots: session.get_remaining_shots(),
+            score: session.get_points(),
+            shot_time_left: session.get_shot_time_left(),
+            time_used: session.get_time_used(),
+            game_over: session.is_over(),
+        })?;
+        Ok(Response::new(response))
+    }
+
+    async fn get_player_stats(&self, request: Request<PlayerStatsRequest>) -> Result<Response<PlayerStatsResponse>, Status> {
+        let stats = self.store.lock().unwrap().get_stats(&request.into_inner().player_name);
+        #[cfg(feature = "sqlite")]
+        let stats = stats.map_err(|err| Status::internal(err.to_string()))?;
+        Ok(Response::new(PlayerStatsResponse {
+            games_played: stats.get_games_played(),
+            wins: stats.get_wins(),
+            average_points: stats.get_average_points(),
+            accuracy: stats.get_accuracy(),
+            longest_streak: stats.get_longest_streak(),
+            shots_to_sink: stats.get_sink_counts().iter().map(|count| ShotsToSink {
+                ship_type: format!("{:?}", count.ship_type),
+                average_shots: f64::from(count.shots) / f64::from(count.sinks),
+            }).collect(),
+            favourite_openings: stats.get_favourite_openings(FAVOURITE_OPENINGS).iter().map(|opening| Opening {
+                x: opening.point.x,
+                y: opening.point.y,
+                games: opening.games,
+            }).collect(),
+        }))
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let address = "[::1]:50051".parse()?;
+
+    #[cfg(not(feature = "sqlite"))]
+    let store = Store::build();
+    #[cfg(feature = "sqlite")]
+    let store = {
+        let path = std::env::var("BATTLESHIP_DB").unwrap_or_else(|_| String::from(DEFAULT_DATABASE));
+        // saves signed with a generated key can not be loaded once the server restarts
+        let save_key = std::env::var("BATTLESHIP_SAVE_KEY").map(String::into_bytes).unwrap_or_else(|_| save::generate_key());
+        let store = SqliteStore::open(&path, save_key)?;
+        println!("Using database {} at schema version {}", path, store.get_schema_version()?);
+        store
+    };
+    println!("Battleship server listening on {}", address);
+
+    Server::builder()
+        .add_service(BattleshipServer::new(BattleshipService::new(store)))
+        .serve(address)
+        .await?;
+
+    Ok(())
 }